@@ -0,0 +1,51 @@
+//! Measures the read path shared by every bound call, descriptor lookup and binding a `GlobalRef` to a thread, as more
+//! attached threads contend for it.
+//!
+//! Run with `cargo bench --bench descriptors`. Each thread count reports the average time per iteration on each thread,
+//! which should stay roughly flat while there are at least as many cores as threads.
+
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+use yajnir::jref::{AsRawObject, RichJavaType};
+use yajnir::jvm::{JavaVM, JniVersion, VmOptions};
+use yajnir::lang::{JObject, JThread};
+use yajnir::util::JPreferences;
+
+const ITERATIONS: u32 = 1_000_000;
+const THREADS: &[usize] = &[1, 2, 4, 8, 16];
+
+fn main() {
+	let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+	let prefs = JPreferences::user_root(env).expect("error getting preferences")
+		.to_global_send().expect("error creating global ref");
+	let prefs = Arc::new(prefs);
+
+	for &threads in THREADS {
+		let barrier = Arc::new(Barrier::new(threads));
+		let workers: Vec<_> = (0..threads).map(|_| {
+			let barrier = Arc::clone(&barrier);
+			let prefs = Arc::clone(&prefs);
+			std::thread::spawn(move || -> Duration {
+				vm.with_env(|env| {
+					barrier.wait();
+					let started = Instant::now();
+					for _ in 0..ITERATIONS {
+						std::hint::black_box(JThread::descriptors(env));
+						std::hint::black_box(JObject::descriptors(env));
+						let raw = prefs.with(env, |obj| obj.as_raw()).expect("reference to be live");
+						std::hint::black_box(raw);
+					}
+					started.elapsed()
+				}).expect("error attaching thread")
+			})
+		}).collect();
+
+		let total: Duration = workers.into_iter().map(|w| w.join().expect("bench thread panicked")).sum();
+		let per_iter = total / (ITERATIONS * threads as u32);
+		println!("{:>2} threads: {:>6.1?} per iteration", threads, per_iter);
+	}
+
+	drop(prefs);
+	vm.destroy().expect("error destroying vm");
+}
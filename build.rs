@@ -0,0 +1,125 @@
+//! Generates the `yajnir::introspect` contract table from the `Contract:` lines in the wrappers' doc comments.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const THREADS: &[(&str, &str)] = &[("any", "Any"), ("attached", "Attached")];
+const EXCEPTIONS: &[(&str, &str)] = &[("unaffected", "Unaffected"), ("reported", "Reported"), ("throws", "Throws"), ("inspects", "Inspects")];
+const FLAGS: &[&str] = &["pending", "critical"];
+
+fn main() {
+	println!("cargo:rerun-if-changed=src");
+
+	let mut contracts = Vec::new();
+	scan_dir(Path::new("src"), &mut contracts);
+	contracts.sort();
+	for pair in contracts.windows(2) {
+		if pair[0].0 == pair[1].0 {
+			panic!("{} has more than one contract", pair[0].0);
+		}
+	}
+
+	let mut table = String::from("contracts! {\n");
+	for (name, entry) in &contracts {
+		writeln!(table, "\t{:?} => {};", name, entry).unwrap();
+	}
+	table.push('}');
+
+	let out = Path::new(&std::env::var_os("OUT_DIR").expect("OUT_DIR to be set by cargo")).join("contracts.rs");
+	fs::write(out, table).expect("error writing the contract table");
+}
+
+fn scan_dir(dir: &Path, contracts: &mut Vec<(String, String)>) {
+	let mut entries: Vec<_> = fs::read_dir(dir).expect("error listing sources").map(|e| e.expect("error listing sources").path()).collect();
+	entries.sort();
+	for path in entries {
+		if path.is_dir() {
+			scan_dir(&path, contracts);
+		} else if path.extension().is_some_and(|ext| ext == "rs") {
+			scan_file(&path, contracts);
+		}
+	}
+}
+
+/// Finds `pub fn`s whose doc comment has a `Contract:` line, naming them by their `impl` type or, for free functions,
+/// their file's module.
+fn scan_file(path: &Path, contracts: &mut Vec<(String, String)>) {
+	let source = fs::read_to_string(path).expect("error reading source");
+	let module = path.file_stem().unwrap().to_string_lossy().into_owned();
+	let mut owner: Option<String> = None;
+	let mut contract: Option<String> = None;
+
+	for (i, line) in source.lines().enumerate() {
+		let trimmed = line.trim_start();
+		if line.starts_with("impl ") || line.starts_with("impl<") {
+			owner = Some(impl_type(line));
+		} else if line.starts_with('}') {
+			owner = None;
+		}
+
+		if let Some(doc) = trimmed.strip_prefix("///") {
+			if let Some(spec) = doc.trim().strip_prefix("Contract:") {
+				let entry = parse(spec).unwrap_or_else(|e| panic!("{}:{}: {}", path.display(), i + 1, e));
+				contract = Some(entry);
+			}
+			continue;
+		}
+		if trimmed.starts_with("#[") {
+			continue;
+		}
+
+		if let Some(entry) = contract.take() {
+			let name = trimmed.strip_prefix("pub fn ").or_else(|| trimmed.strip_prefix("pub unsafe fn "))
+				.and_then(|rest| rest.split(|c: char| !(c.is_alphanumeric() || c == '_')).next())
+				.unwrap_or_else(|| panic!("{}:{}: a Contract: line must document a pub fn", path.display(), i + 1));
+			let qualified = match (&owner, line.starts_with('\t')) {
+				(Some(owner), true) => format!("{}::{}", owner, name),
+				_ => format!("{}::{}", module, name),
+			};
+			contracts.push((qualified, entry));
+		}
+	}
+}
+
+/// Returns the type an `impl` line is for, such as `JniEnv` for `impl<'a> JniEnv<'a> {`
+fn impl_type(line: &str) -> String {
+	let mut rest = &line["impl".len()..];
+	if rest.starts_with('<') {
+		let mut depth = 0;
+		for (i, c) in rest.char_indices() {
+			match c {
+				'<' => depth += 1,
+				'>' => depth -= 1,
+				_ => {},
+			}
+			if depth == 0 {
+				rest = &rest[i + 1..];
+				break;
+			}
+		}
+	}
+	if let Some((_, target)) = rest.split_once(" for ") {
+		rest = target;
+	}
+	rest.trim_start().split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or_default().to_owned()
+}
+
+/// Parses `attached, reported, pending` into the `contracts!` macro's `Attached, Reported, pending`
+fn parse(spec: &str) -> Result<String, String> {
+	fn lookup(table: &[(&str, &'static str)], word: Option<&&str>, what: &str) -> Result<&'static str, String> {
+		let word = word.ok_or_else(|| format!("missing {}", what))?;
+		table.iter().find(|(key, _)| key == word).map(|(_, variant)| *variant)
+			.ok_or_else(|| format!("unknown {} {:?}", what, word))
+	}
+
+	let words: Vec<&str> = spec.split(',').map(str::trim).collect();
+	let mut entry = format!("{}, {}", lookup(THREADS, words.first(), "thread requirement")?, lookup(EXCEPTIONS, words.get(1), "exception behavior")?);
+	for flag in words.iter().skip(2) {
+		if !FLAGS.contains(flag) {
+			return Err(format!("unknown contract flag {:?}", flag));
+		}
+		write!(entry, ", {}", flag).unwrap();
+	}
+	Ok(entry)
+}
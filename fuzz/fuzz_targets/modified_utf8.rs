@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use yajnir::fuzzing::java_cstr;
+
+fuzz_target!(|data: &[u8]| {
+	// decoding arbitrary (JVM-provided) bytes must never panic, and must be stable once re-encoded
+	// (the decoder accepts some non-canonical input, such as raw null bytes, so the bytes themselves may differ)
+	if let Ok(decoded) = cesu8::from_java_cesu8(data) {
+		let reencoded = cesu8::to_java_cesu8(&decoded);
+		assert_eq!(decoded, cesu8::from_java_cesu8(&reencoded).expect("re-encoded string to decode"));
+	}
+
+	// strings passed to JNI must be null-terminated with no interior nulls, and decode back to the original
+	if let Ok(s) = std::str::from_utf8(data) {
+		let encoded = java_cstr(s);
+		let (last, body) = encoded.split_last().expect("encoded strings to be null-terminated");
+		assert_eq!(0, *last);
+		assert!(!body.contains(&0), "interior null in {:?}", encoded);
+		assert_eq!(s, cesu8::from_java_cesu8(body).expect("encoded string to decode"));
+	}
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use yajnir::fuzzing::native_name;
+
+// Input is split on newlines into the class name, method name and (optional) overload signature
+fuzz_target!(|data: &[u8]| {
+	let input = match std::str::from_utf8(data) {
+		Ok(s) => s,
+		Err(_) => return,
+	};
+	let mut parts = input.splitn(3, '\n');
+	let class = parts.next().unwrap_or("");
+	let method = parts.next().unwrap_or("");
+	let sig = parts.next();
+
+	if let Some(mangled) = native_name(class, method, sig) {
+		assert!(mangled.starts_with("Java_"));
+		// mangled names must be plain C identifiers
+		assert!(mangled.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'), "non-identifier output {:?}", mangled);
+	}
+});
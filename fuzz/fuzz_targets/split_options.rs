@@ -0,0 +1,29 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use yajnir::fuzzing::split_options;
+
+fuzz_target!(|data: &[u8]| {
+	let input = match std::str::from_utf8(data) {
+		Ok(s) => s,
+		Err(_) => return,
+	};
+	let opts = match split_options(input) {
+		Ok(opts) => opts,
+		Err(_) => {
+			assert!(input.contains('"') || input.contains('\''), "rejected unquoted {:?}", input);
+			return;
+		},
+	};
+
+	// without quotes, options are exactly the whitespace separated words
+	if !input.contains('"') && !input.contains('\'') {
+		assert_eq!(input.split_whitespace().collect::<Vec<_>>(), opts);
+	}
+
+	// quoting each option again must split back into the same options
+	if opts.iter().all(|opt| !opt.contains('"')) {
+		let requoted: Vec<String> = opts.iter().map(|opt| format!("\"{}\"", opt)).collect();
+		assert_eq!(Ok(opts), split_options(&requoted.join(" ")));
+	}
+});
@@ -0,0 +1,92 @@
+//! Running inside Android apps, where ART hands its VM to native code instead of letting native code create one.
+//!
+//! Only compiled with the `android` feature enabled. When targeting Android, the invocation API is disabled, as ART
+//! doesn't export it to apps: [`JavaVM::create`] and [`JavaVM::destroy`] return
+//! [`VmError::InvocationUnavailable`], and [`JavaVM::created_jvms`] returns the captured VM. Capture it from the
+//! library's `JNI_OnLoad`:
+//!
+//! ```no_run
+//! # use jni_sys as js;
+//! #[no_mangle]
+//! pub unsafe extern "system" fn JNI_OnLoad(vm: *mut js::JavaVM, _reserved: *mut std::ffi::c_void) -> js::jint {
+//!     yajnir::android::on_load(vm)
+//! }
+//! ```
+//!
+//! Apps built on `android-activity` or similar glue crates can instead enable the `ndk-context` feature, which lets
+//! [`jvm::global`](crate::jvm::global) find the VM on its own.
+//!
+//! # Threads
+//!
+//! ART is stricter about attached threads than desktop JVMs:
+//! - Native threads must detach before they exit, or ART aborts the process. Attach them with
+//!   [`JavaVM::attach_current_thread`] or [`JavaVM::attach_permanently`], which both detach for you.
+//! - Threads that called in from Java must never be detached. The crate's attachments leave threads that were already
+//!   attached as they were.
+//! - `FindClass` on a thread attached from native code searches the system class loader, which can't see the app's
+//!   classes. Look those up from `JNI_OnLoad` or a native method, and keep them in a
+//!   [`GlobalRef`](crate::jref::GlobalRef).
+
+use std::ptr::NonNull;
+
+use jni_sys as js;
+
+use crate::jvm::JavaVM;
+#[cfg(doc)]
+use crate::jvm::VmError;
+
+/// Makes `vm` the global JavaVM returned by [`jvm::global`](crate::jvm::global), returning whichever VM is global.
+///
+/// Returns `None` if `vm` is null. If a VM was captured already, it is kept.
+///
+/// # Safety
+/// `vm` must be null or point to the process's live JavaVM, such as the one passed to `JNI_OnLoad`.
+pub unsafe fn capture(vm: *mut js::JavaVM) -> Option<JavaVM> {
+	let vm = JavaVM { ptr: NonNull::new(vm)? };
+	Some(crate::jvm::set_global(vm))
+}
+
+/// Captures `vm` as with [`capture`], returning the JNI version to report from `JNI_OnLoad`.
+///
+/// # Safety
+/// `vm` must be the JavaVM passed to `JNI_OnLoad`.
+pub unsafe fn on_load(vm: *mut js::JavaVM) -> js::jint {
+	if capture(vm).is_none() {
+		log::error!("JNI_OnLoad was called with a null JavaVM");
+		return js::JNI_ERR;
+	}
+	js::JNI_VERSION_1_6
+}
+
+/// Returns the VM `ndk-context` was initialized with, checking only on the first call.
+///
+/// `ndk-context` panics when it hasn't been initialized, so this must first be called (usually through
+/// [`jvm::global`](crate::jvm::global)) once the app's glue crate has set it up.
+#[cfg(feature = "ndk-context")]
+pub(crate) fn from_ndk_context() -> Option<JavaVM> {
+	static VM: once_cell::sync::OnceCell<Option<JavaVM>> = once_cell::sync::OnceCell::new();
+	*VM.get_or_init(|| {
+		let vm = std::panic::catch_unwind(|| ndk_context::android_context().vm()).ok()?;
+		NonNull::new(vm as *mut js::JavaVM).map(|ptr| JavaVM { ptr })
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn captures_global_vm() {
+			assert!(unsafe { super::capture(std::ptr::null_mut()) }.is_none());
+
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert_eq!(jni_sys::JNI_VERSION_1_6, unsafe { super::on_load(vm.ptr.as_ptr()) });
+			let global = crate::jvm::global().expect("vm to be captured");
+			assert_eq!(vm.ptr, global.ptr);
+			global.with_env(|env| env.exception_check().is_ok()).expect("error attaching through global vm");
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -0,0 +1,249 @@
+//! Wrappers for Java arrays of primitive types.
+
+use std::marker::PhantomData;
+
+use jni_sys as js;
+
+use crate::env::{JniEnv, JniError};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, JavaClass, RawJObject, RichJavaType};
+use crate::to_jsize;
+
+/// A Java primitive type that can be stored within a [`JPrimitiveArray`]
+pub trait JavaPrimitive: Copy + Default + 'static {
+	/// The internal name of an array of this type, such as `[I`
+	const ARRAY_CLASS: &'static str;
+
+	#[doc(hidden)]
+	fn new_array(env: JniEnv<'_>, len: js::jsize) -> Result<js::jarray, JniError>;
+	#[doc(hidden)]
+	fn get_region(env: JniEnv<'_>, array: RawJObject, start: js::jsize, buf: &mut [Self]) -> Result<(), JniError>;
+	#[doc(hidden)]
+	fn set_region(env: JniEnv<'_>, array: RawJObject, start: js::jsize, buf: &[Self]) -> Result<(), JniError>;
+}
+
+macro_rules! impl_java_primitive {
+	($($ty:ty => $class:literal, $new:ident, $get:ident, $set:ident;)*) => {$(
+		impl JavaPrimitive for $ty {
+			const ARRAY_CLASS: &'static str = $class;
+
+			fn new_array(env: JniEnv<'_>, len: js::jsize) -> Result<js::jarray, JniError> {
+				let array = jni_unchecked!(env, $new, len);
+				env.exception_check()?;
+				Ok(array)
+			}
+			fn get_region(env: JniEnv<'_>, array: RawJObject, start: js::jsize, buf: &mut [Self]) -> Result<(), JniError> {
+				jni_unchecked!(env, $get, array.as_ptr(), start, to_jsize(buf.len())?, buf.as_mut_ptr());
+				env.exception_check()
+			}
+			fn set_region(env: JniEnv<'_>, array: RawJObject, start: js::jsize, buf: &[Self]) -> Result<(), JniError> {
+				jni_unchecked!(env, $set, array.as_ptr(), start, to_jsize(buf.len())?, buf.as_ptr());
+				env.exception_check()
+			}
+		}
+	)*};
+}
+impl_java_primitive! {
+	js::jboolean => "[Z", NewBooleanArray, GetBooleanArrayRegion, SetBooleanArrayRegion;
+	js::jbyte => "[B", NewByteArray, GetByteArrayRegion, SetByteArrayRegion;
+	js::jchar => "[C", NewCharArray, GetCharArrayRegion, SetCharArrayRegion;
+	js::jshort => "[S", NewShortArray, GetShortArrayRegion, SetShortArrayRegion;
+	js::jint => "[I", NewIntArray, GetIntArrayRegion, SetIntArrayRegion;
+	js::jlong => "[J", NewLongArray, GetLongArrayRegion, SetLongArrayRegion;
+	js::jfloat => "[F", NewFloatArray, GetFloatArrayRegion, SetFloatArrayRegion;
+	js::jdouble => "[D", NewDoubleArray, GetDoubleArrayRegion, SetDoubleArrayRegion;
+}
+
+pub(crate) fn as_jbytes(buf: &[u8]) -> &[js::jbyte] {
+	// SAFETY: u8 and i8 have the same size and alignment, and every bit pattern is valid for both
+	unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const js::jbyte, buf.len()) }
+}
+
+pub(crate) fn as_jbytes_mut(buf: &mut [u8]) -> &mut [js::jbyte] {
+	// SAFETY: u8 and i8 have the same size and alignment, and every bit pattern is valid for both
+	unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut js::jbyte, buf.len()) }
+}
+
+/// A Java array of primitive `T`, such as `int[]`
+#[derive(Debug)]
+pub struct JPrimitiveArray<T: JavaPrimitive>(PhantomData<T>);
+impl<T: JavaPrimitive> RichJavaType for JPrimitiveArray<T> {
+	type IDs = ();
+
+	fn descriptors<'thread>(_env: JniEnv<'thread>) -> &'static () {
+		&()
+	}
+}
+impl<T: JavaPrimitive> JavaClass for JPrimitiveArray<T> {
+	const INTERNAL_NAME: &'static str = T::ARRAY_CLASS;
+}
+
+impl<T: JavaPrimitive> JPrimitiveArray<T> {
+	/// Creates a new, zeroed array of the given length.
+	///
+	/// Contract: attached, reported
+	pub fn new(env: JniEnv<'_>, len: usize) -> Result<AutoObj<'_, JPrimitiveArray<T>>, JniError> {
+		let array = T::new_array(env, to_jsize(len)?)?;
+		let array = std::ptr::NonNull::new(array).ok_or(JniError::NullPointer("New<Type>Array"))?;
+		Ok(AutoObj::from_raw(env, array))
+	}
+
+	/// Creates a new array holding a copy of `buf`.
+	///
+	/// Contract: attached, reported
+	pub fn from_slice<'a>(env: JniEnv<'a>, buf: &[T]) -> Result<AutoObj<'a, JPrimitiveArray<T>>, JniError> {
+		let array = JPrimitiveArray::new(env, buf.len())?;
+		T::set_region(env, array.as_raw_nonnull(), 0, buf)?;
+		Ok(array)
+	}
+
+	/// Returns the length of the array.
+	///
+	/// Contract: attached, unaffected
+	pub fn len<R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R) -> Result<usize, JniError> {
+		env.array_length(raw_nonnull(array))
+	}
+
+	/// Copies elements starting at index `start` into `buf`, filling it completely.
+	///
+	/// Contract: attached, reported
+	pub fn read_region<R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R, start: usize, buf: &mut [T]) -> Result<(), JniError> {
+		T::get_region(env, raw_nonnull(array), to_jsize(start)?, buf)
+	}
+
+	/// Copies all of `buf` into the array, starting at index `start`.
+	///
+	/// Contract: attached, reported
+	pub fn write_region<R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R, start: usize, buf: &[T]) -> Result<(), JniError> {
+		T::set_region(env, raw_nonnull(array), to_jsize(start)?, buf)
+	}
+
+	/// Copies the entire array into a Rust array without allocating, verifying that it has exactly `N` elements.
+	///
+	/// ```no_run
+	/// # use jni_sys as js;
+	/// # use yajnir::{array::JPrimitiveArray, env::{JniEnv, JniError}, jref::AutoObj};
+	/// # fn example(env: JniEnv<'_>, pixel: AutoObj<'_, JPrimitiveArray<js::jbyte>>) -> Result<(), JniError> {
+	/// let rgba: [js::jbyte; 4] = JPrimitiveArray::read_fixed(env, &pixel)?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	///
+	/// Contract: attached, reported
+	pub fn read_fixed<const N: usize, R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R) -> Result<[T; N], JniError> {
+		JPrimitiveArray::check_len(env, array, N)?;
+		let mut buf = [T::default(); N];
+		T::get_region(env, raw_nonnull(array), 0, &mut buf)?;
+		Ok(buf)
+	}
+
+	/// Overwrites the entire array from a Rust array, verifying that it has exactly `N` elements.
+	///
+	/// Contract: attached, reported
+	pub fn write_fixed<const N: usize, R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R, buf: &[T; N]) -> Result<(), JniError> {
+		JPrimitiveArray::check_len(env, array, N)?;
+		T::set_region(env, raw_nonnull(array), 0, buf)
+	}
+
+	/// Runs `func` with direct access to the array's elements, using `GetPrimitiveArrayCritical`.
+	///
+	/// This avoids a copy on most JVMs, but the garbage collector may be paused until `func` returns, so it should
+	/// be short and must not block on other Java threads. Changes to the slice are written back to the array.
+	///
+	/// # Safety
+	///
+	/// `func` must not use JNI in any way, on this thread, until it returns, including through a captured
+	/// [`JniEnv`] or anything it calls. It must also not panic while [`crate::panic_hook`] is installed, as the hook
+	/// calls into Java before the array is released. Either may deadlock the garbage collector.
+	///
+	/// Contract: attached, reported, critical
+	pub unsafe fn with_critical<O, R, F>(env: JniEnv<'_>, array: &R, func: F) -> Result<O, JniError>
+		where R: AsRawObject<Self>, F: FnOnce(&mut [T]) -> O
+	{
+		let raw = raw_nonnull(array);
+		let len = env.array_length(raw)?;
+		let elems = jni_unchecked!(env, GetPrimitiveArrayCritical, raw.as_ptr(), std::ptr::null_mut());
+		if elems.is_null() {
+			env.exception_check()?;
+			return Err(JniError::NullPointer("GetPrimitiveArrayCritical"));
+		}
+
+		struct Release<'e> {
+			env: JniEnv<'e>,
+			array: RawJObject,
+			elems: *mut std::ffi::c_void,
+		}
+		impl Drop for Release<'_> {
+			fn drop(&mut self) {
+				// mode 0: copy back the contents (if needed) and free the buffer
+				let res: Result<(), JniError> = (|| {
+					jni_unchecked!(self.env, ReleasePrimitiveArrayCritical, self.array.as_ptr(), self.elems, 0);
+					Ok(())
+				})();
+				if let Err(e) = res {
+					log::error!("unable to release critical array: {}", e);
+				}
+			}
+		}
+		let _release = Release { env, array: raw, elems };
+
+		// SAFETY: the JVM returned a valid pointer to `len` elements of T, pinned until released
+		let slice = std::slice::from_raw_parts_mut(elems as *mut T, len);
+		Ok(func(slice))
+	}
+
+	fn check_len<R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R, expected: usize) -> Result<(), JniError> {
+		let actual = JPrimitiveArray::len(env, array)?;
+		if actual != expected {
+			return Err(JniError::LengthMismatch { expected, actual });
+		}
+		Ok(())
+	}
+}
+
+impl JPrimitiveArray<js::jbyte> {
+	/// Creates a new `byte[]` holding a copy of `buf`.
+	///
+	/// Contract: attached, reported
+	pub fn from_bytes<'a>(env: JniEnv<'a>, buf: &[u8]) -> Result<AutoObj<'a, JPrimitiveArray<js::jbyte>>, JniError> {
+		JPrimitiveArray::from_slice(env, as_jbytes(buf))
+	}
+
+	/// Copies an entire `byte[]` into a new `Vec<u8>`.
+	///
+	/// Contract: attached, reported
+	pub fn to_bytes<R: AsRawObject<Self>>(env: JniEnv<'_>, array: &R) -> Result<Vec<u8>, JniError> {
+		let mut buf = vec![0; JPrimitiveArray::len(env, array)?];
+		JPrimitiveArray::read_region(env, array, 0, as_jbytes_mut(&mut buf))?;
+		Ok(buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::array::JPrimitiveArray;
+	use crate::env::JniError;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn fixed_size_exchange() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let array = JPrimitiveArray::<i32>::from_slice(env, &[1, 2, 3, 4]).expect("error creating array");
+			let read: [i32; 4] = JPrimitiveArray::read_fixed(env, &array).expect("error reading array");
+			assert_eq!([1, 2, 3, 4], read);
+
+			match JPrimitiveArray::read_fixed::<3, _>(env, &array) {
+				Err(JniError::LengthMismatch { expected: 3, actual: 4 }) => {},
+				other => panic!("expected length mismatch, got {:?}", other),
+			}
+
+			JPrimitiveArray::write_fixed(env, &array, &[5, 6, 7, 8]).expect("error writing array");
+			let mut partial = [0; 2];
+			JPrimitiveArray::read_region(env, &array, 2, &mut partial).expect("error reading region");
+			assert_eq!([7, 8], partial);
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
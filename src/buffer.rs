@@ -0,0 +1,307 @@
+//! Reusable buffers for repeated transfers of Java array contents.
+//!
+//! [`JPrimitiveArray::with_critical`](crate::array::JPrimitiveArray::with_critical) avoids copies, but may stall the
+//! garbage collector for as long as it is held. These helpers copy instead, while reusing allocations so that chatty
+//! interop loops don't allocate on every call.
+
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use jni_sys as js;
+
+use crate::array::{JavaPrimitive, JPrimitiveArray};
+use crate::env::{JniEnv, JniError};
+use crate::jref::{raw_nonnull, AsRawObject, GlobalRef, RawJObject};
+
+/// Buffers are pooled by the next power of two of their length, so similarly sized requests share allocations.
+fn size_class(len: usize) -> usize {
+	len.checked_next_power_of_two().unwrap_or(len)
+}
+
+/// A pool of Rust-side buffers, grouped by size class.
+#[derive(Debug)]
+pub struct BufferPool<T> {
+	classes: Mutex<HashMap<usize, Vec<Vec<T>>>>,
+	max_per_class: usize,
+}
+
+impl<T: JavaPrimitive> BufferPool<T> {
+	/// Creates an empty pool that keeps at most `max_per_class` idle buffers of each size class.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn new(max_per_class: usize) -> BufferPool<T> {
+		BufferPool {
+			classes: Mutex::new(HashMap::new()),
+			max_per_class,
+		}
+	}
+
+	/// Takes a buffer of exactly `len` default elements from the pool, allocating one if none are idle.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn take(&self, len: usize) -> Vec<T> {
+		let class = size_class(len);
+		let pooled = self.classes.lock().unwrap_or_else(|e| e.into_inner())
+			.get_mut(&class)
+			.and_then(Vec::pop);
+
+		let mut buf = pooled.unwrap_or_else(|| Vec::with_capacity(class));
+		buf.clear();
+		buf.resize(len, T::default());
+		buf
+	}
+
+	/// Returns a buffer to the pool for reuse. Buffers that don't fit a size class, or exceed the pool's limit, are
+	/// dropped.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn give(&self, buf: Vec<T>) {
+		let class = buf.capacity();
+		if class == 0 || size_class(class) != class {
+			return;
+		}
+		let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+		let idle = classes.entry(class).or_default();
+		if idle.len() < self.max_per_class {
+			idle.push(buf);
+		}
+	}
+
+	/// Returns the number of idle buffers currently held.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn idle(&self) -> usize {
+		self.classes.lock().unwrap_or_else(|e| e.into_inner())
+			.values()
+			.map(Vec::len)
+			.sum()
+	}
+}
+
+/// A copy of a Java array's contents in a pooled buffer.
+///
+/// If the copy is mutated through `DerefMut`, it is written back to the array when dropped. Use
+/// [`PooledCopy::commit`] to write back and observe any error instead.
+#[derive(Debug)]
+pub struct PooledCopy<'r, T: JavaPrimitive> {
+	env: JniEnv<'r>,
+	array: RawJObject,
+	pool: &'r BufferPool<T>,
+	buf: Option<Vec<T>>,
+	dirty: bool,
+}
+
+impl<'r, T: JavaPrimitive> PooledCopy<'r, T> {
+	/// Copies the entire array into a buffer taken from `pool`.
+	///
+	/// Contract: attached, reported
+	pub fn new<R: AsRawObject<JPrimitiveArray<T>>>(env: JniEnv<'r>, array: &'r R, pool: &'r BufferPool<T>) -> Result<PooledCopy<'r, T>, JniError> {
+		let mut buf = pool.take(JPrimitiveArray::len(env, array)?);
+		if let Err(e) = JPrimitiveArray::read_region(env, array, 0, &mut buf) {
+			pool.give(buf);
+			return Err(e);
+		}
+		Ok(PooledCopy { env, array: raw_nonnull(array), pool, buf: Some(buf), dirty: false })
+	}
+
+	fn write_back(&mut self) -> Result<(), JniError> {
+		match &self.buf {
+			Some(buf) if self.dirty => {
+				T::set_region(self.env, self.array, 0, buf)?;
+				self.dirty = false;
+				Ok(())
+			},
+			_ => Ok(()),
+		}
+	}
+
+	/// Writes any changes back to the array, and returns the buffer to the pool.
+	///
+	/// Contract: attached, reported
+	pub fn commit(mut self) -> Result<(), JniError> {
+		self.write_back()
+	}
+
+	/// Returns the buffer to the pool without writing any changes back.
+	pub fn discard(mut self) {
+		self.dirty = false;
+	}
+}
+
+impl<T: JavaPrimitive> Deref for PooledCopy<'_, T> {
+	type Target = [T];
+	fn deref(&self) -> &[T] {
+		self.buf.as_deref().unwrap_or_default()
+	}
+}
+
+impl<T: JavaPrimitive> DerefMut for PooledCopy<'_, T> {
+	fn deref_mut(&mut self) -> &mut [T] {
+		self.dirty = true;
+		self.buf.as_deref_mut().unwrap_or_default()
+	}
+}
+
+impl<T: JavaPrimitive> Drop for PooledCopy<'_, T> {
+	fn drop(&mut self) {
+		if let Err(e) = self.write_back() {
+			log::error!("unable to write pooled copy back to Java array: {}", e);
+		}
+		if let Some(buf) = self.buf.take() {
+			self.pool.give(buf);
+		}
+	}
+}
+
+/// A Java `byte[]` held by a [`ScratchPool`]
+pub type ScratchArray = GlobalRef<JPrimitiveArray<js::jbyte>>;
+
+/// A pool of Java-side `byte[]` scratch buffers, kept alive with global references.
+///
+/// Reusing arrays avoids allocating a new Java array (and creating garbage) for each small transfer. The pool is
+/// `Send + Sync`, so it can be shared between threads or kept in a static.
+#[derive(Debug)]
+pub struct ScratchPool {
+	classes: Mutex<HashMap<usize, Vec<ScratchArray>>>,
+	max_per_class: usize,
+}
+
+impl ScratchPool {
+	/// Creates an empty pool that keeps at most `max_per_class` idle arrays of each size class.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn new(max_per_class: usize) -> ScratchPool {
+		ScratchPool {
+			classes: Mutex::new(HashMap::new()),
+			max_per_class,
+		}
+	}
+
+	/// Runs `func` with a `byte[]` of at least `len` bytes, returning it to the pool afterwards.
+	///
+	/// The array's length is rounded up to a size class, so pass explicit offsets and lengths to Java methods
+	/// rather than relying on `array.length`. Its previous contents are left in place.
+	///
+	/// Contract: attached, reported
+	pub fn with_scratch_buffer<O, F>(&self, env: JniEnv<'_>, len: usize, func: F) -> Result<O, JniError>
+		where F: FnOnce(&ScratchArray) -> O
+	{
+		let class = size_class(len);
+		let pooled = self.classes.lock().unwrap_or_else(|e| e.into_inner())
+			.get_mut(&class)
+			.and_then(Vec::pop);
+
+		let array = match pooled {
+			Some(array) => array,
+			None => {
+				let local = JPrimitiveArray::<js::jbyte>::new(env, class)?;
+				let global = GlobalRef::new(env, local.as_raw_nonnull());
+				drop(local);
+				global?
+			},
+		};
+
+		let res = func(&array);
+
+		let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+		let idle = classes.entry(class).or_default();
+		if idle.len() < self.max_per_class {
+			idle.push(array);
+		} else {
+			// the array's global reference is deleted as it drops, outside the lock
+			drop(classes);
+			drop(array);
+		}
+		Ok(res)
+	}
+
+	/// Releases every idle array held by the pool.
+	///
+	/// Each array's global reference is deleted as it drops, attaching the calling thread for the deletion if it isn't
+	/// already attached.
+	///
+	/// Contract: any, unaffected
+	pub fn clear(&self) {
+		let idle: Vec<_> = self.classes.lock().unwrap_or_else(|e| e.into_inner())
+			.drain()
+			.flat_map(|(_, arrays)| arrays)
+			.collect();
+		drop(idle);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::array::JPrimitiveArray;
+	use crate::buffer::{BufferPool, PooledCopy, ScratchPool};
+	use crate::jref::AsRawObject;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	#[test]
+	fn pool_reuses_size_classes() {
+		let pool = BufferPool::<i32>::new(1);
+		let buf = pool.take(5);
+		assert_eq!(5, buf.len());
+		assert!(buf.capacity() >= 8);
+		let ptr = buf.as_ptr();
+		pool.give(buf);
+		pool.give(vec![0; 3]);
+		assert_eq!(1, pool.idle());
+
+		let again = pool.take(7);
+		assert_eq!(ptr, again.as_ptr());
+		assert!(again.iter().all(|&v| v == 0));
+	}
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn pooled_copy_writes_back() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let pool = BufferPool::new(4);
+			let array = JPrimitiveArray::<i64>::from_slice(env, &[1, 2, 3]).expect("error creating array");
+
+			{
+				let mut copy = PooledCopy::new(env, &array, &pool).expect("error copying array");
+				assert_eq!(&[1, 2, 3], &copy[..]);
+				copy[1] = 20;
+			}
+			let read: [i64; 3] = JPrimitiveArray::read_fixed(env, &array).expect("error reading array");
+			assert_eq!([1, 20, 3], read);
+
+			let mut copy = PooledCopy::new(env, &array, &pool).expect("error copying array");
+			copy[0] = 10;
+			copy.discard();
+			let read: [i64; 3] = JPrimitiveArray::read_fixed(env, &array).expect("error reading array");
+			assert_eq!([1, 20, 3], read);
+			assert_eq!(1, pool.idle());
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn scratch_buffers_are_reused() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let pool = ScratchPool::new(2);
+
+			let first = pool.with_scratch_buffer(env, 100, |array| {
+				assert_eq!(128, JPrimitiveArray::len(env, array).expect("error reading length"));
+				JPrimitiveArray::write_region(env, array, 0, &[1, 2, 3]).expect("error writing array");
+				array.as_raw()
+			}).expect("error using scratch buffer");
+
+			let second = pool.with_scratch_buffer(env, 120, |array| {
+				let mut start = [0; 3];
+				JPrimitiveArray::read_region(env, array, 0, &mut start).expect("error reading array");
+				assert_eq!([1, 2, 3], start);
+				array.as_raw()
+			}).expect("error using scratch buffer");
+			assert_eq!(first, second);
+
+			assert_eq!(1, crate::jref::live_globals());
+			pool.clear();
+			assert_eq!(0, crate::jref::live_globals());
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
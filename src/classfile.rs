@@ -0,0 +1,228 @@
+//! Assembling small class files at runtime, for Java classes whose methods are implemented in Rust.
+
+use std::os::raw::c_char;
+
+use jni_sys as js;
+
+use crate::env::{JniEnv, JniError};
+use crate::jref::GlobalRef;
+use crate::lang::JClass;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constant {
+	Utf8(String),
+	Class(u16),
+	NameAndType(u16, u16),
+	Methodref(u16, u16),
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+	access: u16,
+	name: u16,
+	descriptor: u16,
+	/// max stack, max locals and bytecode, for methods that aren't native or abstract
+	code: Option<(u16, u16, Vec<u8>)>,
+}
+
+/// A class file builder supporting fields, native methods, and methods whose bytecode doesn't branch, so they need no
+/// stack map frames.
+///
+/// Constants are deduplicated, so [`methodref`](Self::methodref) can be called for each use of a method.
+#[derive(Debug, Clone)]
+pub struct ClassFile {
+	name: String,
+	access: u16,
+	this_class: u16,
+	super_class: u16,
+	/// The `Code` attribute name, once a method with bytecode is added
+	code_name: u16,
+	pool: Vec<Constant>,
+	interfaces: Vec<u16>,
+	fields: Vec<Member>,
+	methods: Vec<Member>,
+}
+
+impl ClassFile {
+	pub const ACC_PUBLIC: u16 = 0x0001;
+	pub const ACC_PRIVATE: u16 = 0x0002;
+	#[cfg_attr(not(feature = "jvm-test"), allow(dead_code))]
+	pub const ACC_STATIC: u16 = 0x0008;
+	pub const ACC_FINAL: u16 = 0x0010;
+	pub const ACC_SUPER: u16 = 0x0020;
+	pub const ACC_NATIVE: u16 = 0x0100;
+
+	/// Starts a class with the given access flags, internal name, and superclass internal name.
+	pub fn new(access: u16, name: &str, superclass: &str) -> ClassFile {
+		let mut class = ClassFile {
+			name: name.to_owned(),
+			access,
+			this_class: 0,
+			super_class: 0,
+			code_name: 0,
+			pool: Vec::new(),
+			interfaces: Vec::new(),
+			fields: Vec::new(),
+			methods: Vec::new(),
+		};
+		class.this_class = class.class(name);
+		class.super_class = class.class(superclass);
+		class
+	}
+
+	/// Adds an interface the class implements.
+	pub fn implements(&mut self, interface: &str) -> &mut ClassFile {
+		let index = self.class(interface);
+		self.interfaces.push(index);
+		self
+	}
+
+	/// Adds a field.
+	pub fn field(&mut self, access: u16, name: &str, descriptor: &str) -> &mut ClassFile {
+		let member = self.member(access, name, descriptor, None);
+		self.fields.push(member);
+		self
+	}
+
+	/// Adds a method implemented by a native function, which must be registered after the class is defined.
+	pub fn native_method(&mut self, access: u16, name: &str, descriptor: &str) -> &mut ClassFile {
+		let member = self.member(access | ClassFile::ACC_NATIVE, name, descriptor, None);
+		self.methods.push(member);
+		self
+	}
+
+	/// Adds a method with the given bytecode, which must not branch.
+	pub fn method(&mut self, access: u16, name: &str, descriptor: &str, max_stack: u16, max_locals: u16, code: &[u8]) -> &mut ClassFile {
+		self.code_name = self.utf8("Code");
+		let member = self.member(access, name, descriptor, Some((max_stack, max_locals, code.to_vec())));
+		self.methods.push(member);
+		self
+	}
+
+	/// Returns the constant pool index of a method reference, for `invoke*` instructions.
+	pub fn methodref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+		let class = self.class(class);
+		let name = self.utf8(name);
+		let descriptor = self.utf8(descriptor);
+		let name_and_type = self.constant(Constant::NameAndType(name, descriptor));
+		self.constant(Constant::Methodref(class, name_and_type))
+	}
+
+	/// Serializes the class file, as version 52.0 (Java 8).
+	pub fn to_bytes(&self) -> Vec<u8> {
+		fn u16s(class: &mut Vec<u8>, values: &[u16]) {
+			for value in values {
+				class.extend_from_slice(&value.to_be_bytes());
+			}
+		}
+
+		let mut class = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52];
+		// constant pool count, one more than the number of entries
+		u16s(&mut class, &[self.pool.len() as u16 + 1]);
+		for constant in &self.pool {
+			match constant {
+				Constant::Utf8(s) => {
+					let s = cesu8::to_java_cesu8(s);
+					class.push(1);
+					u16s(&mut class, &[s.len() as u16]);
+					class.extend_from_slice(&s);
+				},
+				Constant::Class(name) => { class.push(7); u16s(&mut class, &[*name]); },
+				Constant::NameAndType(name, descriptor) => { class.push(12); u16s(&mut class, &[*name, *descriptor]); },
+				Constant::Methodref(class_index, name_and_type) => { class.push(10); u16s(&mut class, &[*class_index, *name_and_type]); },
+			}
+		}
+
+		u16s(&mut class, &[self.access, self.this_class, self.super_class, self.interfaces.len() as u16]);
+		u16s(&mut class, &self.interfaces);
+		for members in [&self.fields, &self.methods] {
+			u16s(&mut class, &[members.len() as u16]);
+			for member in members {
+				u16s(&mut class, &[member.access, member.name, member.descriptor]);
+				match &member.code {
+					None => u16s(&mut class, &[0]),
+					Some((max_stack, max_locals, code)) => {
+						// one Code attribute: max stack and locals, the bytecode, and no exception table or attributes
+						u16s(&mut class, &[1, self.code_name]);
+						class.extend_from_slice(&(12 + code.len() as u32).to_be_bytes());
+						u16s(&mut class, &[*max_stack, *max_locals]);
+						class.extend_from_slice(&(code.len() as u32).to_be_bytes());
+						class.extend_from_slice(code);
+						u16s(&mut class, &[0, 0]);
+					},
+				}
+			}
+		}
+
+		u16s(&mut class, &[0]); // no class attributes
+		class
+	}
+
+	/// Defines the class with the bootstrap loader, and registers its native methods as `(name, descriptor, function)`.
+	///
+	/// # Safety
+	/// Each function pointer must be an `extern "system" fn` whose parameters and return type match its descriptor,
+	/// taking the `JNIEnv` and then the class (for static methods) or instance first.
+	pub(crate) unsafe fn define(&self, env: JniEnv<'_>, natives: &[(&str, &str, *mut std::ffi::c_void)]) -> Result<GlobalRef<JClass>, JniError> {
+		let local = env.define_class(&self.name, None, &self.to_bytes())?;
+		let class = GlobalRef::<JClass>::pinned(env, local);
+		env.delete_local_ref(local)?;
+		let class = class?;
+
+		let names: Vec<_> = natives.iter()
+			.map(|(name, descriptor, _)| (crate::env::java_cstr(name), crate::env::java_cstr(descriptor)))
+			.collect();
+		let methods: Vec<_> = natives.iter().zip(&names)
+			.map(|((_, _, fn_ptr), (name, descriptor))| js::JNINativeMethod {
+				name: name.as_ptr() as *mut c_char,
+				signature: descriptor.as_ptr() as *mut c_char,
+				fnPtr: *fn_ptr,
+			})
+			.collect();
+		env.register_natives(class.as_raw_nonnull(), &methods)?;
+		Ok(class)
+	}
+
+	fn member(&mut self, access: u16, name: &str, descriptor: &str, code: Option<(u16, u16, Vec<u8>)>) -> Member {
+		Member { access, name: self.utf8(name), descriptor: self.utf8(descriptor), code }
+	}
+
+	fn utf8(&mut self, s: &str) -> u16 {
+		self.constant(Constant::Utf8(s.to_owned()))
+	}
+
+	fn class(&mut self, name: &str) -> u16 {
+		let name = self.utf8(name);
+		self.constant(Constant::Class(name))
+	}
+
+	/// Returns the index of `constant`, adding it if it isn't in the pool yet. Indices start at one.
+	fn constant(&mut self, constant: Constant) -> u16 {
+		let index = match self.pool.iter().position(|c| *c == constant) {
+			Some(index) => index,
+			None => {
+				self.pool.push(constant);
+				self.pool.len() - 1
+			},
+		};
+		index as u16 + 1
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ClassFile;
+
+	#[test]
+	fn constants_are_shared() {
+		let mut class = ClassFile::new(ClassFile::ACC_SUPER, "a/B", "java/lang/Object");
+		let init = class.methodref("java/lang/Object", "<init>", "()V");
+		assert_eq!(init, class.methodref("java/lang/Object", "<init>", "()V"));
+		class.native_method(ClassFile::ACC_PUBLIC, "run", "()V");
+
+		let bytes = class.to_bytes();
+		assert_eq!(&[0xca, 0xfe, 0xba, 0xbe], &bytes[..4]);
+		// a/B, its Class, java/lang/Object, its Class, <init>, ()V, NameAndType, Methodref, run
+		assert_eq!(10, u16::from_be_bytes([bytes[8], bytes[9]]));
+	}
+}
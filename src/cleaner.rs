@@ -0,0 +1,333 @@
+//! Releasing Rust resources when the Java objects that own them become unreachable.
+//!
+//! A [`Cleaner`] wraps a `java.lang.ref.Cleaner`, which requires Java 9 or later. Each registered closure is wrapped in
+//! a `Runnable` whose native `run` calls it, and runs on the Java cleaner's own thread once the object it was
+//! registered against has been collected.
+//!
+//! ```no_run
+//! # use yajnir::{cleaner::Cleaner, env::{JniEnv, JniError}, jref::AutoObj, lang::JObject};
+//! # fn example(env: JniEnv<'_>, cleaner: &Cleaner, obj: &AutoObj<'_, JObject>) -> Result<(), JniError> {
+//! let buffer = vec![0u8; 1 << 20];
+//! cleaner.register(env, obj, move || drop(buffer))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use jni_sys as js;
+use once_cell::sync::{Lazy, OnceCell};
+
+use crate::classfile::ClassFile;
+use crate::env::{FieldId, JniEnv, JniError, MethodId};
+use crate::jref::{AsRawObject, GlobalRef, JavaClass, RawJObject, RichJavaType};
+use crate::lang::{class_ref, JClass};
+
+/// `java.lang.ref.Cleaner`
+#[derive(Debug)]
+pub struct JCleaner;
+#[derive(Debug)]
+pub struct CleanerIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) create: MethodId,
+	pub(crate) register: MethodId,
+}
+impl RichJavaType for JCleaner {
+	type IDs = CleanerIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static CleanerIDs {
+		static IDS: OnceCell<CleanerIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JCleaner::INTERNAL_NAME)?;
+			Ok(CleanerIDs {
+				create: env.get_static_method_id(class.as_raw_nonnull(), "create", "()Ljava/lang/ref/Cleaner;")?,
+				register: env.get_method_id(class.as_raw_nonnull(), "register", "(Ljava/lang/Object;Ljava/lang/Runnable;)Ljava/lang/ref/Cleaner$Cleanable;")?,
+				class,
+			})
+		}).expect("unable to resolve java/lang/ref/Cleaner descriptors");
+		ids
+	}
+}
+impl JavaClass for JCleaner {
+	const INTERNAL_NAME: &'static str = "java/lang/ref/Cleaner";
+}
+
+/// `java.lang.ref.Reference`
+#[derive(Debug)]
+pub struct JReference;
+#[derive(Debug)]
+pub struct ReferenceIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	/// `Reference.reachabilityFence`, which was added in Java 9
+	pub(crate) reachability_fence: Option<MethodId>,
+}
+impl RichJavaType for JReference {
+	type IDs = ReferenceIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ReferenceIDs {
+		static IDS: OnceCell<ReferenceIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JReference::INTERNAL_NAME)?;
+			let reachability_fence = match env.get_static_method_id(class.as_raw_nonnull(), "reachabilityFence", "(Ljava/lang/Object;)V") {
+				Ok(mid) => Some(mid),
+				Err(JniError::JavaException) => {
+					env.exception_clear()?;
+					None
+				},
+				Err(e) => return Err(e),
+			};
+			Ok(ReferenceIDs { class, reachability_fence })
+		}).expect("unable to resolve java/lang/ref/Reference descriptors");
+		ids
+	}
+}
+impl JavaClass for JReference {
+	const INTERNAL_NAME: &'static str = "java/lang/ref/Reference";
+}
+
+/// `yajnir.CleanupAction`, a `Runnable` defined at runtime whose native `run` calls a registered Rust closure
+#[derive(Debug)]
+pub struct JCleanupAction;
+#[derive(Debug)]
+pub struct CleanupActionIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	/// The key of the closure in [`ACTIONS`], or zero once it has run
+	pub(crate) id: FieldId,
+}
+impl RichJavaType for JCleanupAction {
+	type IDs = CleanupActionIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static CleanupActionIDs {
+		static IDS: OnceCell<CleanupActionIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			// SAFETY: run_action matches the native run()V
+			let class = unsafe {
+				action_class_file().define(env, &[("run", "()V", run_action as extern "system" fn(*mut js::JNIEnv, js::jobject) as *mut std::ffi::c_void)])?
+			};
+			let raw = class.as_raw_nonnull();
+			Ok(CleanupActionIDs {
+				ctor: env.get_method_id(raw, "<init>", "()V")?,
+				id: env.get_field_id(raw, "id", "J")?,
+				class,
+			})
+		}).expect("unable to define yajnir/CleanupAction");
+		ids
+	}
+}
+impl JavaClass for JCleanupAction {
+	const INTERNAL_NAME: &'static str = "yajnir/CleanupAction";
+}
+
+/// The class file for:
+///
+/// ```java
+/// final class CleanupAction implements Runnable {
+///     private long id;
+///     CleanupAction() { super(); }
+///     public native void run();
+/// }
+/// ```
+fn action_class_file() -> ClassFile {
+	let mut class = ClassFile::new(ClassFile::ACC_FINAL | ClassFile::ACC_SUPER, JCleanupAction::INTERNAL_NAME, "java/lang/Object");
+	let [hi, lo] = class.methodref("java/lang/Object", "<init>", "()V").to_be_bytes();
+	class.implements("java/lang/Runnable")
+		.field(ClassFile::ACC_PRIVATE, "id", "J")
+		// aload_0, invokespecial Object.<init>, return
+		.method(0, "<init>", "()V", 1, 1, &[0x2a, 0xb7, hi, lo, 0xb1])
+		.native_method(ClassFile::ACC_PUBLIC, "run", "()V");
+	class
+}
+
+struct Action {
+	/// The [`Cleaner::id`] it was registered with
+	cleaner: u64,
+	action: Box<dyn FnOnce() + Send>,
+}
+
+/// Closures that haven't run yet, keyed by the id stored in their `CleanupAction`
+static ACTIONS: Lazy<Mutex<HashMap<u64, Action>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Ids for both cleaners and actions, starting from one so zero can mean none
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn actions() -> std::sync::MutexGuard<'static, HashMap<u64, Action>> {
+	ACTIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs Rust cleanup closures after the Java objects they were registered against have been garbage collected.
+///
+/// Closures run on the thread of the underlying `java.lang.ref.Cleaner`, so they must not block for long. Dropping the
+/// cleaner drops any closures that have not run yet, without running them.
+pub struct Cleaner {
+	cleaner: GlobalRef<JCleaner>,
+	id: u64,
+}
+
+impl std::fmt::Debug for Cleaner {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Cleaner")
+			.field("cleaner", &self.cleaner)
+			.field("registered", &self.pending())
+			.finish()
+	}
+}
+
+impl Cleaner {
+	/// Creates a cleaner with `Cleaner.create`, which starts its own thread.
+	///
+	/// Contract: attached, reported
+	pub fn new(env: JniEnv<'_>) -> Result<Cleaner, JniError> {
+		let ids = JCleaner::descriptors(env);
+		let local = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.create, &[])?
+			.ok_or(JniError::NullPointer("Cleaner.create"))?;
+		let cleaner = GlobalRef::new(env, local);
+		env.delete_local_ref(local)?;
+		Ok(Cleaner { cleaner: cleaner?, id: NEXT_ID.fetch_add(1, Ordering::Relaxed) })
+	}
+
+	/// Registers `action` to run once `obj` has become phantom reachable, which is after it has been collected.
+	///
+	/// `action` must not capture a reference to `obj` (such as a [`GlobalRef`]), or it will never be collected.
+	///
+	/// Contract: attached, reported
+	pub fn register<T, R, F>(&self, env: JniEnv<'_>, obj: &R, action: F) -> Result<(), JniError>
+		where T: RichJavaType, R: AsRawObject<T>, F: FnOnce() + Send + 'static
+	{
+		let ids = JCleanupAction::descriptors(env);
+		let runnable = env.new_object(ids.class.as_raw_nonnull(), ids.ctor, &[])?;
+		let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+		actions().insert(id, Action { cleaner: self.id, action: Box::new(action) });
+		let res = (|| -> Result<(), JniError> {
+			env.set_long_field(runnable, ids.id, id as js::jlong)?;
+			let cleanable = env.call_object_method(self.cleaner.as_raw_nonnull(), JCleaner::descriptors(env).register, &[
+				js::jvalue { l: obj.as_raw() },
+				js::jvalue { l: runnable.as_ptr() },
+			])?;
+			match cleanable {
+				Some(cleanable) => env.delete_local_ref(cleanable),
+				None => Ok(()),
+			}
+		})();
+		env.delete_local_ref(runnable)?;
+
+		if res.is_err() {
+			// never registered, so it will never run
+			actions().remove(&id);
+		}
+		res
+	}
+
+	/// Returns how many registered closures have not run yet.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn pending(&self) -> usize {
+		actions().values().filter(|action| action.cleaner == self.id).count()
+	}
+}
+
+impl Drop for Cleaner {
+	fn drop(&mut self) {
+		// removed under the lock, but dropped outside it in case a closure's captures register others as they drop
+		let dropped: Vec<Action> = {
+			let mut actions = actions();
+			let ids: Vec<u64> = actions.iter().filter(|(_, action)| action.cleaner == self.id).map(|(id, _)| *id).collect();
+			ids.iter().filter_map(|id| actions.remove(id)).collect()
+		};
+		drop(dropped);
+	}
+}
+
+/// The native `CleanupAction.run`, which takes and calls the registered closure.
+extern "system" fn run_action(env: *mut js::JNIEnv, this: js::jobject) {
+	let (env, this) = match (NonNull::new(env), NonNull::new(this)) {
+		(Some(env), Some(this)) => (JniEnv { ptr: env, _phantom: PhantomData }, this),
+		_ => return,
+	};
+	let id = match take_id(env, this) {
+		Ok(id) => id,
+		Err(e) => {
+			log::error!("unable to read cleanup action: {}", e);
+			return;
+		},
+	};
+	// missing if its Cleaner was dropped first
+	let action = match actions().remove(&id) {
+		Some(action) => action.action,
+		None => return,
+	};
+
+	// unwinding into the JVM is undefined behaviour
+	if std::panic::catch_unwind(AssertUnwindSafe(action)).is_err() {
+		log::error!("cleanup action panicked");
+	}
+}
+
+fn take_id(env: JniEnv<'_>, this: RawJObject) -> Result<u64, JniError> {
+	let ids = JCleanupAction::descriptors(env);
+	// Cleanable.clean runs each action at most once, but zeroing the id keeps a direct run() call from repeating it
+	let id = env.get_long_field(this, ids.id)?;
+	env.set_long_field(this, ids.id, 0)?;
+	Ok(id as u64)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	use crate::cleaner::Cleaner;
+	use crate::jref::AutoObj;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::{gc_until, JObject};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn runs_after_collection() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let cleaner = Cleaner::new(env).expect("error creating cleaner");
+
+			let cleaned = Arc::new(AtomicBool::new(false));
+			let obj: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("collect me").expect("error creating string"));
+			let flag = Arc::clone(&cleaned);
+			cleaner.register(env, &obj, move || flag.store(true, Ordering::SeqCst)).expect("error registering");
+			assert_eq!(1, cleaner.pending());
+			env.reachability_fence(&obj).expect("error fencing object");
+
+			drop(obj);
+			assert!(gc_until(env, || cleaned.load(Ordering::SeqCst)));
+			assert_eq!(0, cleaner.pending());
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn drop_forgets_pending_closures() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let dropped = Cleaner::new(env).expect("error creating cleaner");
+			let kept = Cleaner::new(env).expect("error creating cleaner");
+			let obj: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("kept alive").expect("error creating string"));
+			let captured = Arc::new(());
+			for cleaner in [&dropped, &dropped, &kept] {
+				let captured = Arc::clone(&captured);
+				cleaner.register(env, &obj, move || panic!("closure should not run: {:?}", captured)).expect("error registering");
+			}
+			assert_eq!(2, dropped.pending());
+			assert_eq!(4, Arc::strong_count(&captured));
+
+			// the closures are dropped without running, and the other cleaner's are kept
+			drop(dropped);
+			assert_eq!(2, Arc::strong_count(&captured));
+			assert_eq!(1, kept.pending());
+
+			drop(kept);
+			drop(obj);
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
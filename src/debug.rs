@@ -0,0 +1,245 @@
+//! Gathering the crate's and the JVM's state into a single report, for attaching to bug reports.
+//!
+//! ```no_run
+//! let report = yajnir::debug::collect_report();
+//! eprintln!("{}", report);
+//! ```
+//!
+//! With the `serde` feature enabled, [`Report`] also implements `Serialize`, so it can be written out as JSON or any
+//! other format.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::env::JniEnv;
+use crate::jvm::{CreationReport, JavaVM};
+use crate::lang::JSystem;
+
+/// How many JNI calls each thread remembers for [`Report::recent_calls`]
+const RECENT_CALLS: usize = 32;
+
+thread_local! {
+	static RECENT: RefCell<VecDeque<&'static str>> = const { RefCell::new(VecDeque::new()) };
+	static PAUSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Records the JNI function about to be called on this thread. Called by the JNI call macros.
+pub(crate) fn record_call(name: &'static str) {
+	// the thread-locals may already be gone while a thread detaches during exit
+	if PAUSED.try_with(Cell::get).unwrap_or(true) {
+		return;
+	}
+	let _ = RECENT.try_with(|recent| {
+		if let Ok(mut recent) = recent.try_borrow_mut() {
+			if recent.len() == RECENT_CALLS {
+				recent.pop_front();
+			}
+			recent.push_back(name);
+		}
+	});
+}
+
+/// Returns the JNI functions most recently called on this thread, oldest first.
+pub(crate) fn recent_calls() -> Vec<&'static str> {
+	RECENT.try_with(|recent| recent.try_borrow().map(|r| r.iter().copied().collect()).unwrap_or_default())
+		.unwrap_or_default()
+}
+
+/// Runs `func` without recording its JNI calls, so inspecting the thread's state doesn't overwrite it.
+pub(crate) fn untraced<R>(func: impl FnOnce() -> R) -> R {
+	let was_paused = PAUSED.try_with(|p| p.replace(true)).unwrap_or(true);
+	let res = func();
+	let _ = PAUSED.try_with(|p| p.set(was_paused));
+	res
+}
+
+/// The crate's and the JVM's state at the time of [`collect_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Report {
+	/// This crate's version
+	pub crate_version: &'static str,
+	/// This crate's enabled cargo features
+	pub features: Vec<&'static str>,
+	/// How many global references created through [`GlobalRef`](crate::jref::GlobalRef) are still alive
+	pub live_globals: usize,
+	/// Whether a JavaVM exists in this process
+	pub vm_created: bool,
+	/// The names of the threads attached through this crate, in the order they attached
+	pub attached_threads: Vec<String>,
+	/// Whether the thread collecting the report is attached
+	pub current_thread_attached: bool,
+	/// The options the JavaVM was created with, if it was created through [`JavaVM::create`]
+	pub vm_options: Option<Vec<String>>,
+	/// The phases of creating the JavaVM, if it was created through [`JavaVM::create`]
+	pub creation: Option<CreationReport>,
+	/// The `java.version` system property
+	pub java_version: Option<String>,
+	/// The `java.vendor` system property
+	pub java_vendor: Option<String>,
+	/// The `java.vm.name` system property
+	pub java_vm_name: Option<String>,
+	/// The JNI functions most recently called on the thread collecting the report, oldest first
+	pub recent_calls: Vec<&'static str>,
+}
+
+/// Collects a [`Report`] of the crate's and the JVM's current state.
+///
+/// This never fails; anything that can't be determined is left empty. If the current thread isn't attached, it is
+/// attached for the duration of the call to read the JVM's system properties, unless an exception is pending on it.
+pub fn collect_report() -> Report {
+	let recent_calls = recent_calls();
+	untraced(move || {
+		let mut report = Report {
+			crate_version: env!("CARGO_PKG_VERSION"),
+			features: enabled_features(),
+			live_globals: crate::jref::live_globals(),
+			vm_created: false,
+			attached_threads: crate::jvm::attached_threads(),
+			current_thread_attached: false,
+			vm_options: crate::jvm::creation_options(),
+			creation: None,
+			java_version: None,
+			java_vendor: None,
+			java_vm_name: None,
+			recent_calls,
+		};
+
+		let vm = match JavaVM::created_jvms().map(|vms| vms.first().copied()) {
+			Ok(Some(vm)) if !vm.is_destroyed() => vm,
+			_ => return report,
+		};
+		report.vm_created = true;
+		report.creation = vm.creation_report();
+		report.current_thread_attached = matches!(vm.get_env(), Ok(Some(_)));
+
+		let _ = vm.with_env(|env| {
+			// Java can't be called into while an exception is pending
+			if env.exception_check().is_err() {
+				return;
+			}
+			report.java_version = property(env, "java.version");
+			report.java_vendor = property(env, "java.vendor");
+			report.java_vm_name = property(env, "java.vm.name");
+		});
+		report
+	})
+}
+
+fn property(env: JniEnv<'_>, name: &str) -> Option<String> {
+	match JSystem::property(env, name) {
+		Ok(value) => value,
+		Err(e) => {
+			log::debug!("unable to read system property {} for report: {}", name, e);
+			let _ = env.exception_clear();
+			None
+		},
+	}
+}
+
+fn enabled_features() -> Vec<&'static str> {
+	let mut features = Vec::new();
+	if cfg!(feature = "collections") {
+		features.push("collections");
+	}
+	if cfg!(feature = "diagnostics") {
+		features.push("diagnostics");
+	}
+	if cfg!(feature = "nio") {
+		features.push("nio");
+	}
+	if cfg!(feature = "security") {
+		features.push("security");
+	}
+	if cfg!(feature = "zip") {
+		features.push("zip");
+	}
+	if cfg!(feature = "experimental") {
+		features.push("experimental");
+	}
+	if cfg!(feature = "pool") {
+		features.push("pool");
+	}
+	if cfg!(feature = "invocation") {
+		features.push("invocation");
+	}
+	if cfg!(feature = "serde") {
+		features.push("serde");
+	}
+	if cfg!(feature = "bisect-options") {
+		features.push("bisect-options");
+	}
+	if cfg!(feature = "android") {
+		features.push("android");
+	}
+	if cfg!(feature = "ndk-context") {
+		features.push("ndk-context");
+	}
+	if cfg!(feature = "jvm-test") {
+		features.push("jvm-test");
+	}
+	features
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fn or_unknown(value: &Option<String>) -> &str {
+			value.as_deref().unwrap_or("unknown")
+		}
+
+		writeln!(f, "yajnir {} (features: [{}])", self.crate_version, self.features.join(", "))?;
+		if !self.vm_created {
+			writeln!(f, "no JavaVM")?;
+		} else {
+			writeln!(f, "JVM: {} {} ({})", or_unknown(&self.java_vm_name), or_unknown(&self.java_version), or_unknown(&self.java_vendor))?;
+		}
+		match &self.vm_options {
+			Some(options) => writeln!(f, "VM options: {:?}", options)?,
+			None => writeln!(f, "VM options: not created through this crate")?,
+		}
+		if let Some(creation) = &self.creation {
+			writeln!(f, "creation: {:?}", creation)?;
+		}
+		writeln!(f, "live global references: {}", self.live_globals)?;
+		writeln!(f, "attached threads: {:?} (current thread attached: {})", self.attached_threads, self.current_thread_attached)?;
+		write!(f, "recent JNI calls: [{}]", self.recent_calls.join(", "))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn collects_report() {
+			let report = super::collect_report();
+			assert!(!report.vm_created);
+			assert!(report.java_version.is_none());
+
+			let (vm, env) = JavaVM::create(VmOptions::with_opts(JniVersion::V10, vec!["-Xss1m".into()])).expect("error creating vm");
+			env.exception_check().expect("no exception to be pending");
+			let worker = std::thread::Builder::new().name(String::from("report-worker")).spawn(move || {
+				let _env = vm.attach_permanently().expect("error attaching");
+				let report = super::collect_report();
+				assert!(report.current_thread_attached);
+				assert_eq!(Some("report-worker"), report.attached_threads.last().map(String::as_str));
+			}).expect("error spawning thread");
+			worker.join().expect("worker thread panicked");
+
+			let report = super::collect_report();
+			assert!(report.vm_created);
+			assert!(report.java_version.is_some());
+			assert_eq!(Some(&vec![String::from("-Xss1m")]), report.vm_options.as_ref());
+			// the worker detached when it exited
+			assert_eq!(1, report.attached_threads.len());
+			// reading the properties isn't recorded
+			assert_eq!(Some(&"ExceptionCheck"), report.recent_calls.last());
+			assert!(report.to_string().contains("-Xss1m"));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
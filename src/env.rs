@@ -1,8 +1,17 @@
 
+use std::ffi::CStr;
+use std::fmt;
 use std::marker::PhantomData;
+use std::os::raw::c_char;
 use std::ptr::NonNull;
 use jni_sys as js;
 
+use crate::{from_jsize, j2r_bool, to_jsize, SizeError};
+use crate::cleaner::JReference;
+use crate::jref::{AutoObj, AsRawObject, RawJObject, RichJavaType};
+use crate::jvm::JavaVM;
+use crate::lang::{ExceptionCtor, JClassLoader, JThread, JThrowable, ThrowableIDs};
+
 #[repr(transparent)]
 #[derive(Debug, Copy, Clone)]
 pub struct JniEnv<'a> {
@@ -10,3 +19,648 @@ pub struct JniEnv<'a> {
 	pub(crate) _phantom: PhantomData<&'a ()>,
 }
 
+/// A cached, non-null JNI method ID. These are valid on any thread for as long as their class is loaded.
+///
+/// Displays as the method it was looked up as, such as `java.lang.Thread#getName()Ljava/lang/String;`.
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) struct MethodId(NonNull<js::_jmethodID>);
+
+// SAFETY: method IDs are not tied to the thread that looked them up
+unsafe impl Send for MethodId {}
+unsafe impl Sync for MethodId {}
+
+impl MethodId {
+	pub(crate) fn as_raw(&self) -> js::jmethodID {
+		self.0.as_ptr()
+	}
+}
+
+impl fmt::Display for MethodId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match crate::names::method_name(*self) {
+			Some(name) => write!(f, "{}", name),
+			None => write!(f, "<method {:p}>", self.0),
+		}
+	}
+}
+
+impl fmt::Debug for MethodId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "MethodId({})", self)
+	}
+}
+
+/// A cached, non-null JNI field ID. Like [`MethodId`], these are valid on any thread for as long as their class is loaded.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct FieldId(NonNull<js::_jfieldID>);
+
+// SAFETY: field IDs are not tied to the thread that looked them up
+unsafe impl Send for FieldId {}
+unsafe impl Sync for FieldId {}
+
+impl FieldId {
+	pub(crate) fn as_raw(&self) -> js::jfieldID {
+		self.0.as_ptr()
+	}
+}
+
+/// A Java method return type, which selects the matching `Call<Type>MethodA` and `CallStatic<Type>MethodA` JNI
+/// functions at compile time. Implemented for each primitive, `jobject` for references, and `()` for `void`.
+///
+/// Calls don't check for exceptions; [`JniEnv::call_method`] and [`JniEnv::call_static_method`] do.
+pub(crate) trait JniCallable: Sized {
+	fn call(env: JniEnv<'_>, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Self, JniError>;
+	fn call_static(env: JniEnv<'_>, class: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Self, JniError>;
+}
+
+macro_rules! jni_callable {
+	( $( $ty:ty => $call:ident, $call_static:ident; )* ) => { $(
+		impl JniCallable for $ty {
+			fn call(env: JniEnv<'_>, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Self, JniError> {
+				Ok(jni_unchecked!(env, $call, obj.as_ptr(), method.as_raw(), args.as_ptr()))
+			}
+
+			fn call_static(env: JniEnv<'_>, class: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Self, JniError> {
+				Ok(jni_unchecked!(env, $call_static, class.as_ptr(), method.as_raw(), args.as_ptr()))
+			}
+		}
+	)* };
+}
+
+jni_callable! {
+	js::jboolean => CallBooleanMethodA, CallStaticBooleanMethodA;
+	js::jbyte => CallByteMethodA, CallStaticByteMethodA;
+	js::jchar => CallCharMethodA, CallStaticCharMethodA;
+	js::jshort => CallShortMethodA, CallStaticShortMethodA;
+	js::jint => CallIntMethodA, CallStaticIntMethodA;
+	js::jlong => CallLongMethodA, CallStaticLongMethodA;
+	js::jfloat => CallFloatMethodA, CallStaticFloatMethodA;
+	js::jdouble => CallDoubleMethodA, CallStaticDoubleMethodA;
+	js::jobject => CallObjectMethodA, CallStaticObjectMethodA;
+	() => CallVoidMethodA, CallStaticVoidMethodA;
+}
+
+/// Converts a Rust string into the null-terminated modified UTF-8 expected by JNI functions.
+pub(crate) fn java_cstr(s: &str) -> Vec<u8> {
+	let mut bytes = cesu8::to_java_cesu8(s).into_owned();
+	bytes.push(0);
+	bytes
+}
+
+impl JniEnv<'static> {
+	/// Returns the current thread's environment, if it is attached to the process's JavaVM.
+	///
+	/// This lets code deep in a call stack, such as a callback, reach the environment without passing it through every
+	/// function. The returned environment is only valid while the thread stays attached, so it must not outlive the
+	/// attachment (such as an [`AttachGuard`](crate::jvm::AttachGuard)) in effect when it was retrieved.
+	///
+	/// Contract: any, unaffected, pending
+	pub fn current() -> Option<JniEnv<'static>> {
+		let vm = match crate::jvm::global() {
+			Some(vm) => vm,
+			None => *JavaVM::created_jvms().ok()?.first()?,
+		};
+		let env = vm.get_env().ok()??;
+		Some(JniEnv { ptr: env, _phantom: PhantomData })
+	}
+}
+
+impl<'a> JniEnv<'a> {
+	/// Returns the JavaVM this environment belongs to.
+	///
+	/// Contract: attached, unaffected, pending
+	pub fn java_vm(&self) -> Result<JavaVM, JniError> {
+		let mut raw_jvm: *mut js::JavaVM = std::ptr::null_mut();
+		let res = jni_unchecked!(*self, GetJavaVM, &mut raw_jvm as *mut *mut js::JavaVM);
+		if res != js::JNI_OK {
+			return Err(JniError::UnexpectedResult("GetJavaVM", res));
+		}
+
+		let ptr = NonNull::new(raw_jvm).ok_or(JniError::NullPointer("GetJavaVM"))?;
+		Ok(JavaVM { ptr })
+	}
+
+	/// Returns an error if a Java exception is currently pending on this thread. The exception is left pending.
+	///
+	/// Contract: attached, inspects, pending
+	pub fn exception_check(&self) -> Result<(), JniError> {
+		if j2r_bool(jni_unchecked!(*self, ExceptionCheck)) {
+			Err(JniError::JavaException)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Clears any pending Java exception on this thread.
+	///
+	/// Contract: attached, inspects, pending
+	pub fn exception_clear(&self) -> Result<(), JniError> {
+		jni_unchecked!(*self, ExceptionClear);
+		Ok(())
+	}
+
+	/// Returns the pending exception, if any, without clearing it.
+	///
+	/// Contract: attached, inspects, pending
+	pub fn exception_occurred(&self) -> Result<Option<AutoObj<'a, JThrowable>>, JniError> {
+		let exc = match NonNull::new(jni_unchecked!(*self, ExceptionOccurred)) {
+			Some(exc) => exc,
+			None => return Ok(None),
+		};
+		// resolving the Throwable descriptors may call into Java, which isn't allowed while the exception is pending
+		jni_unchecked!(*self, ExceptionClear);
+		let exc = AutoObj::from_raw(*self, exc);
+		jni_unchecked!(*self, Throw, exc.as_raw_nonnull().as_ptr());
+		Ok(Some(exc))
+	}
+
+	/// Clears and returns the pending exception if it is an instance of `class`, such as `java/io/IOException`.
+	///
+	/// Any other pending exception is left in place, and `None` is returned.
+	///
+	/// Contract: attached, inspects, pending
+	pub fn catch_exception(&self, class: &str) -> Result<Option<AutoObj<'a, JThrowable>>, JniError> {
+		let exc = match self.exception_occurred()? {
+			Some(exc) => exc,
+			None => return Ok(None),
+		};
+
+		// JNI calls other than a few cleanup functions are not allowed while an exception is pending
+		self.exception_clear()?;
+		let class = self.find_class(class)?;
+		let matches = self.is_instance_of(exc.as_raw_nonnull(), class);
+		self.delete_local_ref(class)?;
+		if matches? {
+			Ok(Some(exc))
+		} else {
+			jni_unchecked!(*self, Throw, exc.as_raw_nonnull().as_ptr());
+			drop(exc);
+			Ok(None)
+		}
+	}
+
+	fn throw_cached(&self, select: fn(&ThrowableIDs) -> &ExceptionCtor, msg: &str) -> Result<(), JniError> {
+		let ids = JThrowable::descriptors(*self);
+		let exc = select(ids);
+		let msg = self.new_string(msg)?;
+		let obj = self.new_object(exc.class.as_raw_nonnull(), exc.ctor, &[js::jvalue { l: msg.as_ptr() }]);
+		self.delete_local_ref(msg)?;
+		let obj = obj?;
+
+		let res = jni_unchecked!(*self, Throw, obj.as_ptr());
+		self.delete_local_ref(obj)?;
+		if res != js::JNI_OK {
+			return Err(JniError::UnexpectedResult("Throw", res));
+		}
+		Ok(())
+	}
+
+	/// Throws a `java.lang.IllegalArgumentException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_illegal_argument(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.illegal_argument, msg)
+	}
+
+	/// Throws a `java.lang.IllegalStateException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_illegal_state(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.illegal_state, msg)
+	}
+
+	/// Throws a `java.lang.NullPointerException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_null_pointer(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.null_pointer, msg)
+	}
+
+	/// Throws a `java.io.IOException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_io(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.io, msg)
+	}
+
+	/// Throws a `java.lang.UnsupportedOperationException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_unsupported_operation(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.unsupported_operation, msg)
+	}
+
+	/// Throws a `java.lang.RuntimeException`. The exception is left pending for the calling Java code.
+	///
+	/// Contract: attached, throws
+	pub fn throw_runtime(&self, msg: &str) -> Result<(), JniError> {
+		self.throw_cached(|ids| &ids.runtime, msg)
+	}
+
+	/// Keeps `obj` strongly reachable until this call, so a [`Cleaner`](crate::cleaner::Cleaner) cannot run the
+	/// cleanup for resources that Rust code is still using, such as a buffer owned by `obj`.
+	///
+	/// Calls `Reference.reachabilityFence` on Java 9 and later. On older VMs, borrowing `obj` until this point already
+	/// keeps its reference, and so the object, alive.
+	///
+	/// Contract: attached, reported
+	pub fn reachability_fence<T: RichJavaType, R: AsRawObject<T>>(&self, obj: &R) -> Result<(), JniError> {
+		let ids = JReference::descriptors(*self);
+		match ids.reachability_fence {
+			Some(fence) => self.call_static_void_method(ids.class.as_raw_nonnull(), fence, &[js::jvalue { l: obj.as_raw() }]),
+			None => Ok(()),
+		}
+	}
+
+	pub(crate) fn find_class(&self, name: &str) -> Result<RawJObject, JniError> {
+		let cname = java_cstr(name);
+		let cls = jni_unchecked!(*self, FindClass, cname.as_ptr() as *const c_char);
+		self.exception_check()?;
+		let cls = NonNull::new(cls).ok_or(JniError::NullPointer("FindClass"))?;
+		crate::names::local_class_found(cls, name);
+		Ok(cls)
+	}
+
+	pub(crate) fn get_method_id(&self, class: RawJObject, name: &str, sig: &str) -> Result<MethodId, JniError> {
+		let (cname, csig) = (java_cstr(name), java_cstr(sig));
+		let mid = jni_unchecked!(*self, GetMethodID, class.as_ptr(), cname.as_ptr() as *const c_char, csig.as_ptr() as *const c_char);
+		self.exception_check()?;
+		let mid = NonNull::new(mid).map(MethodId).ok_or(JniError::NullPointer("GetMethodID"))?;
+		crate::names::method_found(class, mid, name, sig);
+		Ok(mid)
+	}
+
+	pub(crate) fn get_static_method_id(&self, class: RawJObject, name: &str, sig: &str) -> Result<MethodId, JniError> {
+		let (cname, csig) = (java_cstr(name), java_cstr(sig));
+		let mid = jni_unchecked!(*self, GetStaticMethodID, class.as_ptr(), cname.as_ptr() as *const c_char, csig.as_ptr() as *const c_char);
+		self.exception_check()?;
+		let mid = NonNull::new(mid).map(MethodId).ok_or(JniError::NullPointer("GetStaticMethodID"))?;
+		crate::names::method_found(class, mid, name, sig);
+		Ok(mid)
+	}
+
+	/// Looks up an instance field. Unlike reflection, JNI ignores access modifiers, so private fields are reachable.
+	pub(crate) fn get_field_id(&self, class: RawJObject, name: &str, sig: &str) -> Result<FieldId, JniError> {
+		let (name, sig) = (java_cstr(name), java_cstr(sig));
+		let fid = jni_unchecked!(*self, GetFieldID, class.as_ptr(), name.as_ptr() as *const c_char, sig.as_ptr() as *const c_char);
+		self.exception_check()?;
+		NonNull::new(fid).map(FieldId).ok_or(JniError::NullPointer("GetFieldID"))
+	}
+
+	pub(crate) fn get_int_field(&self, obj: RawJObject, field: FieldId) -> Result<js::jint, JniError> {
+		Ok(jni_unchecked!(*self, GetIntField, obj.as_ptr(), field.as_raw()))
+	}
+
+	pub(crate) fn set_int_field(&self, obj: RawJObject, field: FieldId, value: js::jint) -> Result<(), JniError> {
+		jni_unchecked!(*self, SetIntField, obj.as_ptr(), field.as_raw(), value);
+		Ok(())
+	}
+
+	pub(crate) fn get_long_field(&self, obj: RawJObject, field: FieldId) -> Result<js::jlong, JniError> {
+		Ok(jni_unchecked!(*self, GetLongField, obj.as_ptr(), field.as_raw()))
+	}
+
+	pub(crate) fn set_long_field(&self, obj: RawJObject, field: FieldId, value: js::jlong) -> Result<(), JniError> {
+		jni_unchecked!(*self, SetLongField, obj.as_ptr(), field.as_raw(), value);
+		Ok(())
+	}
+
+	/// Defines a class from class file bytes, in the bootstrap class loader if `loader` is `None`.
+	pub(crate) fn define_class(&self, name: &str, loader: Option<RawJObject>, bytes: &[u8]) -> Result<RawJObject, JniError> {
+		let name = java_cstr(name);
+		let loader = loader.map_or(std::ptr::null_mut(), |l| l.as_ptr());
+		let class = jni_unchecked!(*self, DefineClass, name.as_ptr() as *const c_char, loader, bytes.as_ptr() as *const js::jbyte, to_jsize(bytes.len())?);
+		self.exception_check()?;
+		NonNull::new(class).ok_or(JniError::NullPointer("DefineClass"))
+	}
+
+	/// Binds native methods of `class` to Rust functions. The name and signature strings must be NUL-terminated.
+	pub(crate) fn register_natives(&self, class: RawJObject, methods: &[js::JNINativeMethod]) -> Result<(), JniError> {
+		let res = jni_unchecked!(*self, RegisterNatives, class.as_ptr(), methods.as_ptr(), to_jsize(methods.len())?);
+		self.exception_check()?;
+		match res {
+			js::JNI_OK => Ok(()),
+			res => Err(JniError::UnexpectedResult("RegisterNatives", res)),
+		}
+	}
+
+	pub(crate) fn is_instance_of(&self, obj: RawJObject, class: RawJObject) -> Result<bool, JniError> {
+		Ok(j2r_bool(jni_unchecked!(*self, IsInstanceOf, obj.as_ptr(), class.as_ptr())))
+	}
+
+	#[cfg(test)]
+	pub(crate) fn is_same_object(&self, a: RawJObject, b: RawJObject) -> Result<bool, JniError> {
+		Ok(j2r_bool(jni_unchecked!(*self, IsSameObject, a.as_ptr(), b.as_ptr())))
+	}
+
+	pub(crate) fn new_global_ref(&self, obj: RawJObject) -> Result<RawJObject, JniError> {
+		let global = jni_unchecked!(*self, NewGlobalRef, obj.as_ptr());
+		NonNull::new(global).ok_or(JniError::NullPointer("NewGlobalRef"))
+	}
+
+	/// Creates a global reference to `obj`, returning None if `obj` is a weak reference whose object was collected.
+	pub(crate) fn try_new_global_ref(&self, obj: RawJObject) -> Result<Option<RawJObject>, JniError> {
+		Ok(NonNull::new(jni_unchecked!(*self, NewGlobalRef, obj.as_ptr())))
+	}
+
+	/// Returns whether `obj` refers to null, such as a weak reference whose object was collected.
+	pub(crate) fn is_null_ref(&self, obj: RawJObject) -> Result<bool, JniError> {
+		Ok(j2r_bool(jni_unchecked!(*self, IsSameObject, obj.as_ptr(), std::ptr::null_mut())))
+	}
+
+	pub(crate) fn delete_global_ref(&self, obj: RawJObject) -> Result<(), JniError> {
+		jni_unchecked!(*self, DeleteGlobalRef, obj.as_ptr());
+		Ok(())
+	}
+
+	/// Creates a weak global reference, which doesn't keep `obj` from being collected.
+	pub(crate) fn new_weak_global_ref(&self, obj: RawJObject) -> Result<RawJObject, JniError> {
+		let weak = jni_unchecked!(*self, NewWeakGlobalRef, obj.as_ptr());
+		self.exception_check()?;
+		NonNull::new(weak).ok_or(JniError::NullPointer("NewWeakGlobalRef"))
+	}
+
+	pub(crate) fn delete_weak_global_ref(&self, obj: RawJObject) -> Result<(), JniError> {
+		jni_unchecked!(*self, DeleteWeakGlobalRef, obj.as_ptr());
+		Ok(())
+	}
+
+	/// Creates a local reference to `obj`, returning None if `obj` is a weak reference whose object was collected.
+	pub(crate) fn new_local_ref(&self, obj: RawJObject) -> Result<Option<RawJObject>, JniError> {
+		Ok(NonNull::new(jni_unchecked!(*self, NewLocalRef, obj.as_ptr())))
+	}
+
+	pub(crate) fn delete_local_ref(&self, obj: RawJObject) -> Result<(), JniError> {
+		jni_unchecked!(*self, DeleteLocalRef, obj.as_ptr());
+		crate::names::local_deleted(obj);
+		Ok(())
+	}
+
+	/// Creates a new `java.lang.String` local reference from a Rust string.
+	pub(crate) fn new_string(&self, s: &str) -> Result<RawJObject, JniError> {
+		let s = java_cstr(s);
+		let jstr = jni_unchecked!(*self, NewStringUTF, s.as_ptr() as *const c_char);
+		self.exception_check()?;
+		NonNull::new(jstr).ok_or(JniError::NullPointer("NewStringUTF"))
+	}
+
+	/// Copies the contents of a `java.lang.String` into a Rust string.
+	pub(crate) fn get_string(&self, jstr: RawJObject) -> Result<String, JniError> {
+		let chars = jni_unchecked!(*self, GetStringUTFChars, jstr.as_ptr(), std::ptr::null_mut());
+		if chars.is_null() {
+			self.exception_check()?;
+			return Err(JniError::NullPointer("GetStringUTFChars"));
+		}
+
+		// SAFETY: GetStringUTFChars returns a null-terminated string, valid until it is released below
+		let res = cesu8::from_java_cesu8(unsafe { CStr::from_ptr(chars) }.to_bytes())
+			.map(|s| s.into_owned())
+			.map_err(JniError::from);
+		jni_unchecked!(*self, ReleaseStringUTFChars, jstr.as_ptr(), chars);
+		res
+	}
+
+	/// Like [`JniEnv::get_string`], but maps null references to None and deletes the passed local reference.
+	pub(crate) fn take_string(&self, jstr: Option<RawJObject>) -> Result<Option<String>, JniError> {
+		match jstr {
+			None => Ok(None),
+			Some(jstr) => {
+				let s = self.get_string(jstr);
+				self.delete_local_ref(jstr)?;
+				s.map(Some)
+			}
+		}
+	}
+
+	pub(crate) fn array_length(&self, array: RawJObject) -> Result<usize, JniError> {
+		Ok(from_jsize(jni_unchecked!(*self, GetArrayLength, array.as_ptr()))?)
+	}
+
+	#[cfg(feature = "collections")]
+	pub(crate) fn get_object_array_element(&self, array: RawJObject, index: usize) -> Result<Option<RawJObject>, JniError> {
+		let elem = jni_unchecked!(*self, GetObjectArrayElement, array.as_ptr(), to_jsize(index)?);
+		self.exception_check()?;
+		Ok(NonNull::new(elem))
+	}
+
+	/// Calls an instance method returning `R`, using the JNI function for `R`.
+	pub(crate) fn call_method<R: JniCallable>(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<R, JniError> {
+		let res = R::call(*self, obj, method, args)?;
+		self.check_call(method)?;
+		Ok(res)
+	}
+
+	/// Calls a static method returning `R`, using the JNI function for `R`.
+	pub(crate) fn call_static_method<R: JniCallable>(&self, class: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<R, JniError> {
+		let res = R::call_static(*self, class, method, args)?;
+		self.check_call(method)?;
+		Ok(res)
+	}
+
+	pub(crate) fn call_object_method(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Option<RawJObject>, JniError> {
+		self.call_method::<js::jobject>(obj, method, args).map(NonNull::new)
+	}
+
+	pub(crate) fn call_static_object_method(&self, class: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<Option<RawJObject>, JniError> {
+		self.call_static_method::<js::jobject>(class, method, args).map(NonNull::new)
+	}
+
+	pub(crate) fn call_static_void_method(&self, class: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<(), JniError> {
+		self.call_static_method(class, method, args)
+	}
+
+	pub(crate) fn new_object(&self, class: RawJObject, ctor: MethodId, args: &[js::jvalue]) -> Result<RawJObject, JniError> {
+		let obj = jni_unchecked!(*self, NewObjectA, class.as_ptr(), ctor.as_raw(), args.as_ptr());
+		self.check_call(ctor)?;
+		NonNull::new(obj).ok_or(JniError::NullPointer("NewObjectA"))
+	}
+
+	#[cfg_attr(not(feature = "experimental"), allow(dead_code))]
+	pub(crate) fn call_int_method(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<js::jint, JniError> {
+		self.call_method(obj, method, args)
+	}
+
+	pub(crate) fn call_long_method(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<js::jlong, JniError> {
+		self.call_method(obj, method, args)
+	}
+
+	pub(crate) fn call_boolean_method(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<bool, JniError> {
+		self.call_method::<js::jboolean>(obj, method, args).map(j2r_bool)
+	}
+
+	pub(crate) fn call_void_method(&self, obj: RawJObject, method: MethodId, args: &[js::jvalue]) -> Result<(), JniError> {
+		self.call_method(obj, method, args)
+	}
+
+	/// Checks for an exception thrown by `method`, naming the method in the log.
+	fn check_call(&self, method: MethodId) -> Result<(), JniError> {
+		let res = self.exception_check();
+		if res.is_err() {
+			log::debug!("{} threw an exception", method);
+		}
+		res
+	}
+
+	/// Runs `func` with a local reference to the current `java.lang.Thread`, deleting it afterwards.
+	fn with_current_thread<O, F: FnOnce(RawJObject) -> Result<O, JniError>>(&self, func: F) -> Result<O, JniError> {
+		JThread::with_current(*self, |_, thread| func(thread))
+	}
+
+	/// Returns the context class loader of the current thread, or None if it is unset.
+	///
+	/// Threads attached from native code commonly have no context class loader, which breaks frameworks that
+	/// resolve classes through it (JNDI, ServiceLoader, etc).
+	///
+	/// Contract: attached, reported
+	pub fn context_class_loader(&self) -> Result<Option<AutoObj<'a, JClassLoader>>, JniError> {
+		let ids = JThread::descriptors(*self);
+		let loader = self.with_current_thread(|thread| self.call_object_method(thread, ids.get_context_class_loader, &[]))?;
+		Ok(loader.map(|obj| AutoObj::from_raw(*self, obj)))
+	}
+
+	/// Sets the context class loader of the current thread.
+	///
+	/// Contract: attached, reported
+	pub fn set_context_class_loader<R: AsRawObject<JClassLoader>>(&self, loader: &R) -> Result<(), JniError> {
+		self.set_context_class_loader_raw(loader.as_raw())
+	}
+
+	fn set_context_class_loader_raw(&self, loader: js::jobject) -> Result<(), JniError> {
+		let ids = JThread::descriptors(*self);
+		self.with_current_thread(|thread| {
+			self.call_void_method(thread, ids.set_context_class_loader, &[js::jvalue { l: loader }])
+		})
+	}
+
+	/// Runs `func` with the current thread's context class loader set to `loader`, restoring the previous one afterwards.
+	///
+	/// The previous loader is restored even if `func` panics.
+	///
+	/// Contract: attached, reported
+	pub fn with_context_loader<R, O, F>(&self, loader: &R, func: F) -> Result<O, JniError>
+		where R: AsRawObject<JClassLoader>, F: FnOnce() -> O
+	{
+		struct Restore<'e> {
+			env: JniEnv<'e>,
+			previous: Option<AutoObj<'e, JClassLoader>>,
+		}
+		impl Drop for Restore<'_> {
+			fn drop(&mut self) {
+				let previous = self.previous.as_ref().map(|l| l.as_raw()).unwrap_or(std::ptr::null_mut());
+				if let Err(e) = self.env.set_context_class_loader_raw(previous) {
+					log::error!("unable to restore previous context class loader: {}", e);
+				}
+			}
+		}
+
+		let _restore = Restore { env: *self, previous: self.context_class_loader()? };
+		self.set_context_class_loader(loader)?;
+		Ok(func())
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum JniError {
+	#[error("attempt to use missing JNIEnv.{} function (incompatible JNI/JVM version?)", .0)]
+	MissingFunction(&'static str),
+	#[error("a Java exception is pending")]
+	JavaException,
+	#[error("JNI function {} unexpectedly returned null", .0)]
+	NullPointer(&'static str),
+	#[error("JNI function {} returned unexpected result code {}", .0, .1)]
+	UnexpectedResult(&'static str, js::jint),
+	#[error("a JNI function returned a malformed CESU8 string")]
+	BadCesu8String(#[from] cesu8::Cesu8DecodingError),
+	#[error(transparent)]
+	Size(#[from] SizeError),
+	#[error("expected a Java array of length {}, but it had length {}", .expected, .actual)]
+	LengthMismatch { expected: usize, actual: usize },
+	#[error("expected an instance of {}", .0)]
+	NotInstance(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::env::JniError;
+	use crate::jref::AutoObj;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JClassLoader;
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn context_class_loader_roundtrip() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let current = || env.context_class_loader().expect("error getting context loader");
+			let is_current = |loader: &AutoObj<JClassLoader>| {
+				let current = current().expect("context loader to be set");
+				env.is_same_object(current.as_raw_nonnull(), loader.as_raw_nonnull()).expect("error comparing loaders")
+			};
+
+			let loader = current().expect("main thread to have a context class loader");
+			let class_loader = env.find_class("java/lang/ClassLoader").expect("error finding ClassLoader");
+			let get_parent = env.get_method_id(class_loader, "getParent", "()Ljava/lang/ClassLoader;").expect("error finding getParent");
+			let parent: AutoObj<JClassLoader> = AutoObj::from_raw(env, env.call_object_method(loader.as_raw_nonnull(), get_parent, &[])
+				.expect("error getting parent loader").expect("application loader to have a parent"));
+			assert!(!is_current(&parent));
+
+			env.set_context_class_loader(&parent).expect("error setting context loader");
+			assert!(is_current(&parent));
+			env.set_context_class_loader_raw(std::ptr::null_mut()).expect("error clearing context loader");
+			assert!(current().is_none());
+
+			let inner = env.with_context_loader(&loader, || is_current(&loader)).expect("error running scoped context loader");
+			assert!(inner);
+			assert!(current().is_none());
+
+			env.set_context_class_loader(&loader).expect("error restoring context loader");
+			assert!(is_current(&loader));
+
+			drop(parent);
+			env.delete_local_ref(class_loader).expect("error deleting local ref");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn throw_well_known() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			env.throw_illegal_argument("bad argument").expect("error throwing");
+			assert!(matches!(env.exception_check(), Err(JniError::JavaException)));
+
+			let exc = env.exception_occurred().expect("error getting exception").expect("exception to be pending");
+			env.exception_clear().expect("error clearing exception");
+			let class = env.find_class("java/lang/IllegalArgumentException").expect("error finding class");
+			assert!(env.is_instance_of(exc.as_raw_nonnull(), class).expect("error checking instance"));
+			env.exception_check().expect("exception to be cleared");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn call_variants() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let math = env.find_class("java/lang/Math").expect("error finding class");
+
+			let max = env.get_static_method_id(math, "max", "(II)I").expect("error finding method");
+			let res: jni_sys::jint = env.call_static_method(math, max, &[jni_sys::jvalue { i: 3 }, jni_sys::jvalue { i: 7 }]).expect("error calling max");
+			assert_eq!(7, res);
+
+			let sqrt = env.get_static_method_id(math, "sqrt", "(D)D").expect("error finding method");
+			let res: jni_sys::jdouble = env.call_static_method(math, sqrt, &[jni_sys::jvalue { d: 16.0 }]).expect("error calling sqrt");
+			assert_eq!(4.0, res);
+
+			let to_int = env.get_static_method_id(math, "toIntExact", "(J)I").expect("error finding method");
+			let res: Result<jni_sys::jint, _> = env.call_static_method(math, to_int, &[jni_sys::jvalue { j: i64::MAX }]);
+			assert!(matches!(res, Err(JniError::JavaException)));
+			env.exception_clear().expect("error clearing exception");
+
+			env.delete_local_ref(math).expect("error deleting local ref");
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -0,0 +1,212 @@
+//! Helpers for moving pixel data between Rust and `java.awt.image` types.
+//!
+//! Rust-side images are tightly packed 8-bit RGBA, row by row. Java-side pixels are packed `int` ARGB values, as
+//! used by `BufferedImage.TYPE_INT_ARGB` and `BufferedImage.getRGB`.
+
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::array::JPrimitiveArray;
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, JavaClass, RichJavaType};
+use crate::lang::{class_ref, JClass};
+use crate::to_jsize;
+
+/// `BufferedImage.TYPE_INT_ARGB`
+const TYPE_INT_ARGB: js::jint = 2;
+
+fn rgba_len(width: usize, height: usize) -> Result<usize, JniError> {
+	width.checked_mul(height)
+		.and_then(|px| px.checked_mul(4))
+		.ok_or(JniError::Size(crate::SizeError::TooLarge(usize::MAX)))
+}
+
+/// Packs RGBA bytes into ARGB ints. `argb` must hold exactly a quarter as many elements as `rgba`.
+pub fn rgba_to_argb(rgba: &[u8], argb: &mut [js::jint]) {
+	assert_eq!(rgba.len(), argb.len() * 4, "RGBA buffer must hold 4 bytes per ARGB pixel");
+	for (px, out) in rgba.chunks_exact(4).zip(argb.iter_mut()) {
+		*out = i32::from_be_bytes([px[3], px[0], px[1], px[2]]);
+	}
+}
+
+/// Unpacks ARGB ints into RGBA bytes. `rgba` must hold exactly four times as many elements as `argb`.
+pub fn argb_to_rgba(argb: &[js::jint], rgba: &mut [u8]) {
+	assert_eq!(rgba.len(), argb.len() * 4, "RGBA buffer must hold 4 bytes per ARGB pixel");
+	for (px, out) in argb.iter().zip(rgba.chunks_exact_mut(4)) {
+		let [a, r, g, b] = px.to_be_bytes();
+		out.copy_from_slice(&[r, g, b, a]);
+	}
+}
+
+/// Creates a Java `int[]` of ARGB pixels from an RGBA image.
+pub fn rgba_to_int_array<'a>(env: JniEnv<'a>, rgba: &[u8], width: usize, height: usize) -> Result<AutoObj<'a, JPrimitiveArray<js::jint>>, JniError> {
+	let expected = rgba_len(width, height)?;
+	if rgba.len() != expected {
+		return Err(JniError::LengthMismatch { expected, actual: rgba.len() });
+	}
+
+	let array = JPrimitiveArray::new(env, width * height)?;
+	// SAFETY: packing uses no JNI, and the lengths were checked above so it doesn't panic
+	unsafe { JPrimitiveArray::with_critical(env, &array, |argb| rgba_to_argb(rgba, argb))? };
+	Ok(array)
+}
+
+/// Copies a Java `int[]` of ARGB pixels into an RGBA buffer, which must be exactly four times the array's length.
+pub fn int_array_to_rgba<R: AsRawObject<JPrimitiveArray<js::jint>>>(env: JniEnv<'_>, array: &R, rgba: &mut [u8]) -> Result<(), JniError> {
+	let expected = JPrimitiveArray::len(env, array)? * 4;
+	if rgba.len() != expected {
+		return Err(JniError::LengthMismatch { expected, actual: rgba.len() });
+	}
+	// SAFETY: unpacking uses no JNI, and the lengths were checked above so it doesn't panic
+	unsafe { JPrimitiveArray::with_critical(env, array, |argb| argb_to_rgba(argb, rgba)) }
+}
+
+/// `java.awt.image.BufferedImage`
+#[derive(Debug)]
+pub struct JBufferedImage;
+#[derive(Debug)]
+pub struct BufferedImageIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	pub(crate) get_width: MethodId,
+	pub(crate) get_height: MethodId,
+	pub(crate) get_rgb: MethodId,
+	pub(crate) get_raster: MethodId,
+	pub(crate) raster_get_data_buffer: MethodId,
+	pub(crate) data_buffer_int_get_data: MethodId,
+}
+impl RichJavaType for JBufferedImage {
+	type IDs = BufferedImageIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static BufferedImageIDs {
+		static IDS: OnceCell<BufferedImageIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JBufferedImage::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+
+			let raster = env.find_class("java/awt/image/Raster")?;
+			let raster_get_data_buffer = env.get_method_id(raster, "getDataBuffer", "()Ljava/awt/image/DataBuffer;")?;
+			env.delete_local_ref(raster)?;
+
+			let data_buffer_int = env.find_class("java/awt/image/DataBufferInt")?;
+			let data_buffer_int_get_data = env.get_method_id(data_buffer_int, "getData", "()[I")?;
+			env.delete_local_ref(data_buffer_int)?;
+
+			Ok(BufferedImageIDs {
+				ctor: env.get_method_id(raw, "<init>", "(III)V")?,
+				get_width: env.get_method_id(raw, "getWidth", "()I")?,
+				get_height: env.get_method_id(raw, "getHeight", "()I")?,
+				get_rgb: env.get_method_id(raw, "getRGB", "(IIII[III)[I")?,
+				get_raster: env.get_method_id(raw, "getRaster", "()Ljava/awt/image/WritableRaster;")?,
+				raster_get_data_buffer,
+				data_buffer_int_get_data,
+				class,
+			})
+		}).expect("unable to resolve java/awt/image/BufferedImage descriptors");
+		ids
+	}
+}
+impl JavaClass for JBufferedImage {
+	const INTERNAL_NAME: &'static str = "java/awt/image/BufferedImage";
+}
+
+impl JBufferedImage {
+	/// Creates a `TYPE_INT_ARGB` BufferedImage holding a copy of an RGBA image.
+	///
+	/// Pixels are written directly into the image's backing `int[]`.
+	pub fn from_rgba<'a>(env: JniEnv<'a>, rgba: &[u8], width: usize, height: usize) -> Result<AutoObj<'a, JBufferedImage>, JniError> {
+		let expected = rgba_len(width, height)?;
+		if rgba.len() != expected {
+			return Err(JniError::LengthMismatch { expected, actual: rgba.len() });
+		}
+
+		let ids = JBufferedImage::descriptors(env);
+		let image = env.new_object(ids.class.as_raw_nonnull(), ids.ctor, &[
+			js::jvalue { i: to_jsize(width)? },
+			js::jvalue { i: to_jsize(height)? },
+			js::jvalue { i: TYPE_INT_ARGB },
+		])?;
+		let image: AutoObj<JBufferedImage> = AutoObj::from_raw(env, image);
+
+		let raster = env.call_object_method(image.as_raw_nonnull(), ids.get_raster, &[])?
+			.ok_or(JniError::NullPointer("BufferedImage.getRaster"))?;
+		let buffer = env.call_object_method(raster, ids.raster_get_data_buffer, &[]);
+		env.delete_local_ref(raster)?;
+		let buffer = buffer?.ok_or(JniError::NullPointer("Raster.getDataBuffer"))?;
+		let data = env.call_object_method(buffer, ids.data_buffer_int_get_data, &[]);
+		env.delete_local_ref(buffer)?;
+		let data: AutoObj<JPrimitiveArray<js::jint>> = AutoObj::from_raw(env, data?.ok_or(JniError::NullPointer("DataBufferInt.getData"))?);
+
+		// SAFETY: packing uses no JNI, and a new image's data holds exactly width * height pixels, so it doesn't panic
+		unsafe { JPrimitiveArray::with_critical(env, &data, |argb| rgba_to_argb(rgba, argb))? };
+		drop(data);
+		Ok(image)
+	}
+
+	/// Returns the dimensions of an image.
+	pub fn dimensions<R: AsRawObject<JBufferedImage>>(env: JniEnv<'_>, image: &R) -> Result<(usize, usize), JniError> {
+		let ids = JBufferedImage::descriptors(env);
+		let width = env.call_int_method(raw_nonnull(image), ids.get_width, &[])?;
+		let height = env.call_int_method(raw_nonnull(image), ids.get_height, &[])?;
+		Ok((crate::from_jsize(width)?, crate::from_jsize(height)?))
+	}
+
+	/// Copies any BufferedImage into an RGBA buffer, returning it along with the image's width and height.
+	///
+	/// Pixels are converted to the default sRGB color model through `BufferedImage.getRGB`.
+	pub fn to_rgba<R: AsRawObject<JBufferedImage>>(env: JniEnv<'_>, image: &R) -> Result<(Vec<u8>, usize, usize), JniError> {
+		let ids = JBufferedImage::descriptors(env);
+		let (width, height) = JBufferedImage::dimensions(env, image)?;
+		let pixels = env.call_object_method(raw_nonnull(image), ids.get_rgb, &[
+			js::jvalue { i: 0 },
+			js::jvalue { i: 0 },
+			js::jvalue { i: to_jsize(width)? },
+			js::jvalue { i: to_jsize(height)? },
+			js::jvalue { l: std::ptr::null_mut() },
+			js::jvalue { i: 0 },
+			js::jvalue { i: to_jsize(width)? },
+		])?.ok_or(JniError::NullPointer("BufferedImage.getRGB"))?;
+		let pixels: AutoObj<JPrimitiveArray<js::jint>> = AutoObj::from_raw(env, pixels);
+
+		let mut rgba = vec![0; rgba_len(width, height)?];
+		int_array_to_rgba(env, &pixels, &mut rgba)?;
+		drop(pixels);
+		Ok((rgba, width, height))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::experimental::image::{argb_to_rgba, rgba_to_argb, JBufferedImage};
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	#[test]
+	fn pixel_packing() {
+		let rgba = [0x11, 0x22, 0x33, 0x44, 0xff, 0x00, 0x80, 0xff];
+		let mut argb = [0; 2];
+		rgba_to_argb(&rgba, &mut argb);
+		assert_eq!([0x44112233, 0xffff0080u32 as i32], argb);
+
+		let mut back = [0; 8];
+		argb_to_rgba(&argb, &mut back);
+		assert_eq!(rgba, back);
+	}
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn buffered_image_roundtrip() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let (width, height) = (3, 2);
+			let rgba: Vec<u8> = (0..width * height * 4).map(|i| (i * 10) as u8).collect();
+			let image = JBufferedImage::from_rgba(env, &rgba, width, height).expect("error creating image");
+			assert_eq!((width, height), JBufferedImage::dimensions(env, &image).expect("error reading dimensions"));
+
+			let (read, w, h) = JBufferedImage::to_rgba(env, &image).expect("error reading image");
+			assert_eq!((rgba, width, height), (read, w, h));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
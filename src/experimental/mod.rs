@@ -0,0 +1,8 @@
+//! Subsystems that are still finding their shape.
+//!
+//! Everything outside this module follows semver: breaking changes only land with a new minor version while the
+//! crate is pre-1.0. Modules in here may change or move between any two releases, and are only compiled with the
+//! `experimental` feature enabled. Once an API has settled it graduates to a top-level module.
+
+pub mod image;
+pub mod sound;
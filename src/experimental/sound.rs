@@ -0,0 +1,258 @@
+//! Helpers for streaming PCM audio through `javax.sound.sampled` lines.
+//!
+//! Audio is exchanged as raw bytes in the line's `AudioFormat`; encoding samples into that format is left to the
+//! caller. A [`PcmBuffer`] keeps a single Java `byte[]` around so that streaming doesn't allocate per write.
+
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::array::{as_jbytes, as_jbytes_mut, JPrimitiveArray};
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, JavaClass, RichJavaType};
+use crate::{from_jsize, to_jsize, SizeError};
+
+/// `javax.sound.sampled.SourceDataLine`
+#[derive(Debug)]
+pub struct JSourceDataLine;
+#[derive(Debug)]
+pub struct SourceDataLineIDs {
+	pub(crate) write: MethodId,
+}
+impl RichJavaType for JSourceDataLine {
+	type IDs = SourceDataLineIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static SourceDataLineIDs {
+		static IDS: OnceCell<SourceDataLineIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JSourceDataLine::INTERNAL_NAME)?;
+			let ids = SourceDataLineIDs {
+				write: env.get_method_id(class, "write", "([BII)I")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve javax/sound/sampled/SourceDataLine descriptors");
+		ids
+	}
+}
+impl JavaClass for JSourceDataLine {
+	const INTERNAL_NAME: &'static str = "javax/sound/sampled/SourceDataLine";
+}
+
+/// `javax.sound.sampled.TargetDataLine`
+#[derive(Debug)]
+pub struct JTargetDataLine;
+#[derive(Debug)]
+pub struct TargetDataLineIDs {
+	pub(crate) read: MethodId,
+}
+impl RichJavaType for JTargetDataLine {
+	type IDs = TargetDataLineIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static TargetDataLineIDs {
+		static IDS: OnceCell<TargetDataLineIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JTargetDataLine::INTERNAL_NAME)?;
+			let ids = TargetDataLineIDs {
+				read: env.get_method_id(class, "read", "([BII)I")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve javax/sound/sampled/TargetDataLine descriptors");
+		ids
+	}
+}
+impl JavaClass for JTargetDataLine {
+	const INTERNAL_NAME: &'static str = "javax/sound/sampled/TargetDataLine";
+}
+
+/// A reusable Java `byte[]` used to move PCM data to and from data lines.
+///
+/// Writes and reads larger than the buffer are split into multiple calls on the line.
+#[derive(Debug)]
+pub struct PcmBuffer<'a> {
+	env: JniEnv<'a>,
+	array: AutoObj<'a, JPrimitiveArray<js::jbyte>>,
+	capacity: usize,
+}
+
+impl<'a> PcmBuffer<'a> {
+	/// Allocates a Java buffer holding up to `capacity` bytes, which should be a multiple of the line's frame size.
+	///
+	/// Returns [`SizeError::Empty`] if `capacity` is zero.
+	pub fn new(env: JniEnv<'a>, capacity: usize) -> Result<PcmBuffer<'a>, JniError> {
+		if capacity == 0 {
+			return Err(SizeError::Empty.into());
+		}
+		let array = JPrimitiveArray::new(env, capacity)?;
+		Ok(PcmBuffer { env, array, capacity })
+	}
+
+	/// The size of the Java buffer, in bytes.
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Writes all of `data` to the line, blocking until the line has accepted it.
+	///
+	/// Returns the number of bytes written, which is only less than `data.len()` if the line was stopped or closed. A
+	/// line claiming to have written more than it was given is reported as [`JniError::UnexpectedResult`].
+	pub fn write_to<R: AsRawObject<JSourceDataLine>>(&self, line: &R, data: &[u8]) -> Result<usize, JniError> {
+		let ids = JSourceDataLine::descriptors(self.env);
+		let mut written = 0;
+		for chunk in data.chunks(self.capacity) {
+			JPrimitiveArray::write_region(self.env, &self.array, 0, as_jbytes(chunk))?;
+			let n = self.env.call_int_method(raw_nonnull(line), ids.write, &[
+				js::jvalue { l: self.array.as_raw_nonnull().as_ptr() },
+				js::jvalue { i: 0 },
+				js::jvalue { i: to_jsize(chunk.len())? },
+			])?;
+			let n = checked_count("SourceDataLine.write", n, chunk.len())?;
+			written += n;
+			if n < chunk.len() {
+				break;
+			}
+		}
+		Ok(written)
+	}
+
+	/// Reads from the line into `buf`, blocking until it is filled.
+	///
+	/// Returns the number of bytes read, which is only less than `buf.len()` if the line was stopped or closed. A line
+	/// claiming to have read more than it was asked for is reported as [`JniError::UnexpectedResult`].
+	pub fn read_from<R: AsRawObject<JTargetDataLine>>(&self, line: &R, buf: &mut [u8]) -> Result<usize, JniError> {
+		let ids = JTargetDataLine::descriptors(self.env);
+		let mut read = 0;
+		for chunk in buf.chunks_mut(self.capacity) {
+			let n = self.env.call_int_method(raw_nonnull(line), ids.read, &[
+				js::jvalue { l: self.array.as_raw_nonnull().as_ptr() },
+				js::jvalue { i: 0 },
+				js::jvalue { i: to_jsize(chunk.len())? },
+			])?;
+			let n = checked_count("TargetDataLine.read", n, chunk.len())?;
+			JPrimitiveArray::read_region(self.env, &self.array, 0, as_jbytes_mut(&mut chunk[..n]))?;
+			read += n;
+			if n < chunk.len() {
+				break;
+			}
+		}
+		Ok(read)
+	}
+}
+
+/// Converts the byte count returned by a line's `write` or `read`, which can't be more than it was asked to transfer.
+fn checked_count(method: &'static str, n: js::jint, requested: usize) -> Result<usize, JniError> {
+	match from_jsize(n)? {
+		n if n <= requested => Ok(n),
+		_ => Err(JniError::UnexpectedResult(method, n)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	use jni_sys as js;
+
+	use crate::array::JavaPrimitive;
+	use crate::classfile::ClassFile;
+	use crate::env::{JniEnv, JniError};
+	use crate::experimental::sound::{JSourceDataLine, JTargetDataLine, PcmBuffer};
+	use crate::jref::{AsRawObject, AutoObj, JavaClass};
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JObject;
+	use crate::SizeError;
+
+	/// Bytes written to the loopback line and not read back yet
+	static LOOPBACK: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+	/// Makes the loopback line's `read` claim one more byte than it was asked for
+	static OVERREPORT: AtomicBool = AtomicBool::new(false);
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn write_and_read_round_trip() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert!(matches!(PcmBuffer::new(env, 0), Err(JniError::Size(SizeError::Empty))));
+
+			let line = loopback_line(env);
+			// smaller than the data, so each transfer is split into several calls
+			let buffer = PcmBuffer::new(env, 4).expect("error creating buffer");
+			let data: Vec<u8> = (0..10).collect();
+			assert_eq!(10, buffer.write_to(&line, &data).expect("error writing"));
+			assert_eq!(data, *LOOPBACK.lock().unwrap());
+
+			let mut read = [0; 10];
+			assert_eq!(10, buffer.read_from(&line, &mut read).expect("error reading"));
+			assert_eq!(&data[..], &read[..]);
+			// an empty line reads nothing, as a closed one would
+			assert_eq!(0, buffer.read_from(&line, &mut read).expect("error reading"));
+
+			OVERREPORT.store(true, Ordering::SeqCst);
+			let res = buffer.read_from(&line, &mut read[..3]);
+			assert!(matches!(res, Err(JniError::UnexpectedResult("TargetDataLine.read", 4))), "{:?}", res);
+
+			drop(buffer);
+			drop(line);
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+
+	/// An instance of a class implementing both `SourceDataLine.write` and `TargetDataLine.read` through [`LOOPBACK`]
+	struct Loopback<'a>(AutoObj<'a, JObject>);
+	impl AsRawObject<JSourceDataLine> for Loopback<'_> {
+		fn as_raw(&self) -> js::jobject {
+			self.0.as_raw()
+		}
+	}
+	impl AsRawObject<JTargetDataLine> for Loopback<'_> {
+		fn as_raw(&self) -> js::jobject {
+			self.0.as_raw()
+		}
+	}
+
+	fn loopback_line(env: JniEnv<'_>) -> Loopback<'_> {
+		type Transfer = extern "system" fn(*mut js::JNIEnv, js::jobject, js::jbyteArray, js::jint, js::jint) -> js::jint;
+
+		// the other interface methods are left unimplemented, and would throw AbstractMethodError
+		let mut class = ClassFile::new(ClassFile::ACC_PUBLIC | ClassFile::ACC_SUPER, "yajnir/test/Loopback", "java/lang/Object");
+		let [hi, lo] = class.methodref("java/lang/Object", "<init>", "()V").to_be_bytes();
+		class.implements(JSourceDataLine::INTERNAL_NAME)
+			.implements(JTargetDataLine::INTERNAL_NAME)
+			// aload_0, invokespecial Object.<init>, return
+			.method(ClassFile::ACC_PUBLIC, "<init>", "()V", 1, 1, &[0x2a, 0xb7, hi, lo, 0xb1])
+			.native_method(ClassFile::ACC_PUBLIC, "write", "([BII)I")
+			.native_method(ClassFile::ACC_PUBLIC, "read", "([BII)I");
+		// SAFETY: both functions match the ([BII)I descriptor
+		let class = unsafe {
+			class.define(env, &[
+				("write", "([BII)I", write as Transfer as *mut std::ffi::c_void),
+				("read", "([BII)I", read as Transfer as *mut std::ffi::c_void),
+			])
+		}.expect("error defining loopback class");
+
+		let ctor = env.get_method_id(class.as_raw_nonnull(), "<init>", "()V").expect("error finding constructor");
+		let line = env.new_object(class.as_raw_nonnull(), ctor, &[]).expect("error creating loopback line");
+		Loopback(AutoObj::from_raw(env, line))
+	}
+
+	extern "system" fn write(_env: *mut js::JNIEnv, _this: js::jobject, array: js::jbyteArray, offset: js::jint, len: js::jint) -> js::jint {
+		let env = JniEnv::current().expect("native method to be called on an attached thread");
+		let mut buf = vec![0; len as usize];
+		js::jbyte::get_region(env, std::ptr::NonNull::new(array).expect("array to be non-null"), offset, &mut buf).expect("error reading array");
+		LOOPBACK.lock().unwrap().extend(buf.iter().map(|b| *b as u8));
+		len
+	}
+
+	extern "system" fn read(_env: *mut js::JNIEnv, _this: js::jobject, array: js::jbyteArray, offset: js::jint, len: js::jint) -> js::jint {
+		let env = JniEnv::current().expect("native method to be called on an attached thread");
+		let mut loopback = LOOPBACK.lock().unwrap();
+		let n = loopback.len().min(len as usize);
+		let buf: Vec<js::jbyte> = loopback.drain(..n).map(|b| b as js::jbyte).collect();
+		js::jbyte::set_region(env, std::ptr::NonNull::new(array).expect("array to be non-null"), offset, &buf).expect("error writing array");
+		if OVERREPORT.load(Ordering::SeqCst) {
+			return len + 1;
+		}
+		n as js::jint
+	}
+}
@@ -0,0 +1,111 @@
+//! Machine-readable safety contracts for the crate's public wrappers.
+//!
+//! Each entry records which thread a function may be called from, how it interacts with pending Java exceptions, and
+//! whether it restricts other JNI use while running. Tooling can use this to check that a sequence of calls is legal
+//! without parsing documentation.
+//!
+//! Contracts live on the wrappers themselves, as a doc comment line such as `Contract: attached, reported, critical`,
+//! and the build script collects them into the table. The line lists a [`ThreadRequirement`] (`any` or `attached`),
+//! then an [`ExceptionBehavior`] (`unaffected`, `reported`, `throws` or `inspects`), then the flags `pending` for
+//! [`Contract::allowed_while_pending`] and `critical` for [`Contract::critical`].
+
+/// Which threads a function may be called from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ThreadRequirement {
+	/// Any thread, attached to a JVM or not
+	Any,
+	/// Only the thread that owns the `JniEnv` passed in
+	Attached,
+}
+
+/// How a function interacts with Java exceptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExceptionBehavior {
+	/// Does not run Java code, so it cannot raise an exception
+	Unaffected,
+	/// A Java exception raised during the call is returned as `JniError::JavaException` and left pending
+	Reported,
+	/// Intentionally leaves a new exception pending on success
+	Throws,
+	/// Inspects or clears the pending exception
+	Inspects,
+}
+
+/// The contract of a single public function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Contract {
+	/// The function's path within the crate, such as `JniEnv::exception_check`
+	pub name: &'static str,
+	pub thread: ThreadRequirement,
+	pub exceptions: ExceptionBehavior,
+	/// Whether the function may be called while an exception is pending
+	pub allowed_while_pending: bool,
+	/// Whether no other JNI functions may be called until the function returns, because it holds a critical region
+	pub critical: bool,
+}
+
+macro_rules! contracts {
+	($( $name:literal => $thread:ident, $exc:ident $(, $flag:ident)* ;)*) => {
+		&[$(
+			Contract {
+				name: $name,
+				thread: ThreadRequirement::$thread,
+				exceptions: ExceptionBehavior::$exc,
+				allowed_while_pending: contracts!(@flag pending $($flag)*),
+				critical: contracts!(@flag critical $($flag)*),
+			},
+		)*]
+	};
+	(@flag $want:ident) => { false };
+	(@flag pending pending $($rest:ident)*) => { true };
+	(@flag critical critical $($rest:ident)*) => { true };
+	(@flag $want:ident $other:ident $($rest:ident)*) => { contracts!(@flag $want $($rest)*) };
+}
+
+// generated by build.rs from the `Contract:` lines in the wrappers' doc comments
+static CONTRACTS: &[Contract] = include!(concat!(env!("OUT_DIR"), "/contracts.rs"));
+
+/// Returns the contract for a public function, by its path within the crate such as `JniEnv::exception_check`.
+///
+/// Functions without a contract of their own that take a `JniEnv` (such as the descriptor wrappers in
+/// [`util`](crate::util) or [`zip`](crate::zip)) follow the default contract: attached thread only, exceptions
+/// reported, and not allowed while an exception is pending. Functions that don't take one, such as those of
+/// [`BufferPool`](crate::buffer::BufferPool), have their own contracts.
+pub fn contract(name: &str) -> Option<&'static Contract> {
+	CONTRACTS.iter().find(|c| c.name == name)
+}
+
+/// Returns every registered contract.
+pub fn contracts() -> &'static [Contract] {
+	CONTRACTS
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use crate::introspect::{contract, contracts, ExceptionBehavior, ThreadRequirement};
+
+	#[test]
+	fn contract_lookup() {
+		let names: HashSet<_> = contracts().iter().map(|c| c.name).collect();
+		assert_eq!(names.len(), contracts().len(), "contract names must be unique");
+
+		let check = contract("JniEnv::exception_check").expect("contract to be registered");
+		assert_eq!(ThreadRequirement::Attached, check.thread);
+		assert_eq!(ExceptionBehavior::Inspects, check.exceptions);
+		assert!(check.allowed_while_pending && !check.critical);
+
+		let critical = contract("JPrimitiveArray::with_critical").expect("contract to be registered");
+		assert!(critical.critical && !critical.allowed_while_pending);
+
+		let pending = contract("Cleaner::pending").expect("contract to be registered");
+		assert_eq!(ThreadRequirement::Any, pending.thread);
+		assert_eq!(Some(ThreadRequirement::Any), contract("BufferPool::take").map(|c| c.thread));
+		assert_eq!(Some(ThreadRequirement::Any), contract("jvm::global").map(|c| c.thread));
+		assert_eq!(None, contract("JniEnv::does_not_exist"));
+	}
+}
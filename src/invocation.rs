@@ -0,0 +1,182 @@
+//! Loading the JVM's invocation API from `libjvm` at runtime, instead of linking against it.
+//!
+//! Only compiled with the `invocation` feature enabled. Binaries built this way start without a JVM installed, and
+//! only need one once a [`JavaVM`](crate::jvm::JavaVM) is created. If [`load`] has not been called by then, the
+//! library is looked up by its platform name ([`DEFAULT_LIBRARY`]) on the system's library search path.
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::OnceCell;
+
+use crate::jvm::{CreateJavaVMFn, GetCreatedJavaVMsFn, GetDefaultJavaVMInitArgsFn, InvocationApi};
+
+/// The file name of the JVM library on this platform
+#[cfg(target_os = "windows")]
+pub const DEFAULT_LIBRARY: &str = "jvm.dll";
+/// The file name of the JVM library on this platform
+#[cfg(target_os = "macos")]
+pub const DEFAULT_LIBRARY: &str = "libjvm.dylib";
+/// The file name of the JVM library on this platform
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub const DEFAULT_LIBRARY: &str = "libjvm.so";
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum LoadError {
+	#[error("unable to load JVM library {:?}: {}", .path, .reason)]
+	Open { path: PathBuf, reason: String },
+	#[error("JVM library {:?} does not export {}", .path, .symbol)]
+	MissingSymbol { path: PathBuf, symbol: &'static str },
+	#[error("a different JVM library has already been loaded, from {:?}", .0)]
+	AlreadyLoaded(PathBuf),
+}
+
+#[derive(Debug)]
+struct Library {
+	path: PathBuf,
+	api: InvocationApi,
+}
+
+// libjvm can't be unloaded once a VM has been created, so it's loaded at most once and kept for the process lifetime
+static LIBRARY: OnceCell<Library> = OnceCell::new();
+
+/// Loads the JVM library at `path`, such as `$JAVA_HOME/lib/server/libjvm.so`, for all later VM operations.
+///
+/// Only one library can be loaded per process. Loading the same path again is a no-op.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(), LoadError> {
+	let path = path.as_ref();
+	let lib = LIBRARY.get_or_try_init(|| open(path))?;
+	if lib.path != path {
+		return Err(LoadError::AlreadyLoaded(lib.path.clone()));
+	}
+	Ok(())
+}
+
+/// Returns the path the JVM library was loaded from, if it has been loaded.
+pub fn loaded_path() -> Option<&'static Path> {
+	LIBRARY.get().map(|lib| lib.path.as_path())
+}
+
+/// Returns the invocation API, loading [`DEFAULT_LIBRARY`] if no library has been loaded yet.
+pub(crate) fn api() -> Result<InvocationApi, LoadError> {
+	LIBRARY.get_or_try_init(|| open(Path::new(DEFAULT_LIBRARY))).map(|lib| lib.api)
+}
+
+fn open(path: &Path) -> Result<Library, LoadError> {
+	let handle = sys::open(path).map_err(|reason| LoadError::Open { path: path.to_owned(), reason })?;
+	let symbol = |name: &'static str| -> Result<*mut c_void, LoadError> {
+		let cname = format!("{}\0", name);
+		let sym = sys::symbol(handle, cname.as_ptr() as *const c_char);
+		if sym.is_null() {
+			return Err(LoadError::MissingSymbol { path: path.to_owned(), symbol: name });
+		}
+		Ok(sym)
+	};
+
+	// SAFETY: these are the documented signatures of the invocation API exported by every JVM
+	let api = unsafe {
+		InvocationApi {
+			get_default_java_vm_init_args: std::mem::transmute::<*mut c_void, GetDefaultJavaVMInitArgsFn>(symbol("JNI_GetDefaultJavaVMInitArgs")?),
+			create_java_vm: std::mem::transmute::<*mut c_void, CreateJavaVMFn>(symbol("JNI_CreateJavaVM")?),
+			get_created_java_vms: std::mem::transmute::<*mut c_void, GetCreatedJavaVMsFn>(symbol("JNI_GetCreatedJavaVMs")?),
+		}
+	};
+	log::debug!("loaded JVM library from {:?}", path);
+	Ok(Library { path: path.to_owned(), api })
+}
+
+#[cfg(unix)]
+mod sys {
+	use std::ffi::{c_void, CStr, CString};
+	use std::os::raw::{c_char, c_int};
+	use std::os::unix::ffi::OsStrExt;
+	use std::path::Path;
+
+	const RTLD_NOW: c_int = 2;
+
+	#[cfg_attr(target_os = "linux", link(name = "dl"))]
+	extern "C" {
+		fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+		fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+		fn dlerror() -> *mut c_char;
+	}
+
+	pub(super) fn open(path: &Path) -> Result<*mut c_void, String> {
+		let cpath = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+		// SAFETY: cpath is a valid C string, and dlerror's message is copied out before any other dl* call
+		unsafe {
+			let handle = dlopen(cpath.as_ptr(), RTLD_NOW);
+			if handle.is_null() {
+				let err = dlerror();
+				return Err(if err.is_null() { String::from("unknown error") } else { CStr::from_ptr(err).to_string_lossy().into_owned() });
+			}
+			Ok(handle)
+		}
+	}
+
+	pub(super) fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+		// SAFETY: handle came from dlopen, and name is NUL-terminated
+		unsafe { dlsym(handle, name) }
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::ffi::c_void;
+	use std::os::raw::c_char;
+	use std::os::windows::ffi::OsStrExt;
+	use std::path::Path;
+
+	/// Searches the loaded library's own directory for its dependencies, instead of the application's
+	const LOAD_WITH_ALTERED_SEARCH_PATH: u32 = 0x0000_0008;
+
+	#[link(name = "kernel32")]
+	extern "system" {
+		fn LoadLibraryExW(filename: *const u16, file: *mut c_void, flags: u32) -> *mut c_void;
+		fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+	}
+
+	pub(super) fn open(path: &Path) -> Result<*mut c_void, String> {
+		let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+		// jvm.dll imports DLLs from the JDK's bin directory, which isn't on the default search path. The altered search
+		// path is only defined for absolute paths, so bare names keep the default search.
+		let flags = if path.is_absolute() { LOAD_WITH_ALTERED_SEARCH_PATH } else { 0 };
+		// SAFETY: wide is a NUL-terminated UTF-16 string, and the reserved file handle is null
+		let handle = unsafe { LoadLibraryExW(wide.as_ptr(), std::ptr::null_mut(), flags) };
+		if handle.is_null() {
+			return Err(std::io::Error::last_os_error().to_string());
+		}
+		Ok(handle)
+	}
+
+	pub(super) fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+		// SAFETY: handle came from LoadLibraryExW, and name is NUL-terminated
+		unsafe { GetProcAddress(handle, name) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use crate::invocation::{self, LoadError, DEFAULT_LIBRARY};
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn loads_on_first_use() {
+			match invocation::load("/does/not/exist/libjvm.so") {
+				Err(LoadError::Open { .. }) => {},
+				other => panic!("expected open failure, got {:?}", other),
+			}
+			assert_eq!(None, invocation::loaded_path());
+
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert_eq!(Some(Path::new(DEFAULT_LIBRARY)), invocation::loaded_path());
+			assert!(matches!(invocation::load("other/libjvm.so"), Err(LoadError::AlreadyLoaded(_))));
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
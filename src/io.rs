@@ -0,0 +1,96 @@
+//! Helpers for handing file descriptors between Rust and `java.io`.
+//!
+//! Only available on unix, where a `java.io.FileDescriptor` is a thin wrapper around a native fd number.
+
+use std::os::unix::io::RawFd;
+
+use once_cell::sync::OnceCell;
+
+use crate::env::{FieldId, JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, JavaClass, RichJavaType};
+use crate::lang::{class_ref, JClass};
+
+/// `java.io.FileDescriptor`
+#[derive(Debug)]
+pub struct JFileDescriptor;
+#[derive(Debug)]
+pub struct FileDescriptorIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	pub(crate) valid: MethodId,
+	pub(crate) fd: FieldId,
+}
+impl RichJavaType for JFileDescriptor {
+	type IDs = FileDescriptorIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static FileDescriptorIDs {
+		static IDS: OnceCell<FileDescriptorIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JFileDescriptor::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(FileDescriptorIDs {
+				ctor: env.get_method_id(raw, "<init>", "()V")?,
+				valid: env.get_method_id(raw, "valid", "()Z")?,
+				fd: env.get_field_id(raw, "fd", "I")?,
+				class,
+			})
+		}).expect("unable to resolve java/io/FileDescriptor descriptors");
+		ids
+	}
+}
+impl JavaClass for JFileDescriptor {
+	const INTERNAL_NAME: &'static str = "java/io/FileDescriptor";
+}
+
+impl JFileDescriptor {
+	/// Wraps an open fd in a new `FileDescriptor`, by setting its private `fd` field.
+	///
+	/// The fd is not duplicated. A bare `FileDescriptor` never closes its fd, but streams or channels created from it
+	/// will when they are closed, so either keep ownership in Rust and don't close those, or hand ownership over
+	/// (e.g. with `IntoRawFd`) and let Java close it.
+	///
+	/// Wrapping sockets as a `SocketChannel` depends on JDK internals and is not supported; `FileInputStream` and
+	/// `FileOutputStream` accept the returned `FileDescriptor` directly.
+	pub fn from_raw_fd(env: JniEnv<'_>, fd: RawFd) -> Result<AutoObj<'_, JFileDescriptor>, JniError> {
+		let ids = JFileDescriptor::descriptors(env);
+		let obj = env.new_object(ids.class.as_raw_nonnull(), ids.ctor, &[])?;
+		let obj: AutoObj<JFileDescriptor> = AutoObj::from_raw(env, obj);
+		env.set_int_field(obj.as_raw_nonnull(), ids.fd, fd)?;
+		Ok(obj)
+	}
+
+	/// Returns the fd wrapped by a `FileDescriptor`, or `None` if it is closed or was never valid.
+	///
+	/// The fd remains owned by Java; duplicate it before using it past the lifetime of the Java object.
+	pub fn as_raw_fd<R: AsRawObject<JFileDescriptor>>(env: JniEnv<'_>, fd: &R) -> Result<Option<RawFd>, JniError> {
+		let ids = JFileDescriptor::descriptors(env);
+		if !env.call_boolean_method(raw_nonnull(fd), ids.valid, &[])? {
+			return Ok(None);
+		}
+		Ok(Some(env.get_int_field(raw_nonnull(fd), ids.fd)?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::os::unix::io::AsRawFd;
+
+	use crate::io::JFileDescriptor;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn file_descriptor_roundtrip() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let file = std::fs::File::open("/dev/null").expect("error opening /dev/null");
+			let jfd = JFileDescriptor::from_raw_fd(env, file.as_raw_fd()).expect("error wrapping fd");
+			assert_eq!(Some(file.as_raw_fd()), JFileDescriptor::as_raw_fd(env, &jfd).expect("error reading fd"));
+
+			let invalid = JFileDescriptor::from_raw_fd(env, -1).expect("error wrapping fd");
+			assert_eq!(None, JFileDescriptor::as_raw_fd(env, &invalid).expect("error reading fd"));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -1,12 +1,14 @@
 use std::marker::PhantomData;
+use std::ops::Deref;
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::env::JniEnv;
-use crate::jvm::JavaVM;
+use crate::env::{JniEnv, JniError};
+use crate::jvm::{JavaVM, VmError};
 
 
-type RawJObject = NonNull<jni_sys::_jobject>;
+pub(crate) type RawJObject = NonNull<jni_sys::_jobject>;
 
 // struct Timer;
 
@@ -37,16 +39,85 @@ type RawJObject = NonNull<jni_sys::_jobject>;
 // 	fn upgrade<'a, T>(kk env: NNJniEnv<'a>) -> Self::Upgraded
 // }
 
+/// Global references currently held by [`GlobalRef`]s, excluding those pinned by descriptor objects
+static LIVE_GLOBALS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how many tracked global references are still alive, as reported by [`JavaVM::destroy`].
+pub(crate) fn live_globals() -> usize {
+	LIVE_GLOBALS.load(Ordering::SeqCst)
+}
+
+/// A JNI global reference, tagged with the VM epoch it was created in.
+///
+/// Tracked references are deleted once the last [`GlobalRef`] or [`GlobalObj`] sharing them is dropped. Pinned ones
+/// live as long as the VM.
+#[derive(Debug)]
+struct GlobalHandle {
+	raw: RawJObject,
+	jvm: JavaVM,
+	epoch: u64,
+	tracked: bool,
+}
+// SAFETY: JNI global references may be used from any thread attached to their JavaVM
+unsafe impl Send for GlobalHandle {}
+unsafe impl Sync for GlobalHandle {}
+impl GlobalHandle {
+	fn new(jvm: JavaVM, raw: RawJObject, tracked: bool) -> GlobalHandle {
+		if tracked {
+			LIVE_GLOBALS.fetch_add(1, Ordering::SeqCst);
+		}
+		GlobalHandle { raw, jvm, epoch: crate::jvm::epoch(), tracked }
+	}
+}
+impl Deref for GlobalHandle {
+	type Target = RawJObject;
+	fn deref(&self) -> &RawJObject {
+		&self.raw
+	}
+}
+impl Drop for GlobalHandle {
+	fn drop(&mut self) {
+		if !self.tracked {
+			return;
+		}
+		LIVE_GLOBALS.fetch_sub(1, Ordering::SeqCst);
+
+		// the VM frees every global reference when it is destroyed, so there is nothing left to delete
+		if self.epoch != crate::jvm::epoch() {
+			log::trace!("not deleting a global reference that outlived its JavaVM");
+			return;
+		}
+		// attaches for the duration of the call if this thread isn't attached, such as when dropped on a plain thread
+		match self.jvm.with_env(|env| env.delete_global_ref(self.raw)) {
+			Ok(Ok(())) => {},
+			Ok(Err(e)) => log::warn!("unable to delete a dropped global reference: {}", e),
+			Err(e) => log::warn!("unable to attach to delete a dropped global reference: {}", e),
+		}
+	}
+}
+
 /// A cacheable, thread-safe global reference to a non-null Java object.
+///
+/// Cloning is cheap, sharing the same JNI global reference, which is deleted once every clone and every [`GlobalObj`]
+/// upgraded from them are dropped, attaching the dropping thread for the call if needed. Use
+/// [`GlobalRef::duplicate`] for an independent global reference instead. Once its VM has been destroyed, methods that would use the reference
+/// return [`VmError::Destroyed`], and dropping it deletes nothing.
 #[derive(Debug)]
 pub struct GlobalRef<T: RichJavaType> {
 	jvm: JavaVM,
-	obj: Arc<RawJObject>,
-	desc: Arc<T::IDs>,
+	obj: Arc<GlobalHandle>,
+	desc: &'static T::IDs,
 	_phantom: PhantomData<T>,
 }
 
+// SAFETY: JNI global references may be used from any thread attached to their JavaVM
+unsafe impl<T: RichJavaType> Send for GlobalRef<T> where T::IDs: Send + Sync {}
+unsafe impl<T: RichJavaType> Sync for GlobalRef<T> where T::IDs: Send + Sync {}
+
 /// A local reference to a non-null Java object. Note that this type is especially suitable for type-safe method arguments for JNI native methods, when wrapped in Option.
+///
+/// The reference is borrowed rather than owned: it is never deleted by this crate, and lives until the native frame
+/// that received it returns. Use [`AutoRef`] for references this crate should delete.
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct LocalRef<T: RichJavaType> {
@@ -55,6 +126,9 @@ pub struct LocalRef<T: RichJavaType> {
 }
 
 /// A local, scoped reference to a non-null Java object. Note that this type is especially suitable for local variable references emitted from wrapper code.
+///
+/// Unlike a [`LocalRef`], the reference is owned: once bound to its thread as an [`AutoObj`], it is deleted with
+/// `DeleteLocalRef` when dropped, so loops creating many objects don't exhaust the thread's local reference table.
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct AutoRef<T: RichJavaType> {
@@ -65,8 +139,8 @@ pub struct AutoRef<T: RichJavaType> {
 #[derive(Debug)]
 pub struct GlobalObj<'a, T: RichJavaType> {
 	env: JniEnv<'a>,
-	obj: Arc<RawJObject>,
-	desc: Arc<T::IDs>,
+	obj: Arc<GlobalHandle>,
+	desc: &'static T::IDs,
 	_phantom: PhantomData<&'a T>,
 }
 
@@ -74,43 +148,426 @@ pub struct GlobalObj<'a, T: RichJavaType> {
 pub struct LocalObj<'a, T: RichJavaType> {
 	env: JniEnv<'a>,
 	obj: RawJObject,
-	desc: Arc<T::IDs>,
+	desc: &'static T::IDs,
 	_phantom: PhantomData<&'a T>,
 }
 
+/// An owned local reference bound to its thread, which deletes the reference when dropped.
 #[derive(Debug)]
 pub struct AutoObj<'a, T: RichJavaType> {
 	env: JniEnv<'a>,
 	obj: RawJObject,
-	desc: Arc<T::IDs>,
+	/// The VM epoch the reference was created in, as local references can't be deleted once their VM is destroyed
+	epoch: u64,
 	_phantom: PhantomData<&'a T>,
 }
 
+impl<T: RichJavaType> Clone for GlobalRef<T> {
+	fn clone(&self) -> GlobalRef<T> {
+		GlobalRef {
+			jvm: self.jvm,
+			obj: Arc::clone(&self.obj),
+			desc: self.desc,
+			_phantom: PhantomData,
+		}
+	}
+}
+
 impl<T: RichJavaType> GlobalRef<T> {
-	pub fn upgrade<'a>(&'_ self, env: &'a JniEnv<'a>) -> GlobalObj<'a, T> {
-		GlobalObj {
+	/// Creates a new JNI global reference to `obj`, which may be any kind of reference valid on the current thread.
+	pub(crate) fn new(env: JniEnv<'_>, obj: RawJObject) -> Result<GlobalRef<T>, JniError> {
+		GlobalRef::new_handle(env, obj, true)
+	}
+
+	/// Creates a global reference that lives as long as the VM, such as a class held by a descriptor object, and so
+	/// is not counted as outstanding when the VM is destroyed.
+	pub(crate) fn pinned(env: JniEnv<'_>, obj: RawJObject) -> Result<GlobalRef<T>, JniError> {
+		GlobalRef::new_handle(env, obj, false)
+	}
+
+	fn new_handle(env: JniEnv<'_>, obj: RawJObject, tracked: bool) -> Result<GlobalRef<T>, JniError> {
+		let jvm = env.java_vm()?;
+		let global = env.new_global_ref(obj)?;
+		Ok(GlobalRef {
+			jvm,
+			obj: Arc::new(GlobalHandle::new(jvm, global, tracked)),
+			desc: T::descriptors(env),
+			_phantom: PhantomData,
+		})
+	}
+
+	pub(crate) fn as_raw_nonnull(&self) -> RawJObject {
+		**self.obj
+	}
+
+	/// Returns whether the VM this reference belongs to has been destroyed, leaving the reference dangling.
+	pub fn is_destroyed(&self) -> bool {
+		// the epoch is bumped by every destroy, so this is a single atomic load on each bound call
+		self.obj.epoch != crate::jvm::epoch()
+	}
+
+	fn ensure_live(&self) -> Result<(), VmError> {
+		if self.is_destroyed() {
+			return Err(VmError::Destroyed);
+		}
+		Ok(())
+	}
+
+	/// Creates a new JNI global reference to the same object, with a lifetime independent of this one's.
+	///
+	/// Unlike a clone, dropping either reference never affects the other, so subsystems that release references at
+	/// different times don't have to share a reference count.
+	///
+	/// Contract: attached, unaffected
+	pub fn duplicate(&self, env: &JniEnv<'_>) -> Result<GlobalRef<T>, VmError> {
+		self.ensure_live()?;
+		Ok(GlobalRef::new(*env, **self.obj)?)
+	}
+
+	pub fn upgrade<'a>(&'_ self, env: &'a JniEnv<'a>) -> Result<GlobalObj<'a, T>, VmError> {
+		self.ensure_live()?;
+		Ok(GlobalObj {
 			env: *env,
 			obj: Arc::clone(&self.obj),
-			desc: Arc::clone(&self.desc),
+			desc: self.desc,
+			_phantom: PhantomData,
+		})
+	}
+	/// Runs `func` with this reference bound to `env`, which may belong to any thread attached to the VM.
+	///
+	/// This is the receiving half of [`AutoObj::to_global_send`], for using an object on another thread.
+	pub fn with<'a, O, F: FnOnce(&GlobalObj<'a, T>) -> O>(&self, env: JniEnv<'a>, func: F) -> Result<O, VmError> where T: 'a {
+		self.ensure_live()?;
+		let obj = GlobalObj {
+			env,
+			obj: Arc::clone(&self.obj),
+			desc: self.desc,
+			_phantom: PhantomData,
+		};
+		Ok(func(&obj))
+	}
+
+	/// Converts this reference into one typed as an interface (or superclass) that `T` implements.
+	pub fn upcast<I: JavaClass>(self, env: JniEnv<'_>) -> GlobalRef<I> where T: Implements<I> {
+		GlobalRef {
+			jvm: self.jvm,
+			obj: self.obj,
+			desc: I::descriptors(env),
+			_phantom: PhantomData,
+		}
+	}
+}
+impl<'a, T: RichJavaType> AutoObj<'a, T> {
+	/// Wraps a local reference, which will be owned by the returned object.
+	pub(crate) fn from_raw(env: JniEnv<'a>, obj: RawJObject) -> AutoObj<'a, T> {
+		AutoObj {
+			env,
+			obj,
+			epoch: crate::jvm::epoch(),
+			_phantom: PhantomData,
+		}
+	}
+
+	pub(crate) fn as_raw_nonnull(&self) -> RawJObject {
+		self.obj
+	}
+
+	/// Gives up ownership of the local reference without deleting it, such as to rewrap it as another type.
+	pub(crate) fn into_raw(self) -> RawJObject {
+		let this = std::mem::ManuallyDrop::new(self);
+		this.obj
+	}
+
+	/// Creates a global reference to the same object, which can be sent to and used from other threads.
+	///
+	/// Local references are only valid on the thread that created them, so this is how objects move between threads.
+	/// Use [`GlobalRef::with`] on the receiving thread.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_global_send(&self) -> Result<GlobalRef<T>, JniError> where T::IDs: Send + Sync {
+		GlobalRef::new(self.env, self.obj)
+	}
+
+	/// Creates a weak global reference to the same object, which doesn't keep it from being collected.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_weak(&self) -> Result<WeakGlobalRef<T>, JniError> {
+		WeakGlobalRef::new(self.env, self)
+	}
+
+	/// Converts this reference into one typed as an interface (or superclass) that `T` implements.
+	pub fn upcast<I: JavaClass>(self) -> AutoObj<'a, I> where T: Implements<I> {
+		let env = self.env;
+		AutoObj::from_raw(env, self.into_raw())
+	}
+
+	/// Checks whether the referenced object is an instance of `U`, such as an implementation of an interface.
+	///
+	/// Contract: attached, reported
+	pub fn is_instance<U: JavaClass>(&self) -> Result<bool, JniError> {
+		let class = self.env.find_class(U::INTERNAL_NAME)?;
+		let matches = self.env.is_instance_of(self.obj, class);
+		self.env.delete_local_ref(class)?;
+		matches
+	}
+
+	/// Converts this reference into one typed as `U`, after verifying that the object is an instance of it.
+	///
+	/// Returns the original reference as `Ok(Err(self))` if it is not.
+	///
+	/// Contract: attached, reported
+	pub fn downcast<U: JavaClass>(self) -> Result<Result<AutoObj<'a, U>, AutoObj<'a, T>>, JniError> {
+		if self.is_instance::<U>()? {
+			let env = self.env;
+			Ok(Ok(AutoObj::from_raw(env, self.into_raw())))
+		} else {
+			Ok(Err(self))
+		}
+	}
+}
+
+impl<'a, T: RichJavaType> Drop for AutoObj<'a, T> {
+	fn drop(&mut self) {
+		if self.epoch != crate::jvm::epoch() {
+			return;
+		}
+		if let Err(e) = self.env.delete_local_ref(self.obj) {
+			log::warn!("unable to delete a dropped local reference: {}", e);
+		}
+	}
+}
+
+impl<T: RichJavaType> LocalRef<T> {
+	/// Binds this reference to the thread it belongs to, such as a native method's argument to its `JniEnv`.
+	pub fn upgrade<'a>(&self, env: &'a JniEnv<'a>) -> LocalObj<'a, T> {
+		LocalObj {
+			env: *env,
+			obj: self.obj,
+			desc: T::descriptors(*env),
 			_phantom: PhantomData,
 		}
 	}
 }
+
+impl<'a, T: RichJavaType> LocalObj<'a, T> {
+	/// Creates a global reference to the same object, which can be sent to and used from other threads.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_global_send(&self) -> Result<GlobalRef<T>, JniError> where T::IDs: Send + Sync {
+		GlobalRef::new(self.env, self.obj)
+	}
+
+	/// Promotes this reference to a global one, which stays valid after the native method returns.
+	///
+	/// Contract: attached, unaffected
+	pub fn into_global(self) -> Result<GlobalRef<T>, JniError> {
+		GlobalRef::new(self.env, self.obj)
+	}
+
+	/// Creates a weak global reference to the same object, which doesn't keep it from being collected.
+	///
+	/// Contract: attached, unaffected
+	pub fn into_weak(self) -> Result<WeakGlobalRef<T>, JniError> {
+		WeakGlobalRef::new(self.env, &self)
+	}
+
+	/// Creates a new local reference to the same object, owned by the returned object.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_auto(&self) -> Result<AutoObj<'a, T>, JniError> {
+		new_auto(self.env, self.obj)
+	}
+}
+
 impl<'a, T: RichJavaType> GlobalObj<'a, T> {
+	/// Returns the [`GlobalRef`] this was upgraded from, sharing the same JNI global reference.
 	pub fn downgrade(&self) -> GlobalRef<T> {
-		// safety: passed pointer is not null
-		todo!("downgrade globalobj to globalref");
-		// let env: JNIEnv = unsafe { jni::JNIEnv::from_raw(self.env.ptr.as_ptr()) }.unwrap();
-		// let jvm = env.get_java_vm().expect("GetJavaVM failure");
-		// let nnjvm = NNJavaVM {
-		// 	ptr: NonNull::new(jvm.get_java_vm_pointer()).expect("JNIEnv used null JavaVM")
-		// };
+		GlobalRef {
+			jvm: self.obj.jvm,
+			obj: Arc::clone(&self.obj),
+			desc: self.desc,
+			_phantom: PhantomData,
+		}
+	}
+
+	/// Creates a local reference to the same object, owned by the returned object.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_local(&self) -> Result<AutoObj<'a, T>, JniError> {
+		new_auto(self.env, **self.obj)
+	}
+
+	/// Creates a weak global reference to the same object, which doesn't keep it from being collected.
+	///
+	/// Contract: attached, unaffected
+	pub fn to_weak(&self) -> Result<WeakGlobalRef<T>, JniError> {
+		WeakGlobalRef::new(self.env, self)
+	}
+}
+
+/// Creates an owned local reference to `obj`, which must be a strong reference.
+fn new_auto<T: RichJavaType>(env: JniEnv<'_>, obj: RawJObject) -> Result<AutoObj<'_, T>, JniError> {
+	let local = env.new_local_ref(obj)?.ok_or(JniError::NullPointer("NewLocalRef"))?;
+	Ok(AutoObj::from_raw(env, local))
+}
+
+/// A weak global reference to a Java object, which doesn't keep the object (or its class loader) from being collected.
+///
+/// Upgrade it to use the object, which fails once the object has been collected. A reference whose VM has been
+/// destroyed is treated as collected. The weak reference is deleted when this is dropped, attaching the dropping
+/// thread for the call if needed.
+pub struct WeakGlobalRef<T: RichJavaType> {
+	jvm: JavaVM,
+	raw: RawJObject,
+	epoch: u64,
+	_phantom: PhantomData<fn() -> T>,
+}
+
+// SAFETY: JNI weak global references may be used from any thread attached to their JavaVM
+unsafe impl<T: RichJavaType> Send for WeakGlobalRef<T> {}
+unsafe impl<T: RichJavaType> Sync for WeakGlobalRef<T> {}
+
+impl<T: RichJavaType> std::fmt::Debug for WeakGlobalRef<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("WeakGlobalRef").field("jvm", &self.jvm).field("raw", &self.raw).field("epoch", &self.epoch).finish()
+	}
+}
+
+impl<T: RichJavaType> WeakGlobalRef<T> {
+	/// Creates a weak global reference to the object behind any kind of reference valid on the current thread.
+	///
+	/// Contract: attached, unaffected
+	pub fn new<R: AsRawObject<T>>(env: JniEnv<'_>, obj: &R) -> Result<WeakGlobalRef<T>, JniError> {
+		let jvm = env.java_vm()?;
+		let raw = env.new_weak_global_ref(raw_nonnull(obj))?;
+		Ok(WeakGlobalRef { jvm, raw, epoch: crate::jvm::epoch(), _phantom: PhantomData })
+	}
+
+	/// Returns whether the VM this reference belongs to has been destroyed.
+	fn is_destroyed(&self) -> bool {
+		self.epoch != crate::jvm::epoch()
+	}
+
+	/// Returns whether the referenced object has been collected.
+	///
+	/// The object may still be collected right after this returns false, so use [`WeakGlobalRef::upgrade`] to
+	/// actually use it.
+	///
+	/// Contract: attached, unaffected
+	pub fn is_collected(&self, env: &JniEnv<'_>) -> Result<bool, JniError> {
+		if self.is_destroyed() {
+			return Ok(true);
+		}
+		env.is_null_ref(self.raw)
+	}
+
+	/// Returns a strong reference to the object, or None if it has been collected.
+	///
+	/// Contract: attached, unaffected
+	pub fn upgrade<'a>(&self, env: &'a JniEnv<'a>) -> Result<Option<GlobalObj<'a, T>>, JniError> {
+		if self.is_collected(env)? {
+			return Ok(None);
+		}
+		// the object may have been collected since the check
+		Ok(env.try_new_global_ref(self.raw)?.map(|global| GlobalObj {
+			env: *env,
+			obj: Arc::new(GlobalHandle::new(self.jvm, global, true)),
+			desc: T::descriptors(*env),
+			_phantom: PhantomData,
+		}))
+	}
+
+	/// Returns a local reference to the object, or None if it has been collected.
+	fn to_local<'env>(&self, env: JniEnv<'env>) -> Result<Option<AutoObj<'env, T>>, JniError> {
+		if self.is_destroyed() {
+			return Ok(None);
+		}
+		Ok(env.new_local_ref(self.raw)?.map(|local| AutoObj::from_raw(env, local)))
+	}
+}
+
+impl<T: RichJavaType> Drop for WeakGlobalRef<T> {
+	fn drop(&mut self) {
+		if self.is_destroyed() {
+			return;
+		}
+		match self.jvm.with_env(|env| env.delete_weak_global_ref(self.raw)) {
+			Ok(Ok(())) => {},
+			Ok(Err(e)) => log::warn!("unable to delete a dropped weak global reference: {}", e),
+			Err(e) => log::warn!("unable to attach to delete a dropped weak global reference: {}", e),
+		}
+	}
+}
+
+/// Caches a Java object computed on demand, without keeping it alive.
+///
+/// The object is held through a [`WeakGlobalRef`], so the JVM may still collect it (such as when its class loader
+/// is unloaded, or it is a cache the JVM should be able to reclaim under memory pressure). Once it has been collected,
+/// the next [`Memo::get_or_init`] computes it again.
+///
+/// ```no_run
+/// # use yajnir::{env::{JniEnv, JniError}, jref::{AutoObj, Memo}, lang::JThreadGroup};
+/// static WORKERS: Memo<JThreadGroup> = Memo::new();
+///
+/// fn workers(env: JniEnv<'_>) -> Result<AutoObj<'_, JThreadGroup>, JniError> {
+///     WORKERS.get_or_init(env, || JThreadGroup::new(env, "workers"))
+/// }
+/// ```
+pub struct Memo<T: RichJavaType> {
+	slot: Mutex<Option<WeakGlobalRef<T>>>,
+}
 
-		// GlobalRef {
-		// 	jvm: nnjvm,
-		// 	obj_desc: Arc::clone(&self.obj_desc),
-		// 	_phantom: PhantomData,
-		// }
+impl<T: RichJavaType> std::fmt::Debug for Memo<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let cached = self.lock().as_ref().map(|weak| weak.raw);
+		f.debug_struct("Memo").field("weak", &cached).finish()
+	}
+}
+
+impl<T: RichJavaType> Default for Memo<T> {
+	fn default() -> Memo<T> {
+		Memo::new()
+	}
+}
+
+impl<T: RichJavaType> Memo<T> {
+	/// Creates an empty memo, which can be used as a `static`.
+	pub const fn new() -> Memo<T> {
+		Memo { slot: Mutex::new(None) }
+	}
+
+	/// Returns the cached object, unless it hasn't been computed yet or has since been collected.
+	///
+	/// Contract: attached, unaffected
+	pub fn get<'env>(&self, env: JniEnv<'env>) -> Result<Option<AutoObj<'env, T>>, JniError> {
+		match &*self.lock() {
+			Some(weak) => weak.to_local(env),
+			None => Ok(None),
+		}
+	}
+
+	/// Returns the cached object, or caches and returns the one `init` computes if there is none.
+	///
+	/// `init` runs without holding a lock, so threads racing to fill an empty memo may each run it, with the last to
+	/// finish being cached.
+	///
+	/// Contract: attached, reported
+	pub fn get_or_init<'env, F>(&self, env: JniEnv<'env>, init: F) -> Result<AutoObj<'env, T>, JniError>
+		where F: FnOnce() -> Result<AutoObj<'env, T>, JniError>
+	{
+		if let Some(obj) = self.get(env)? {
+			return Ok(obj);
+		}
+		let obj = init()?;
+		let weak = WeakGlobalRef::new(env, &obj)?;
+		// the previous reference is deleted outside the lock
+		let previous = self.lock().replace(weak);
+		drop(previous);
+		Ok(obj)
+	}
+
+	fn lock(&self) -> std::sync::MutexGuard<'_, Option<WeakGlobalRef<T>>> {
+		self.slot.lock().unwrap_or_else(|e| e.into_inner())
 	}
 }
 
@@ -143,12 +600,248 @@ impl<'a, T: RichJavaType> GlobalObj<'a, T> {
 // }
 
 
+/// Provides access to the raw JNI object pointer behind each reference type, for a Java object of type `T`.
+pub trait AsRawObject<T: RichJavaType> {
+	fn as_raw(&self) -> jni_sys::jobject;
+}
+
+/// Returns the non-null pointer behind any reference wrapper.
+pub(crate) fn raw_nonnull<T: RichJavaType, R: AsRawObject<T>>(obj: &R) -> RawJObject {
+	NonNull::new(obj.as_raw()).expect("reference wrappers to never hold null pointers")
+}
+
+macro_rules! impl_as_raw {
+	($($ty:ident$(<$lt:lifetime>)?),*) => {$(
+		impl<$($lt,)? T: RichJavaType> AsRawObject<T> for $ty<$($lt,)? T> {
+			fn as_raw(&self) -> jni_sys::jobject {
+				self.obj.as_ptr()
+			}
+		}
+	)*};
+}
+impl_as_raw!(GlobalRef, LocalRef, AutoRef, GlobalObj<'a>, LocalObj<'a>, AutoObj<'a>);
+
 pub trait RichJavaType {
 	// Descriptor object should contain a GlobalRef to a class, as well as method/field IDs
 	// all of these should be thread/invocation safe, so no specific lifetime requirements
 	// as long as it has been created everything should stay valid
 	// (unless the JVM shuts down - then all bets are off)
-	type IDs;
+	type IDs: 'static;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static Self::IDs;
+}
+
+/// Declares that the Java class `Self` implements the interface `I` (or extends the class `I`), so that references
+/// to it can be upcast without a runtime check.
+///
+/// # Safety
+/// The relationship must hold for every class loaded as `Self`, or `I`'s methods would be called on an incompatible object.
+pub unsafe trait Implements<I: JavaClass>: JavaClass {}
+
+/// A RichJavaType bound to a single, statically known Java class or interface.
+pub trait JavaClass: RichJavaType {
+	/// The internal (slash separated) name of the class, such as `java/lang/Thread`
+	const INTERNAL_NAME: &'static str;
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JObject;
+	#[cfg(feature = "collections")]
+	use crate::{jvm::VmError, lang::JThread, util::JPreferences};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		#[cfg(feature = "collections")]
+		fn upcast_and_downcast() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let prefs = JPreferences::user_root(env).expect("error getting preferences");
+			let obj = prefs.upcast::<JObject>();
+			assert!(obj.is_instance::<JPreferences>().expect("error checking instance"));
+
+			let obj = obj.downcast::<JThread>().expect("error downcasting").expect_err("preferences to not be a thread");
+			let prefs = obj.downcast::<JPreferences>().expect("error downcasting").expect("object to be preferences");
+			let node = JPreferences::node(env, &prefs, &format!("yajnir-test-{}", std::process::id())).expect("error using downcast reference");
+			JPreferences::remove_node(env, &node).expect("error removing node");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		#[cfg(feature = "collections")]
+		fn ship_object_to_other_thread() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let prefs = JPreferences::user_root(env).expect("error getting preferences");
+			let global = prefs.to_global_send().expect("error creating global ref");
+			let global = std::thread::spawn(move || {
+				vm.with_env(|env| {
+					global.with(env, |prefs| {
+						let node = JPreferences::node(env, prefs, &format!("yajnir-test-{}", std::process::id()))?;
+						JPreferences::remove_node(env, &node)
+					})
+				}).expect("error attaching thread").expect("reference to be live").expect("error using shipped reference");
+				global
+			}).join().expect("worker thread panicked");
+
+			assert_eq!(1, vm.destroy().expect("error destroying vm"));
+			assert!(global.is_destroyed());
+			assert!(matches!(global.with(env, |_| ()), Err(VmError::Destroyed)));
+			assert!(matches!(vm.attach_current_thread(), Err(VmError::Destroyed)));
+			assert!(matches!(vm.destroy(), Err(VmError::Destroyed)));
+		}
+
+		#[test]
+		#[cfg(feature = "collections")]
+		fn drop_deletes_global_ref() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let global = JPreferences::user_root(env).expect("error getting preferences")
+				.to_global_send().expect("error creating global ref");
+			let upgraded = global.upgrade(&env).expect("reference to be live");
+			drop(global);
+			assert_eq!(1, crate::jref::live_globals());
+			drop(upgraded);
+			assert_eq!(0, crate::jref::live_globals());
+
+			// dropped on a thread that isn't attached
+			let global = JPreferences::user_root(env).expect("error getting preferences")
+				.to_global_send().expect("error creating global ref");
+			std::thread::spawn(move || {
+				drop(global);
+				assert!(!vm.is_current_thread_attached().expect("error checking attachment"));
+			}).join().expect("worker thread panicked");
+
+			assert_eq!(0, vm.destroy().expect("error destroying vm"));
+		}
+
+		#[test]
+		fn weak_ref_upgrade() {
+			use crate::jref::{AutoObj, WeakGlobalRef};
+			use crate::lang::gc_until;
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let obj: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("weak").expect("error creating string"));
+			let weak = WeakGlobalRef::new(env, &obj).expect("error creating weak ref");
+
+			let strong = weak.upgrade(&env).expect("error upgrading").expect("object to be reachable");
+			assert!(env.is_same_object(obj.as_raw_nonnull(), strong.obj.raw).expect("error comparing objects"));
+			drop(strong);
+			assert_eq!(0, crate::jref::live_globals());
+
+			drop(obj);
+			let collected = gc_until(env, || weak.is_collected(&env).expect("error checking weak ref"));
+			assert!(collected, "object was never collected");
+			assert!(weak.upgrade(&env).expect("error upgrading").is_none());
+
+			vm.destroy().expect("error destroying vm");
+			assert!(weak.is_collected(&env).expect("error checking weak ref"));
+		}
 
-	fn descriptors<'thread>(env: JniEnv<'thread>) -> Arc<Self::IDs>;
+		#[test]
+		fn auto_obj_deletes_on_drop() {
+			use crate::jref::{AutoObj, WeakGlobalRef};
+			use crate::lang::gc_until;
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let obj: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("dropped").expect("error creating string"));
+			let weak = WeakGlobalRef::new(env, &obj).expect("error creating weak ref");
+			drop(obj);
+
+			// only the dropped local kept it reachable
+			let collected = gc_until(env, || weak.is_collected(&env).expect("error checking weak ref"));
+			assert!(collected, "object was never collected");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn converts_between_reference_kinds() {
+			use crate::jref::{AutoObj, LocalRef};
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let auto: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("converted").expect("error creating string"));
+			let same = |other: crate::jref::RawJObject| env.is_same_object(auto.as_raw_nonnull(), other).expect("error comparing objects");
+
+			// as a native method would receive it
+			let arg: LocalRef<JObject> = LocalRef { obj: auto.as_raw_nonnull(), _phantom: std::marker::PhantomData };
+			let local = arg.upgrade(&env);
+			let copy = local.to_auto().expect("error copying local ref");
+			assert!(same(copy.as_raw_nonnull()));
+			let weak = local.into_weak().expect("error creating weak ref");
+			assert!(!weak.is_collected(&env).expect("error checking weak ref"));
+
+			let global = arg.upgrade(&env).into_global().expect("error creating global ref");
+			let upgraded = global.upgrade(&env).expect("reference to be live");
+			assert!(same(upgraded.to_local().expect("error creating local ref").as_raw_nonnull()));
+			assert!(upgraded.to_weak().expect("error creating weak ref").upgrade(&env).expect("error upgrading").is_some());
+
+			// downgrading shares the global reference rather than creating another
+			let downgraded = upgraded.downgrade();
+			drop((global, upgraded));
+			assert_eq!(1, crate::jref::live_globals());
+			drop(downgraded);
+			assert_eq!(0, crate::jref::live_globals());
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn clone_shares_and_duplicate_copies() {
+			use crate::jref::AutoObj;
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let obj: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("shared").expect("error creating string"));
+			let global: crate::jref::GlobalRef<JObject> = crate::jref::GlobalRef::new(env, obj.as_raw_nonnull()).expect("error creating global ref");
+
+			let clone = global.clone();
+			assert_eq!(clone.as_raw_nonnull(), global.as_raw_nonnull());
+			assert_eq!(1, crate::jref::live_globals());
+
+			let duplicate = global.duplicate(&env).expect("error duplicating global ref");
+			assert_ne!(duplicate.as_raw_nonnull(), global.as_raw_nonnull());
+			assert_eq!(2, crate::jref::live_globals());
+
+			drop((global, clone));
+			assert_eq!(1, crate::jref::live_globals());
+			let upgraded = duplicate.upgrade(&env).expect("reference to be live");
+			assert!(env.is_same_object(obj.as_raw_nonnull(), upgraded.obj.raw).expect("error comparing objects"));
+			drop(upgraded);
+
+			assert_eq!(1, vm.destroy().expect("error destroying vm"));
+			assert!(duplicate.is_destroyed());
+		}
+
+		#[test]
+		fn memo_recomputes_after_collection() {
+			use crate::jref::{AutoObj, Memo};
+			use crate::lang::gc_until;
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let memo: Memo<JObject> = Memo::new();
+			let computed = std::cell::Cell::new(0);
+			let init = || {
+				computed.set(computed.get() + 1);
+				env.new_string("memoized").map(|s| AutoObj::from_raw(env, s))
+			};
+
+			let first = memo.get_or_init(env, init).expect("error initializing memo");
+			let second = memo.get_or_init(env, init).expect("error reading memo");
+			assert!(env.is_same_object(first.as_raw_nonnull(), second.as_raw_nonnull()).expect("error comparing objects"));
+			assert_eq!(1, computed.get());
+
+			drop(first);
+			drop(second);
+			// each read returns a new local reference, which is deleted before the next collection
+			let collected = gc_until(env, || memo.get(env).expect("error reading memo").is_none());
+			assert!(collected);
+			memo.get_or_init(env, init).expect("error reinitializing memo");
+			assert_eq!(2, computed.get());
+
+			drop(memo);
+			vm.destroy().expect("error destroying vm");
+		}
+	}
 }
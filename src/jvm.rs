@@ -1,16 +1,23 @@
 use std::ffi::CStr;
 use std::fmt;
 use std::borrow::Cow;
-use std::convert::TryInto;
 use std::ffi::c_void;
 use std::marker::PhantomData;
 use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use log;
 use jni_sys as js;
+use once_cell::sync::OnceCell;
 
-use crate::env::JniEnv;
+use crate::env::{java_cstr, JniEnv};
+use crate::jref::{AsRawObject, GlobalRef};
+use crate::lang::JThreadGroup;
+use crate::{from_jsize, to_jsize, SizeError};
 use crate::j2r_bool;
 use crate::r2j_bool;
 
@@ -21,7 +28,8 @@ use crate::r2j_bool;
 // jni_sys::
 
 /// JNI
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JniVersion {
 	pub major: u16,
 	pub minor: u16,
@@ -34,6 +42,15 @@ impl JniVersion {
 	pub const V1_8: JniVersion = JniVersion::new(1, 8);
 	pub const V9: JniVersion = JniVersion::new(9, 0);
 	pub const V10: JniVersion = JniVersion::new(10, 0);
+	pub const V19: JniVersion = JniVersion::new(19, 0);
+	pub const V20: JniVersion = JniVersion::new(20, 0);
+	pub const V21: JniVersion = JniVersion::new(21, 0);
+
+	/// Every version defined so far, newest first
+	const KNOWN: [JniVersion; 10] = [
+		JniVersion::V21, JniVersion::V20, JniVersion::V19, JniVersion::V10, JniVersion::V9,
+		JniVersion::V1_8, JniVersion::V1_6, JniVersion::V1_4, JniVersion::V1_2, JniVersion::V1_1,
+	];
 
 	pub const fn new(major: u16, minor: u16) -> JniVersion {
 		JniVersion { major, minor }
@@ -45,8 +62,8 @@ impl JniVersion {
 
 	const fn from_native(n: u32) -> JniVersion {
 		JniVersion {
-			major: (n & 0xFFFF0000 >> 16) as u16,
-			minor: (n & 0x0000FFFF >>  0) as u16,
+			major: ((n & 0xFFFF0000) >> 16) as u16,
+			minor:  (n & 0x0000FFFF) as u16,
 		}
 	}
 }
@@ -56,6 +73,38 @@ impl fmt::Display for JniVersion {
 	}
 }
 
+pub(crate) type GetDefaultJavaVMInitArgsFn = unsafe extern "system" fn(args: *mut c_void) -> js::jint;
+pub(crate) type CreateJavaVMFn = unsafe extern "system" fn(pvm: *mut *mut js::JavaVM, penv: *mut *mut c_void, args: *mut c_void) -> js::jint;
+pub(crate) type GetCreatedJavaVMsFn = unsafe extern "system" fn(vms: *mut *mut js::JavaVM, len: js::jsize, n_vms: *mut js::jsize) -> js::jint;
+
+/// The process-level JNI functions used to create and find VMs
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct InvocationApi {
+	pub(crate) get_default_java_vm_init_args: GetDefaultJavaVMInitArgsFn,
+	pub(crate) create_java_vm: CreateJavaVMFn,
+	pub(crate) get_created_java_vms: GetCreatedJavaVMsFn,
+}
+
+#[cfg(not(any(feature = "invocation", all(feature = "android", target_os = "android"))))]
+fn invocation_api() -> Result<InvocationApi, VmError> {
+	Ok(InvocationApi {
+		get_default_java_vm_init_args: js::JNI_GetDefaultJavaVMInitArgs,
+		create_java_vm: js::JNI_CreateJavaVM,
+		get_created_java_vms: js::JNI_GetCreatedJavaVMs,
+	})
+}
+
+#[cfg(all(feature = "invocation", not(all(feature = "android", target_os = "android"))))]
+fn invocation_api() -> Result<InvocationApi, VmError> {
+	Ok(crate::invocation::api()?)
+}
+
+// ART doesn't export the invocation API to apps, see `yajnir::android`
+#[cfg(all(feature = "android", target_os = "android"))]
+fn invocation_api() -> Result<InvocationApi, VmError> {
+	Err(VmError::InvocationUnavailable)
+}
+
 /// A threadsafe pointer to an existing (but not necessarily active) Java VM
 #[repr(transparent)]
 #[derive(Debug, Copy, Clone)]
@@ -63,7 +112,12 @@ pub struct JavaVM {
 	pub(crate) ptr: NonNull<jni_sys::JavaVM>,
 }
 
+// SAFETY: the invocation interface may be used from any thread
+unsafe impl Send for JavaVM {}
+unsafe impl Sync for JavaVM {}
+
 impl JavaVM {
+	/// Contract: any, unaffected
 	pub fn default_args(target_version: JniVersion) -> Result<VmOptions, VmError> {
 		// TODO: Does this have an actual failure state? (other than bad params)
 		
@@ -71,63 +125,84 @@ impl JavaVM {
 			optionString: std::ptr::null_mut(),
 			extraInfo: std::ptr::null_mut(),
 		};
-		let n_options = 64;
-		let mut options: Vec<js::JavaVMOption> = vec![empty_option; n_options];
-
-		// must set vm_args->version field
-		let mut args: js::JavaVMInitArgs = js::JavaVMInitArgs {
-			version: target_version.as_native() as i32,
-			nOptions: n_options as i32,
-			options: options.as_mut_ptr(),
-			ignoreUnrecognized: r2j_bool(true),
-		};
-		let res = VmError::assert_ok(unsafe { jni_sys::JNI_GetDefaultJavaVMInitArgs(&mut args as *mut js::JavaVMInitArgs as *mut c_void) })?;
-		assert_eq!(res, 0, "JNI_GetDefaultJavaVMInitArgs did not return an error constant or JNI_OK as expected (returned {})", res);
-		assert!(! args.options.is_null(), "JNI_GetDefaultJavaVMInitArgs returned null pointer for args.options with successful call");
-		assert!(  args.nOptions <= n_options as i32, "JVM had more than {} default arguments (provided {}). Please raise the limit in yajnir.", n_options, args.nOptions);
+		let mut options: Vec<js::JavaVMOption> = vec![empty_option; 64];
+
+		// loop around until the buffer is large enough for every default option
+		loop {
+			// must set vm_args->version field
+			let mut args: js::JavaVMInitArgs = js::JavaVMInitArgs {
+				version: target_version.as_native() as i32,
+				nOptions: to_jsize(options.len())?,
+				options: options.as_mut_ptr(),
+				ignoreUnrecognized: r2j_bool(true),
+			};
+			let res = VmError::assert_ok(unsafe { (invocation_api()?.get_default_java_vm_init_args)(&mut args as *mut js::JavaVMInitArgs as *mut c_void) })?;
+			assert_eq!(res, 0, "JNI_GetDefaultJavaVMInitArgs did not return an error constant or JNI_OK as expected (returned {})", res);
+			assert!(! args.options.is_null(), "JNI_GetDefaultJavaVMInitArgs returned null pointer for args.options with successful call");
 
-		eprintln!("processing result (nOptions = {})", args.nOptions);
+			let n_returned = from_jsize(args.nOptions)?;
+			log::trace!("JNI_GetDefaultJavaVMInitArgs returned {} options (buffer holds {})", n_returned, options.len());
+			if n_returned > options.len() && args.options == options.as_mut_ptr() {
+				options.resize(n_returned, empty_option);
+				continue;
+			}
 
-		// JNI docs to not specifify if the options are statically owned/how long they will last, so make our own copies
-		let opts: Vec<String> = options[..args.nOptions as usize].iter()
-			.filter(|opt_ent| ! opt_ent.optionString.is_null())
-			.map(|opt_ent| unsafe { CStr::from_ptr(opt_ent.optionString) })
-			.map(|cs| cesu8::from_java_cesu8(cs.to_bytes()).map(|c| String::from(c)))
-			.collect::<Result<Vec<String>, cesu8::Cesu8DecodingError>>()?;
+			// the JVM may have pointed args.options at its own array rather than filling ours
+			// SAFETY: the JVM reported that args.options holds n_returned entries
+			let returned = unsafe { std::slice::from_raw_parts(args.options, n_returned) };
 
-		eprintln!("done processing result");
+			// JNI docs to not specifify if the options are statically owned/how long they will last, so make our own copies
+			let opts: Vec<String> = returned.iter()
+				.filter(|opt_ent| ! opt_ent.optionString.is_null())
+				.map(|opt_ent| unsafe { CStr::from_ptr(opt_ent.optionString) })
+				.map(|cs| cesu8::from_java_cesu8(cs.to_bytes()).map(|c| String::from(c)))
+				.collect::<Result<Vec<String>, cesu8::Cesu8DecodingError>>()?;
 
-		Ok(VmOptions {
-			version: JniVersion::from_native(args.version as u32),
-			options: opts.into_iter().map(|s| Cow::Owned(s)).collect(),
-			ignore_unrecognized: j2r_bool(args.ignoreUnrecognized),
-		})
+			return Ok(VmOptions {
+				version: JniVersion::from_native(args.version as u32),
+				options: opts.into_iter().map(|s| Cow::Owned(s)).collect(),
+				ignore_unrecognized: j2r_bool(args.ignoreUnrecognized),
+				vfprintf: None,
+				exit: None,
+				abort: None,
+			});
+		}
 	}
 
 	/// Return the created JavaVMs that exist in this process.
 	/// 
 	/// Note that most or all JVMs only support one instance per process.
+	///
+	/// On Android, where the invocation API is unavailable, this returns the VM captured by the `android` module.
+	///
+	/// Contract: any, unaffected
 	pub fn created_jvms() -> Result<Vec<JavaVM>, VmError> {
+		if cfg!(all(feature = "android", target_os = "android")) {
+			return Ok(global().into_iter().collect());
+		}
+
 		let mut buf: Vec<*mut jni_sys::JavaVM> = vec![std::ptr::null_mut(); 1];
 		let mut true_len: js::jsize = 0;
 
+		let api = invocation_api()?;
+
 		// loop around until we have the right number
 		loop {
 			let res = VmError::assert_ok(unsafe {
-				jni_sys::JNI_GetCreatedJavaVMs(
+				(api.get_created_java_vms)(
 					buf.as_mut_ptr(),
-					buf.len().try_into().expect("more JavaVMs exist than positive integers?"),
+					to_jsize(buf.len())?,
 					&mut true_len as *mut js::jsize
 				)
 			})?;
 			assert_eq!(res, 0, "JNI_GetCreatedJavaVMs did not return an error constant or JNI_OK as expected (returned {})", res);
 
-			let buf_len = buf.len().try_into().expect("more JavaVMs exist than positive integers?");
-			if true_len > buf_len {
-				buf.resize(true_len as usize, std::ptr::null_mut());
+			let n_jvms = from_jsize(true_len)?;
+			if n_jvms > buf.len() {
+				buf.resize(n_jvms, std::ptr::null_mut());
 				continue;
-			} else if true_len < buf_len {
-				buf.truncate(true_len as usize);
+			} else if n_jvms < buf.len() {
+				buf.truncate(n_jvms);
 			}
 
 			// true_len == buf_len
@@ -146,7 +221,14 @@ impl JavaVM {
 	/// 
 	/// Creating multiple VMs in a single process is not supported.
 	/// 
-	/// This implementation does not support 'vfprintf', 'exit', or 'abort' options, and will panic if they are provided.
+	/// The 'vfprintf', 'exit', and 'abort' hooks must be set through [`VmOptions::set_vfprintf_hook`],
+	/// [`VmOptions::set_exit_hook`], and [`VmOptions::set_abort_hook`]. Passing them as raw option strings returns
+	/// [`VmError::ReservedOption`].
+	///
+	/// If the JVM rejects the options, the error names the offending option as [`VmError::UnrecognizedOption`] when
+	/// [`VmOptions::validate`] finds it, or with the `bisect-options` feature, when `bisect_options` does. As most JVMs
+	/// can't be created again in a process after failing, call [`VmOptions::validate`] first to check options without
+	/// that risk.
 	///
 	/// ```
 	/// use yajnir::jvm::{JavaVM, JniVersion, VmOptions, VmError};
@@ -160,21 +242,17 @@ impl JavaVM {
 	/// # }
 	/// ```
 	///
+	/// Contract: any, unaffected
 	pub fn create<'env>(opts: VmOptions) -> Result<(JavaVM, JniEnv<'env>), VmError> {
-		
-		// would love to support these, but I couldn't find any documentation on them
-		if opts.options.iter().any(|s| s == "vfprintf") {
-			panic!("tried to use `vfprintf` option when starting jvm");
-		}
-		if opts.options.iter().any(|s| s == "exit") {
-			panic!("tried to use `exit` option when starting jvm");
-		}
-		if opts.options.iter().any(|s| s == "abort") {
-			panic!("tried to use `abort` option when starting jvm");
+		let started = Instant::now();
+
+		// these take a function pointer in extraInfo, so can only be set through the VmOptions hooks
+		if let Some((opt, setter)) = opts.options.iter().find_map(|opt| reserved_option(opt).map(|setter| (opt, setter))) {
+			return Err(VmError::ReservedOption(opt.to_string(), setter));
 		}
 
-		let mut vmoptstrs: Vec<Cow<[u8]>> = opts.options.iter()
-			.map(|s| cesu8::to_java_cesu8(s))
+		let vmoptstrs: Vec<Vec<u8>> = opts.options.iter()
+			.map(|s| java_cstr(s))
 			.collect();
 
 		let mut vmopts: Vec<js::JavaVMOption> = vmoptstrs.iter()
@@ -185,75 +263,798 @@ impl JavaVM {
 				}
 			})
 			.collect();
+		// the JVM only routes output through the hook once it has parsed it, so it has to come first
+		if let Some(hook) = opts.vfprintf {
+			vmopts.insert(0, js::JavaVMOption {
+				optionString: b"vfprintf\0".as_ptr() as *mut c_char,
+				extraInfo: hook as *mut c_void,
+			});
+		}
+		if let Some(hook) = opts.exit {
+			*EXIT_HOOK.lock().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+			vmopts.push(js::JavaVMOption {
+				optionString: b"exit\0".as_ptr() as *mut c_char,
+				extraInfo: exit_trampoline as extern "C" fn(js::jint) as *mut c_void,
+			});
+		}
+		if let Some(hook) = opts.abort {
+			*ABORT_HOOK.lock().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+			vmopts.push(js::JavaVMOption {
+				optionString: b"abort\0".as_ptr() as *mut c_char,
+				extraInfo: abort_trampoline as extern "C" fn() as *mut c_void,
+			});
+		}
 
 		let mut init_args: js::JavaVMInitArgs = js::JavaVMInitArgs {
 			version: opts.version.as_native() as i32,
-			nOptions: opts.options.len() as i32,
+			nOptions: to_jsize(vmopts.len())?,
 			options: vmopts.as_mut_ptr(),
 			ignoreUnrecognized: r2j_bool(opts.ignore_unrecognized),
 		};
 
 		let mut raw_jvm_ptr: *mut js::JavaVM = std::ptr::null_mut();
 		let mut raw_jenv_ptr: *mut js::JNIEnv = std::ptr::null_mut();
+		let marshalled = Instant::now();
 		let res = VmError::assert_ok(unsafe {
-			js::JNI_CreateJavaVM(
+			(invocation_api()?.create_java_vm)(
 				&mut raw_jvm_ptr as *mut *mut js::JavaVM,
 				&mut raw_jenv_ptr as *mut *mut js::JNIEnv as *mut *mut c_void,
 				&mut init_args as *mut js::JavaVMInitArgs as *mut c_void
 			)
-		})?;
+		}).map_err(|e| opts.diagnose_failure(e))?;
 		assert_eq!(res, 0, "JNI_GetCreatedJavaVMs did not return an error constant or JNI_OK as expected (returned {})", res);
 
+		let report = CreationReport {
+			marshal_options: marshalled - started,
+			create_vm: marshalled.elapsed(),
+			first_attach: None,
+		};
+		log::debug!("created JavaVM: {:?}", report);
+		*CREATION_REPORT.lock().unwrap_or_else(|e| e.into_inner()) = Some(report);
+		*CREATION_OPTIONS.lock().unwrap_or_else(|e| e.into_inner()) = Some(opts.options.iter().map(|o| o.to_string()).collect());
+
 		let jvm = NonNull::new(raw_jvm_ptr).expect("JNI_CreateJavaVM output null pointer for JavaVM without returning error");
 		let jenv = NonNull::new(raw_jenv_ptr).expect("JNI_CreateJavaVM output null pointer for JNIEnv without returning error");
 
 		// TODO: would it be better to simply return the JavaVM and let the user retrieve the JniEnv seperately?
 		//       this would better enforce the lifetime requirement of JniEnv being a part of the JavaVM
 
+		// some JVMs can be created again after being destroyed, possibly at the same address
+		DESTROYED.lock().unwrap_or_else(|e| e.into_inner()).retain(|&ptr| ptr != jvm.as_ptr() as usize);
+		track_attached(jenv, std::thread::current().name().map(str::to_owned));
+
 		Ok((
 			JavaVM { ptr: jvm },
 			JniEnv { ptr: jenv, _phantom: PhantomData }
 		))
 	}
 
-	pub fn destroy(self) -> Result<(), VmError> {
+	/// Creates a Java Virtual Machine as with [`JavaVM::create`], or if one already exists in this process, attaches the
+	/// current thread to it instead.
+	///
+	/// This suits libraries loaded into host processes that may have started a VM of their own. When attaching, `opts`
+	/// is ignored, and the thread stays attached until it exits, as with [`JavaVM::attach_permanently`].
+	///
+	/// Contract: any, unaffected
+	pub fn create_or_attach<'env>(opts: VmOptions) -> Result<(JavaVM, JniEnv<'env>), VmError> {
+		// check first, as a failed JNI_CreateJavaVM hides the existing VM from JNI_GetCreatedJavaVMs on some JVMs
+		if let Some(vm) = JavaVM::created_jvms()?.first().copied() {
+			log::debug!("JavaVM already exists, attaching to it instead");
+			let env = vm.attach_permanently()?;
+			return Ok((vm, env));
+		}
+
+		// another thread may have created one in the meantime
+		match JavaVM::create(opts) {
+			Err(VmError::VMExists(Ok(vm))) => {
+				log::debug!("JavaVM already exists, attaching to it instead");
+				let env = vm.attach_permanently()?;
+				Ok((vm, env))
+			},
+			res => res,
+		}
+	}
+
+	/// Returns how long each phase of creating this process's JavaVM took, if it was created through [`JavaVM::create`].
+	///
+	/// Contract: any, unaffected
+	pub fn creation_report(&self) -> Option<CreationReport> {
+		*CREATION_REPORT.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Returns whether this VM has been destroyed through [`JavaVM::destroy`], which makes any use of it (through
+	/// this or any other copy) invalid.
+	pub fn is_destroyed(&self) -> bool {
+		DESTROYED.lock().unwrap_or_else(|e| e.into_inner()).contains(&(self.ptr.as_ptr() as usize))
+	}
+
+	fn ensure_live(&self) -> Result<(), VmError> {
+		if self.is_destroyed() {
+			return Err(VmError::Destroyed);
+		}
+		Ok(())
+	}
+
+	/// Returns the current thread's JNIEnv, or `None` if it isn't attached to this VM.
+	pub(crate) fn get_env(&self) -> Result<Option<NonNull<js::JNIEnv>>, VmError> {
+		self.ensure_live()?;
+		let mut raw_env: *mut c_void = std::ptr::null_mut();
+		match java_vm_unchecked!(*self, GetEnv, &mut raw_env as *mut *mut c_void, JniVersion::V1_6.as_native() as js::jint) {
+			js::JNI_EDETACHED => Ok(None),
+			res => {
+				VmError::assert_ok(res)?;
+				Ok(NonNull::new(raw_env as *mut js::JNIEnv))
+			},
+		}
+	}
+
+	/// Returns whether the current thread is attached to this VM, whether through this crate or otherwise.
+	///
+	/// Contract: any, unaffected
+	pub fn is_current_thread_attached(&self) -> Result<bool, VmError> {
+		Ok(self.get_env()?.is_some())
+	}
+
+	/// Checks whether the VM implements the given JNI version, by negotiating it through `GetEnv`.
+	///
+	/// This allows branching on newer JNI functions (such as `GetModule` from JNI 9) up front, instead of failing with
+	/// `MissingFunction` partway through an operation. The current thread is attached for the check if it isn't already.
+	///
+	/// Contract: any, unaffected
+	pub fn supports(&self, version: JniVersion) -> Result<bool, VmError> {
+		let _guard = self.attach_current_thread()?;
+		let mut raw_env: *mut c_void = std::ptr::null_mut();
+		match java_vm_unchecked!(*self, GetEnv, &mut raw_env as *mut *mut c_void, version.as_native() as js::jint) {
+			js::JNI_EVERSION => Ok(false),
+			res => VmError::assert_ok(res).map(|_| true),
+		}
+	}
+
+	/// Returns the newest JNI version this crate knows of that the VM implements.
+	///
+	/// Contract: any, unaffected
+	pub fn supported_version(&self) -> Result<JniVersion, VmError> {
+		for version in JniVersion::KNOWN {
+			if self.supports(version)? {
+				return Ok(version);
+			}
+		}
+		Err(VmError::BadVersion)
+	}
+
+	/// Attaches the current thread to the VM, returning a guard that detaches it again when dropped.
+	///
+	/// The Java thread is named after the Rust thread, if it has a name.
+	///
+	/// If the thread is already attached (for example, it created the VM), the guard borrows the existing
+	/// attachment and leaves the thread attached when dropped.
+	///
+	/// ```no_run
+	/// # use yajnir::jvm::{JavaVM, VmError};
+	/// # fn example(vm: JavaVM) -> Result<(), VmError> {
+	/// std::thread::spawn(move || -> Result<(), VmError> {
+	///     let guard = vm.attach_current_thread()?;
+	///     let env = guard.env();
+	///     /* actions that require a JniEnv */
+	///     Ok(())
+	/// });
+	/// # Ok(())
+	/// # }
+	/// ```
+	///
+	/// Contract: any, unaffected
+	pub fn attach_current_thread(&self) -> Result<AttachGuard<'_>, VmError> {
+		self.attach_current_thread_with(&AttachArgs::new())
+	}
+
+	/// Attaches the current thread as with [`JavaVM::attach_current_thread`], using `args` for the Java thread's name
+	/// and group. `args` is ignored if the thread is already attached.
+	///
+	/// Contract: any, unaffected
+	pub fn attach_current_thread_with(&self, args: &AttachArgs) -> Result<AttachGuard<'_>, VmError> {
+		if let Some(env) = self.get_env()? {
+			return Ok(AttachGuard { vm: self, env, owned: false, _unsend: PhantomData });
+		}
+
+		let env = self.attach_raw(args)?;
+		Ok(AttachGuard { vm: self, env, owned: true, _unsend: PhantomData })
+	}
+
+	/// Runs `func` with a JNIEnv for the current thread, attaching it first if needed and detaching it again afterwards
+	/// if it was not attached before.
+	///
+	/// ```no_run
+	/// # use yajnir::jvm::{JavaVM, VmError};
+	/// # fn example(vm: JavaVM) -> Result<(), VmError> {
+	/// let version = vm.with_env(|env| yajnir::lang::JSystem::property(env, "java.version"))?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	///
+	/// Contract: any, unaffected
+	pub fn with_env<R, F: FnOnce(JniEnv<'_>) -> R>(&self, func: F) -> Result<R, VmError> {
+		let guard = self.attach_current_thread()?;
+		Ok(func(guard.env()))
+	}
+
+	/// Checks that the VM is responsive, returning how long the check took, for use as a readiness or liveness probe.
+	///
+	/// A new thread attaches to the VM and looks up `java/lang/Object`, so the latency includes attaching. If that
+	/// doesn't finish within `timeout` (for example, because the VM is stuck at a safepoint) this returns
+	/// [`VmError::Unresponsive`], and the probe thread is left to finish and detach on its own.
+	///
+	/// Contract: any, unaffected
+	pub fn ping(&self, timeout: Duration) -> Result<Duration, VmError> {
+		self.ensure_live()?;
+		let vm = *self;
+		let (tx, rx) = std::sync::mpsc::channel();
+		let started = Instant::now();
+		std::thread::Builder::new()
+			.name(String::from("yajnir-ping"))
+			.spawn(move || {
+				let res = vm.with_env(|env| {
+					let class = env.find_class("java/lang/Object")?;
+					env.delete_local_ref(class)
+				});
+				// the caller stops waiting after the timeout
+				let _ = tx.send(res);
+			})
+			.map_err(VmError::Spawn)?;
+
+		match rx.recv_timeout(timeout) {
+			Ok(res) => {
+				res??;
+				Ok(started.elapsed())
+			},
+			Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Err(VmError::Unresponsive(timeout)),
+			Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err(VmError::Unknown),
+		}
+	}
+
+	/// Detaches the current thread, if it is attached. Does nothing once the VM has been destroyed, since every
+	/// attachment ended with it.
+	pub(crate) fn detach_raw(&self) -> Result<(), VmError> {
+		if self.is_destroyed() {
+			return Ok(());
+		}
+		let vm = *self;
+		let env = match self.get_env()? {
+			Some(env) => env,
+			None => return Ok(()),
+		};
+		VmError::assert_ok(java_vm_unchecked!(vm, DetachCurrentThread))?;
+		ATTACHED.lock().unwrap_or_else(|e| e.into_inner()).retain(|t| t.env != env.as_ptr() as usize);
+		Ok(())
+	}
+
+	/// Attaches the current thread to the VM until the thread exits, returning its JNIEnv.
+	///
+	/// Unlike [`JavaVM::attach_current_thread`], this avoids paying for an attach and detach around each unit of work
+	/// in long-lived worker threads. The thread is detached by a thread-local destructor when it exits. If the thread
+	/// is already attached, the existing attachment is reused; one made by an [`AttachGuard`] is then kept alive
+	/// past the guard.
+	///
+	/// Attached threads keep the VM from being destroyed, so `DestroyJavaVM` will wait for permanently attached
+	/// threads to exit.
+	///
+	/// Contract: any, unaffected
+	pub fn attach_permanently(&self) -> Result<JniEnv<'static>, VmError> {
+		self.attach_permanently_with(&AttachArgs::new())
+	}
+
+	/// Attaches the current thread as with [`JavaVM::attach_permanently`], using `args` for the Java thread's name and
+	/// group. `args` is ignored if the thread is already attached.
+	///
+	/// Contract: any, unaffected
+	pub fn attach_permanently_with(&self, args: &AttachArgs) -> Result<JniEnv<'static>, VmError> {
+		let env = match self.get_env()? {
+			Some(env) => {
+				PERMANENT.with(|p| {
+					p.borrow_mut().get_or_insert(PermanentAttachment { vm: *self, detach: false });
+				});
+				env
+			},
+			None => {
+				let env = self.attach_raw(args)?;
+				PERMANENT.with(|p| *p.borrow_mut() = Some(PermanentAttachment { vm: *self, detach: true }));
+				env
+			},
+		};
+		Ok(JniEnv { ptr: env, _phantom: PhantomData })
+	}
+
+	/// Attaches the current thread, which must not already be attached.
+	fn attach_raw(&self, args: &AttachArgs) -> Result<NonNull<js::JNIEnv>, VmError> {
+		let started = Instant::now();
+
+		// by default, name the Java thread after the Rust one, so both show up consistently in thread dumps and profilers
+		let name = args.name.clone().or_else(|| std::thread::current().name().map(str::to_owned));
+		let cname = name.as_deref().map(java_cstr);
+		let mut args = js::JavaVMAttachArgs {
+			version: JniVersion::V1_6.as_native() as js::jint,
+			name: cname.as_ref().map_or(std::ptr::null_mut(), |n| n.as_ptr() as *mut c_char),
+			group: args.group.as_ref().map_or(std::ptr::null_mut(), |g| g.as_raw()),
+		};
+
+		let mut raw_env: *mut c_void = std::ptr::null_mut();
+		let res = VmError::assert_ok(java_vm_unchecked!(*self, AttachCurrentThread, &mut raw_env as *mut *mut c_void, &mut args as *mut js::JavaVMAttachArgs as *mut c_void))?;
+		assert_eq!(res, 0, "JavaVM.AttachCurrentThread did not return an error constant or JNI_OK as expected (returned {})", res);
+		let env = NonNull::new(raw_env as *mut js::JNIEnv).expect("AttachCurrentThread output null pointer for JNIEnv without returning error");
+
+		if let Some(report) = CREATION_REPORT.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+			report.first_attach.get_or_insert_with(|| started.elapsed());
+		}
+		track_attached(env, name);
+		Ok(env)
+	}
+
+	/// Registers `hook` to run on a Java shutdown hook thread when the VM shuts down, whether through
+	/// [`JavaVM::destroy`] or Java code calling `System.exit`.
+	///
+	/// The closure is owned by the Java hook object, and is dropped once it has run. Any exception it leaves pending is
+	/// logged and cleared. Unlike [`shutdown::register`](crate::shutdown::register), ordering relative to other hooks
+	/// is not defined, as Java runs its shutdown hooks concurrently.
+	///
+	/// Contract: any, unaffected
+	pub fn add_shutdown_hook<F: FnOnce(JniEnv<'_>) + Send + 'static>(&self, hook: F) -> Result<(), VmError> {
+		self.with_env(|env| crate::shutdown::add_hook(env, Box::new(hook)))??;
+		Ok(())
+	}
+
+	/// Destroys the VM, returning how many [`GlobalRef`](crate::jref::GlobalRef)s were still alive.
+	///
+	/// Callbacks registered with [`shutdown::register`](crate::shutdown::register) run first, in priority order.
+	///
+	/// Those references, and any remaining copies of this `JavaVM`, return [`VmError::Destroyed`] from then on instead
+	/// of dangling.
+	///
+	/// Contract: any, unaffected
+	pub fn destroy(self) -> Result<usize, VmError> {
 		// TODO: assert that no exception is pending? Clear it if it is?
+		self.ensure_live()?;
+		// the app's VM lives as long as its process
+		if cfg!(all(feature = "android", target_os = "android")) {
+			return Err(VmError::InvocationUnavailable);
+		}
+
+		// stop handing out the global VM before it starts shutting down
+		if GLOBAL_VM.get().is_some_and(|vm| vm.ptr == self.ptr) {
+			GLOBAL_DESTROYED.store(true, Ordering::SeqCst);
+		}
+
+		// registered teardown needs the VM to still be fully usable
+		match self.attach_current_thread() {
+			Ok(guard) => crate::shutdown::run(guard.env()),
+			Err(e) => log::error!("unable to attach to run shutdown teardown: {}", e),
+		}
 
 		let res = VmError::assert_ok(java_vm_unchecked!(self, DestroyJavaVM))?;
 		assert_eq!(res, 0, "JavaVM.DestroyJavaVM did not return an error constant or JNI_OK as expected (returned {})", res);
 
-		Ok(())
+		EPOCH.fetch_add(1, Ordering::SeqCst);
+		DESTROYED.lock().unwrap_or_else(|e| e.into_inner()).push(self.ptr.as_ptr() as usize);
+		ATTACHED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+
+		let live = crate::jref::live_globals();
+		if live > 0 {
+			log::warn!("destroyed JavaVM with {} global references still alive", live);
+		}
+		Ok(live)
 	}
 
-	pub fn create_with<O, F: Fn(JavaVM, JniEnv) -> O>(opts: VmOptions, func: F) -> Result<O, (VmError, Option<O>)> {
-		// three failure conditions
-		// create
-		//   user (who may or may not destroy jvm)
-		// destroy (if fails, how to return user rtn?)
-		
+	/// Creates a JavaVM, runs `func` with it, and destroys it afterwards.
+	///
+	/// If `func` panics, the VM is still destroyed before the panic resumes, so a failing test does not leave a live
+	/// VM behind. If destroying fails, the error is returned along with the closure's output.
+	///
+	/// Contract: any, unaffected
+	pub fn create_with<O, F: FnOnce(JavaVM, JniEnv) -> O>(opts: VmOptions, func: F) -> CreateWithResult<O> {
 		let (jvm, jenv) = JavaVM::create(opts)
 			.map_err(|e| (e, None))?;
-		let res = func(jvm, jenv);
-		match jvm.destroy() {
-			Ok(()) => Ok(res),
-			Err(e) => return Err((e, Some(res))),
+		let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| func(jvm, jenv)));
+		let destroyed = jvm.destroy();
+		match (res, destroyed) {
+			(Ok(res), Ok(_)) => Ok(res),
+			(Ok(res), Err(e)) => Err((e, Some(res))),
+			(Err(panic), destroyed) => {
+				if let Err(e) = destroyed {
+					log::error!("unable to destroy JavaVM after panic: {}", e);
+				}
+				std::panic::resume_unwind(panic)
+			},
+		}
+	}
+
+	/// Runs [`JavaVM::create_with`] on a new thread named `java-main`, leaving the current thread unattached.
+	///
+	/// Contract: any, unaffected
+	pub fn create_on_thread<O, F>(opts: VmOptions, func: F) -> std::io::Result<std::thread::JoinHandle<CreateWithResult<O>>>
+		where O: Send + 'static, F: FnOnce(JavaVM, JniEnv) -> O + Send + 'static
+	{
+		std::thread::Builder::new()
+			.name("java-main".to_owned())
+			.spawn(move || JavaVM::create_with(opts, func))
+	}
+}
+
+/// The result of [`JavaVM::create_with`]: the closure's output, or the error creating or destroying the VM along with
+/// the output if the closure ran.
+pub type CreateWithResult<O> = Result<O, (VmError, Option<O>)>;
+
+/// The Java thread name and group to attach a thread with, for [`JavaVM::attach_current_thread_with`] and
+/// [`JavaVM::attach_permanently_with`].
+///
+/// ```no_run
+/// # use yajnir::jvm::{AttachArgs, JavaVM, VmError};
+/// # fn example(vm: JavaVM) -> Result<(), VmError> {
+/// let env = vm.attach_permanently_with(&AttachArgs::new().name("render-worker"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct AttachArgs {
+	name: Option<String>,
+	group: Option<GlobalRef<JThreadGroup>>,
+}
+
+impl AttachArgs {
+	/// Attaches with the Rust thread's name, if it has one, in the VM's main thread group.
+	pub fn new() -> AttachArgs {
+		AttachArgs::default()
+	}
+
+	/// Names the Java thread, instead of naming it after the Rust thread.
+	pub fn name(mut self, name: &str) -> AttachArgs {
+		self.name = Some(name.to_owned());
+		self
+	}
+
+	/// Adds the Java thread to `group`, instead of the VM's main thread group.
+	pub fn group(mut self, group: GlobalRef<JThreadGroup>) -> AttachArgs {
+		self.group = Some(group);
+		self
+	}
+}
+
+/// Keeps the current thread attached to a JavaVM. See [`JavaVM::attach_current_thread`].
+#[derive(Debug)]
+pub struct AttachGuard<'vm> {
+	vm: &'vm JavaVM,
+	env: NonNull<js::JNIEnv>,
+	/// Whether this guard attached the thread, and so should detach it
+	owned: bool,
+	// attachment is per-thread, so the guard must be dropped on the thread that created it
+	_unsend: PhantomData<*const ()>,
+}
+
+impl<'vm> AttachGuard<'vm> {
+	/// Returns the JNIEnv for the current thread, valid for as long as the guard is held.
+	pub fn env(&self) -> JniEnv<'_> {
+		JniEnv { ptr: self.env, _phantom: PhantomData }
+	}
+
+	/// Returns the VM this thread is attached to.
+	pub fn vm(&self) -> &'vm JavaVM {
+		self.vm
+	}
+}
+
+impl Drop for AttachGuard<'_> {
+	fn drop(&mut self) {
+		if !self.owned {
+			return;
+		}
+		// if the thread has since been attached permanently, hand the attachment over instead of detaching
+		let handed_over = PERMANENT.with(|p| match p.borrow_mut().as_mut() {
+			Some(permanent) => {
+				permanent.detach = true;
+				true
+			},
+			None => false,
+		});
+		if handed_over {
+			return;
+		}
+		if let Err(e) = self.vm.detach_raw() {
+			log::error!("unable to detach thread from JavaVM: {}", e);
+		}
+	}
+}
+
+/// A thread's permanent attachment, detached when the thread exits. See [`JavaVM::attach_permanently`].
+struct PermanentAttachment {
+	vm: JavaVM,
+	/// False if the thread was attached by someone else (such as creating the VM), who remains responsible for it
+	detach: bool,
+}
+
+impl Drop for PermanentAttachment {
+	fn drop(&mut self) {
+		if !self.detach {
+			return;
+		}
+		if let Err(e) = self.vm.detach_raw() {
+			log::error!("unable to detach exiting thread from JavaVM: {}", e);
+		}
+	}
+}
+
+thread_local! {
+	static PERMANENT: std::cell::RefCell<Option<PermanentAttachment>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Bumped whenever a VM is destroyed. JNI supports at most one VM per process, so global references created before the
+/// latest bump belong to a destroyed VM.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+/// The addresses of destroyed VMs, which remaining `JavaVM` copies must not be used through
+static DESTROYED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// A thread attached through this crate, keyed by its JNIEnv as each attached thread has its own
+struct AttachedThread {
+	env: usize,
+	#[cfg_attr(not(feature = "diagnostics"), allow(dead_code))]
+	name: String,
+}
+
+/// Threads attached through this crate (including the one that created the VM) that have not detached yet
+static ATTACHED: Mutex<Vec<AttachedThread>> = Mutex::new(Vec::new());
+
+fn track_attached(env: NonNull<js::JNIEnv>, name: Option<String>) {
+	let name = name.unwrap_or_else(|| format!("{:?}", std::thread::current().id()));
+	ATTACHED.lock().unwrap_or_else(|e| e.into_inner()).push(AttachedThread { env: env.as_ptr() as usize, name });
+}
+
+/// Returns the names of the threads currently attached through this crate, in the order they attached.
+#[cfg(feature = "diagnostics")]
+pub(crate) fn attached_threads() -> Vec<String> {
+	ATTACHED.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|t| t.name.clone()).collect()
+}
+
+pub(crate) fn epoch() -> u64 {
+	EPOCH.load(Ordering::SeqCst)
+}
+
+static GLOBAL_VM: OnceCell<JavaVM> = OnceCell::new();
+static GLOBAL_DESTROYED: AtomicBool = AtomicBool::new(false);
+
+/// Creates the process-global JavaVM on the current thread, or returns it if it already exists, so code spread across
+/// crates can share one VM without passing a handle around. See [`global`].
+///
+/// If the VM was already created, `opts` is ignored. A JVM cannot be recreated within a process, so once the global VM
+/// has been destroyed this returns [`VmError::GlobalDestroyed`].
+///
+/// Contract: any, unaffected
+pub fn init_global(opts: VmOptions) -> Result<JavaVM, VmError> {
+	if GLOBAL_DESTROYED.load(Ordering::SeqCst) {
+		return Err(VmError::GlobalDestroyed);
+	}
+	GLOBAL_VM.get_or_try_init(|| JavaVM::create(opts).map(|(vm, _env)| vm)).copied()
+}
+
+/// Returns the process-global JavaVM created by [`init_global`], unless it has not been created yet or has since been
+/// destroyed.
+///
+/// In libraries loaded by a JVM, this is the VM captured by [`on_load!`](crate::on_load).
+///
+/// With the `android` feature, this is also the VM captured through [`android::capture`](crate::android::capture),
+/// or with the `ndk-context` feature, the one `ndk-context` was initialized with.
+///
+/// Contract: any, unaffected
+pub fn global() -> Option<JavaVM> {
+	if GLOBAL_DESTROYED.load(Ordering::SeqCst) {
+		return None;
+	}
+	#[cfg(feature = "ndk-context")]
+	if GLOBAL_VM.get().is_none() {
+		if let Some(vm) = crate::android::from_ndk_context() {
+			return Some(set_global(vm));
 		}
 	}
+	GLOBAL_VM.get().copied()
+}
+
+/// Makes `vm` the global JavaVM, unless one has been set already, returning whichever is global.
+pub(crate) fn set_global(vm: JavaVM) -> JavaVM {
+	let global = *GLOBAL_VM.get_or_init(|| vm);
+	if global.ptr != vm.ptr {
+		log::warn!("ignoring JavaVM {:?}, as the global JavaVM is already {:?}", vm.ptr, global.ptr);
+	}
+	global
+}
+
+/// Implements the `JNI_OnLoad` exported by [`on_load!`](crate::on_load): captures `vm` as the [`global`] JavaVM,
+/// checks that it supports `version`, and runs `init`, returning the version to report to the VM.
+///
+/// Errors and panics from `init` are logged, and fail loading the library.
+///
+/// # Safety
+/// `vm` must be the JavaVM passed to `JNI_OnLoad`, on the thread it was called on.
+#[doc(hidden)]
+pub unsafe fn on_load<F>(vm: *mut js::JavaVM, version: JniVersion, init: F) -> js::jint
+	where F: FnOnce(JavaVM, JniEnv<'_>) -> Result<(), Box<dyn std::error::Error>>
+{
+	let vm = match NonNull::new(vm) {
+		Some(ptr) => set_global(JavaVM { ptr }),
+		None => {
+			log::error!("JNI_OnLoad was called with a null JavaVM");
+			return js::JNI_ERR;
+		},
+	};
+	match vm.supports(version) {
+		Ok(true) => {},
+		Ok(false) => {
+			log::error!("the JavaVM does not support JNI {}, which this library requires", version);
+			return js::JNI_ERR;
+		},
+		Err(e) => {
+			log::error!("unable to check the JavaVM's JNI version: {}", e);
+			return js::JNI_ERR;
+		},
+	}
+	let env = match vm.get_env() {
+		Ok(Some(env)) => JniEnv { ptr: env, _phantom: PhantomData },
+		Ok(None) => {
+			log::error!("JNI_OnLoad was called on a thread not attached to the JavaVM");
+			return js::JNI_ERR;
+		},
+		Err(e) => {
+			log::error!("unable to get the JNIEnv in JNI_OnLoad: {}", e);
+			return js::JNI_ERR;
+		},
+	};
+
+	// unwinding into the JVM is undefined behavior
+	match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| init(vm, env))) {
+		Ok(Ok(())) => version.as_native() as js::jint,
+		Ok(Err(e)) => {
+			log::error!("library initialization in JNI_OnLoad failed: {}", e);
+			js::JNI_ERR
+		},
+		Err(_) => {
+			log::error!("library initialization in JNI_OnLoad panicked");
+			js::JNI_ERR
+		},
+	}
 }
 
+/// Implements the `JNI_OnUnload` exported by [`on_load!`](crate::on_load), running `fini` with the current JNIEnv.
+///
+/// # Safety
+/// `vm` must be the JavaVM passed to `JNI_OnUnload`, on the thread it was called on.
+#[doc(hidden)]
+pub unsafe fn on_unload<F: FnOnce(JavaVM, JniEnv<'_>)>(vm: *mut js::JavaVM, fini: F) {
+	let vm = match NonNull::new(vm) {
+		Some(ptr) => JavaVM { ptr },
+		None => return,
+	};
+	match vm.get_env() {
+		Ok(Some(env)) => {
+			let env = JniEnv { ptr: env, _phantom: PhantomData };
+			if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fini(vm, env))).is_err() {
+				log::error!("library teardown in JNI_OnUnload panicked");
+			}
+		},
+		Ok(None) => log::error!("JNI_OnUnload was called on a thread not attached to the JavaVM"),
+		Err(e) => log::error!("unable to get the JNIEnv in JNI_OnUnload: {}", e),
+	}
+}
+
+/// Timings for the phases of [`JavaVM::create`], to attribute startup latency between this crate and the JVM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct CreationReport {
+	/// Converting the options into the JNI's native structures
+	pub marshal_options: Duration,
+	/// The `JNI_CreateJavaVM` call itself
+	pub create_vm: Duration,
+	/// The first `AttachCurrentThread` from another thread, once one has happened
+	pub first_attach: Option<Duration>,
+}
+
+// only one JavaVM can be created per process
+static CREATION_REPORT: Mutex<Option<CreationReport>> = Mutex::new(None);
+static CREATION_OPTIONS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// The options passed to [`JavaVM::create`], if this process's JavaVM was created through it.
+#[cfg(feature = "diagnostics")]
+pub(crate) fn creation_options() -> Option<Vec<String>> {
+	CREATION_OPTIONS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A `vfprintf` hook, which the JVM calls with C `printf` arguments instead of writing diagnostics to stdout/stderr.
+///
+/// The native signature is `jint vfprintf(FILE *fp, const char *format, va_list args)`. On all supported platforms
+/// the `va_list` arrives as a single pointer, which can only be consumed by passing it on to a C `v*printf` function.
+pub type VfprintfHook = unsafe extern "C" fn(fp: *mut c_void, format: *const c_char, args: *mut c_void) -> js::jint;
+
+extern "C" {
+	fn vsnprintf(buf: *mut c_char, len: usize, format: *const c_char, args: *mut c_void) -> std::os::raw::c_int;
+}
+
+/// A [`VfprintfHook`] that forwards JVM output to the `log` crate, at warn level for warnings and errors and at info
+/// level otherwise. Messages longer than 4KiB are truncated.
+///
+/// # Safety
+/// Must only be called by the JVM, with a valid format string and matching arguments.
+pub unsafe extern "C" fn log_vfprintf(_fp: *mut c_void, format: *const c_char, args: *mut c_void) -> js::jint {
+	let mut buf = [0 as c_char; 4096];
+	let written = vsnprintf(buf.as_mut_ptr(), buf.len(), format, args);
+	if written < 0 {
+		return written;
+	}
+
+	let msg = CStr::from_ptr(buf.as_ptr()).to_string_lossy();
+	let msg = msg.trim_end();
+	if msg.is_empty() {
+		// nothing worth a log record, such as a lone newline
+	} else if msg.contains("Warning") || msg.contains("Error") {
+		log::warn!("{}", msg);
+	} else {
+		log::info!("{}", msg);
+	}
+	written
+}
+
+// only one JavaVM can be created per process, so its hooks are kept globally for the trampolines below
+static EXIT_HOOK: Mutex<Option<fn(i32)>> = Mutex::new(None);
+static ABORT_HOOK: Mutex<Option<fn()>> = Mutex::new(None);
+
+extern "C" fn exit_trampoline(code: js::jint) {
+	let hook = *EXIT_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+	if let Some(hook) = hook {
+		// unwinding into the JVM is undefined behavior
+		if std::panic::catch_unwind(|| hook(code)).is_err() {
+			log::error!("JVM exit hook panicked");
+		}
+	}
+}
+
+extern "C" fn abort_trampoline() {
+	let hook = *ABORT_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+	if let Some(hook) = hook {
+		if std::panic::catch_unwind(hook).is_err() {
+			log::error!("JVM abort hook panicked");
+		}
+	}
+}
+
+/// With the `serde` feature, options can be loaded from configuration files. Hooks are not serialized, and must be set
+/// after loading.
+///
+/// ```toml
+/// version = { major = 10, minor = 0 }
+/// options = ["-Xmx512m", "-Djava.class.path=app.jar"]
+/// ignore_unrecognized = false
+/// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VmOptions {
 	version: JniVersion,
+	#[cfg_attr(feature = "serde", serde(default))]
 	options: Vec<Cow<'static, str>>,
+	#[cfg_attr(feature = "serde", serde(default))]
 	ignore_unrecognized: bool,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	vfprintf: Option<VfprintfHook>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	exit: Option<fn(i32)>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	abort: Option<fn()>,
 }
 impl VmOptions {
+	/// The target for [`VmOptions::add_opens`] and [`VmOptions::add_exports`] that grants access to all code on the classpath.
+	pub const ALL_UNNAMED: &'static str = "ALL-UNNAMED";
+
 	/// Creates a basic VmOptions struct, which passes an empty list of arguments to the JVM upon creation while checking the version number.
 	pub fn new(version: JniVersion) -> VmOptions {
 		VmOptions {
 			version,
 			options: Vec::new(),
 			ignore_unrecognized: false,
+			vfprintf: None,
+			exit: None,
+			abort: None,
 		}
 	}
 
@@ -265,6 +1066,9 @@ impl VmOptions {
 			version,
 			options: opts,
 			ignore_unrecognized: false,
+			vfprintf: None,
+			exit: None,
+			abort: None,
 		}
 	}
 
@@ -276,6 +1080,9 @@ impl VmOptions {
 			version,
 			options: opts,
 			ignore_unrecognized: true,
+			vfprintf: None,
+			exit: None,
+			abort: None,
 		}
 	}
 
@@ -289,15 +1096,412 @@ impl VmOptions {
 		self.ignore_unrecognized = allow;
 	}
 
+	/// Routes the JVM's own diagnostic output through `hook`, such as [`log_vfprintf`], or restores the default of
+	/// writing to stdout/stderr.
+	pub fn set_vfprintf_hook(&mut self, hook: Option<VfprintfHook>) {
+		self.vfprintf = hook;
+	}
+
+	/// Sets a hook called with the exit code when the JVM terminates the process, such as through `System.exit`, so
+	/// state can be flushed first.
+	///
+	/// The hook runs on the exiting Java thread, and the process exits once it returns. Panics are caught and logged.
+	pub fn set_exit_hook(&mut self, hook: Option<fn(i32)>) {
+		self.exit = hook;
+	}
+
+	/// Sets a hook called when the JVM aborts the process after a fatal error. The process aborts once it returns.
+	///
+	/// The JVM may be in an inconsistent state, so the hook should avoid calling back into it.
+	pub fn set_abort_hook(&mut self, hook: Option<fn()>) {
+		self.abort = hook;
+	}
+
 	/// Pushes a system property argument onto the VM's arguments list.
 	pub fn push_property(&mut self, name: &str, value: &str) {
 		self.options.push(Cow::from(format!("-D{}={}", name, value)));
 	}
+
+	/// Sets the JVM's default charset (`file.encoding`), used whenever Java converts between bytes and strings
+	/// without an explicit charset.
+	pub fn set_default_charset(&mut self, charset: &str) {
+		self.push_property("file.encoding", charset);
+	}
+
+	/// Sets the JVM's default locale from an ISO 639 language code and optional ISO 3166 country code.
+	pub fn set_default_locale(&mut self, language: &str, country: Option<&str>) {
+		self.push_property("user.language", language);
+		if let Some(country) = country {
+			self.push_property("user.country", country);
+		}
+	}
+
+	/// Sets the JVM's default time zone, by ID such as `UTC` or `America/New_York`.
+	pub fn set_default_time_zone(&mut self, zone: &str) {
+		self.push_property("user.timezone", zone);
+	}
+
+	/// Appends a directory or archive to the `-Djava.class.path=` argument, creating it if needed.
+	///
+	/// Entries are joined with the platform's path separator. Paths that are not valid unicode, or that contain the
+	/// separator themselves, cannot be passed to the JVM and are rejected.
+	pub fn add_classpath_entry<P: AsRef<Path>>(&mut self, entry: P) -> Result<(), VmError> {
+		self.append_search_path(CLASSPATH_OPTION, entry.as_ref())
+	}
+
+	/// Appends a `.jar` file to the classpath, as with [`VmOptions::add_classpath_entry`].
+	pub fn add_jar<P: AsRef<Path>>(&mut self, jar: P) -> Result<(), VmError> {
+		let jar = jar.as_ref();
+		let is_jar = jar.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jar"));
+		if !is_jar {
+			return Err(VmError::InvalidPath(jar.to_owned(), "not a .jar file"));
+		}
+		self.add_classpath_entry(jar)
+	}
+
+	/// Replaces the classpath with the given entries, in order.
+	///
+	/// The existing classpath is left untouched if any entry is rejected.
+	pub fn set_classpath<I, P>(&mut self, entries: I) -> Result<(), VmError>
+		where I: IntoIterator<Item = P>, P: AsRef<Path>
+	{
+		let mut classpath = String::from(CLASSPATH_OPTION);
+		for (i, entry) in entries.into_iter().enumerate() {
+			if i > 0 {
+				classpath.push(PATH_SEPARATOR);
+			}
+			classpath.push_str(search_path_entry(entry.as_ref())?);
+		}
+		self.options.retain(|opt| !opt.starts_with(CLASSPATH_OPTION));
+		self.options.push(Cow::from(classpath));
+		Ok(())
+	}
+
+	/// Opens `package` in `module` to `target` for deep reflection (`--add-opens`), such as with
+	/// `setAccessible(true)`.
+	///
+	/// `target` is a module name, or [`VmOptions::ALL_UNNAMED`] for code on the classpath.
+	pub fn add_opens(&mut self, module: &str, package: &str, target: &str) {
+		self.options.push(Cow::from(format!("--add-opens={}/{}={}", module, package, target)));
+	}
+
+	/// Exports `package` in `module` to `target` (`--add-exports`), making its public members accessible.
+	///
+	/// `target` is a module name, or [`VmOptions::ALL_UNNAMED`] for code on the classpath.
+	pub fn add_exports(&mut self, module: &str, package: &str, target: &str) {
+		self.options.push(Cow::from(format!("--add-exports={}/{}={}", module, package, target)));
+	}
+
+	/// Resolves the given modules in addition to the default root modules (`--add-modules`).
+	pub fn add_modules<'m, I: IntoIterator<Item = &'m str>>(&mut self, modules: I) {
+		let modules: Vec<&str> = modules.into_iter().collect();
+		self.options.push(Cow::from(format!("--add-modules={}", modules.join(","))));
+	}
+
+	/// Appends a directory of modules or a modular jar to the `--module-path` argument, creating it if needed.
+	///
+	/// Entries are validated the same way as with [`VmOptions::add_classpath_entry`].
+	pub fn add_module_path_entry<P: AsRef<Path>>(&mut self, entry: P) -> Result<(), VmError> {
+		self.append_search_path(MODULE_PATH_OPTION, entry.as_ref())
+	}
+
+	fn append_search_path(&mut self, option: &str, entry: &Path) -> Result<(), VmError> {
+		let entry = search_path_entry(entry)?;
+		match self.options.iter_mut().find(|opt| opt.starts_with(option)) {
+			Some(opt) => {
+				let opt = opt.to_mut();
+				if opt.len() > option.len() {
+					opt.push(PATH_SEPARATOR);
+				}
+				opt.push_str(entry);
+			},
+			None => self.options.push(Cow::from(format!("{}{}", option, entry))),
+		}
+		Ok(())
+	}
+
+	/// Loads a Java agent from a jar before the main class (`-javaagent`), passing it the optional argument string.
+	pub fn add_java_agent<P: AsRef<Path>>(&mut self, jar: P, args: Option<&str>) -> Result<(), VmError> {
+		let jar = option_path(jar.as_ref())?;
+		self.options.push(Cow::from(agent_option("-javaagent:", jar, args)));
+		Ok(())
+	}
+
+	/// Loads a native JVMTI agent by library name (`-agentlib`), passing it the optional option string.
+	///
+	/// The library is looked up on the system library path, e.g. `hprof` for `libhprof.so`.
+	pub fn add_native_agent(&mut self, lib: &str, opts: Option<&str>) {
+		self.options.push(Cow::from(agent_option("-agentlib:", lib, opts)));
+	}
+
+	/// Starts the JDWP debugging agent, listening for a debugger on `port` (on localhost only, for Java 9+).
+	///
+	/// If `suspend` is set, the JVM waits for a debugger to connect before [`JavaVM::create`] returns.
+	pub fn enable_jdwp(&mut self, port: u16, suspend: bool) {
+		let suspend = if suspend { "y" } else { "n" };
+		let opts = format!("transport=dt_socket,server=y,suspend={},address={}", suspend, port);
+		self.add_native_agent("jdwp", Some(&opts));
+	}
+
+	/// Sets the maximum heap size (`-Xmx`), which must be a multiple of 1024 and at least 2MiB.
+	pub fn max_heap(&mut self, bytes: u64) -> Result<(), VmError> {
+		self.set_memory_option("-Xmx", bytes, MIN_HEAP_SIZE)
+	}
+
+	/// Sets the initial heap size (`-Xms`), which must be a multiple of 1024 and at least 2MiB.
+	pub fn initial_heap(&mut self, bytes: u64) -> Result<(), VmError> {
+		self.set_memory_option("-Xms", bytes, MIN_HEAP_SIZE)
+	}
+
+	/// Sets the stack size of Java threads (`-Xss`), which must be a non-zero multiple of 1024.
+	///
+	/// This does not affect threads created in Rust and then attached to the JVM.
+	pub fn thread_stack_size(&mut self, bytes: u64) -> Result<(), VmError> {
+		self.set_memory_option("-Xss", bytes, 1024)
+	}
+
+	fn set_memory_option(&mut self, option: &'static str, bytes: u64, min: u64) -> Result<(), VmError> {
+		if bytes < min || !bytes.is_multiple_of(1024) {
+			return Err(VmError::InvalidMemorySize { option, bytes });
+		}
+		// use the largest unit that represents the size exactly
+		let (size, unit) = [(1 << 30, "g"), (1 << 20, "m"), (1 << 10, "k")].iter()
+			.find(|(scale, _)| bytes.is_multiple_of(*scale))
+			.map(|(scale, unit)| (bytes / scale, *unit))
+			.expect("size to be a multiple of 1024");
+
+		self.options.retain(|opt| !opt.starts_with(option));
+		self.options.push(Cow::from(format!("{}{}{}", option, size, unit)));
+		Ok(())
+	}
+
+	/// Creates options for `version` from the `JDK_JAVA_OPTIONS` environment variable. See [`VmOptions::merge_env`].
+	pub fn from_env(version: JniVersion) -> Result<VmOptions, VmError> {
+		let mut opts = VmOptions::new(version);
+		opts.merge_env()?;
+		Ok(opts)
+	}
+
+	/// Appends the options in the `JDK_JAVA_OPTIONS` environment variable, so an embedded JVM honors the same
+	/// settings as the `java` launcher.
+	///
+	/// `JAVA_TOOL_OPTIONS` is left out, as most JVMs (including HotSpot and OpenJ9) already read it themselves during
+	/// creation, and merging it too would load any `-javaagent` or `-agentlib` twice.
+	pub fn merge_env(&mut self) -> Result<(), VmError> {
+		self.merge_env_var("JDK_JAVA_OPTIONS")
+	}
+
+	/// Appends the options in environment variable `var`, if it is set.
+	///
+	/// Options are separated by whitespace, except within single or double quotes, which are removed. As with the
+	/// `java` launcher, there are no escape characters. The hook options (`vfprintf`, `exit` and `abort`) are rejected
+	/// with [`VmError::ReservedOption`], leaving the options unchanged.
+	pub fn merge_env_var(&mut self, var: &str) -> Result<(), VmError> {
+		let value = match std::env::var(var) {
+			Ok(value) => value,
+			Err(std::env::VarError::NotPresent) => return Ok(()),
+			Err(std::env::VarError::NotUnicode(_)) => return Err(VmError::InvalidEnvOptions(var.to_owned(), "value is not valid unicode")),
+		};
+		let opts = split_options(&value).map_err(|reason| VmError::InvalidEnvOptions(var.to_owned(), reason))?;
+		if let Some((opt, setter)) = opts.iter().find_map(|opt| reserved_option(opt).map(|setter| (opt, setter))) {
+			return Err(VmError::ReservedOption(opt.clone(), setter));
+		}
+		log::debug!("adding options from {}: {:?}", var, opts);
+		self.options.extend(opts.into_iter().map(Cow::from));
+		Ok(())
+	}
+
+	/// Checks every option against the option forms HotSpot accepts, returning [`VmError::UnrecognizedOption`] for
+	/// the first that isn't.
+	///
+	/// `-XX:` options are only checked for their shape, as the flags available vary between releases. Other JVMs, such
+	/// as OpenJ9, accept `-X` options HotSpot does not, and may be wrongly rejected. Options are not checked at all if
+	/// unrecognized options are allowed.
+	pub fn validate(&self) -> Result<(), VmError> {
+		if self.ignore_unrecognized {
+			return Ok(());
+		}
+		match self.options.iter().find(|opt| !recognized_option(opt)) {
+			Some(opt) => Err(VmError::UnrecognizedOption(opt.to_string())),
+			None => Ok(()),
+		}
+	}
+
+	/// Narrows down an error from `JNI_CreateJavaVM` to the option that caused it, where possible.
+	fn diagnose_failure(&self, err: VmError) -> VmError {
+		if self.ignore_unrecognized || !matches!(err, VmError::InvalidArguments | VmError::Unknown) {
+			return err;
+		}
+		if let Err(unrecognized) = self.validate() {
+			return unrecognized;
+		}
+		#[cfg(feature = "bisect-options")]
+		{
+			let options: Vec<&str> = self.options.iter().map(|opt| opt.as_ref()).collect();
+			if let Some(opt) = bisect_options(&options) {
+				return VmError::UnrecognizedOption(opt.to_owned());
+			}
+		}
+		err
+	}
+}
+
+/// Splits an options string the way the `java` launcher splits `JDK_JAVA_OPTIONS`.
+pub(crate) fn split_options(value: &str) -> Result<Vec<String>, &'static str> {
+	let mut opts = Vec::new();
+	let mut current: Option<String> = None;
+	let mut quote = None;
+	for c in value.chars() {
+		match (quote, c) {
+			(Some(q), c) if c == q => quote = None,
+			(Some(_), c) => current.get_or_insert_with(String::new).push(c),
+			(None, '"' | '\'') => {
+				quote = Some(c);
+				// quotes may delimit an empty option, such as `-Dempty=""` or `""`
+				current.get_or_insert_with(String::new);
+			},
+			(None, c) if c.is_whitespace() => opts.extend(current.take()),
+			(None, c) => current.get_or_insert_with(String::new).push(c),
+		}
+	}
+	if quote.is_some() {
+		return Err("unmatched quote");
+	}
+	opts.extend(current);
+	Ok(opts)
+}
+
+/// Returns the `VmOptions` setter to use instead, if `opt` is one of the invocation API's hook options.
+fn reserved_option(opt: &str) -> Option<&'static str> {
+	match opt {
+		"vfprintf" => Some("set_vfprintf_hook"),
+		"exit" => Some("set_exit_hook"),
+		"abort" => Some("set_abort_hook"),
+		_ => None,
+	}
+}
+
+/// Whether HotSpot recognizes `opt`, by the `java` launcher's documented options and the JNI invocation API's own.
+fn recognized_option(opt: &str) -> bool {
+	// options that take a value directly after the prefix, which is required when the prefix ends in `:` or `=`
+	const PREFIXED: &[&str] = &[
+		"-Xbootclasspath:", "-Xbootclasspath/a:", "-Xbootclasspath/p:", "-Xlog", "-Xloggc:", "-Xmn", "-Xms", "-Xmx",
+		"-Xss", "-Xshare:", "-XshowSettings", "-Xverify:", "-Xrun", "-agentlib:", "-agentpath:", "-javaagent:",
+		"--add-opens=", "--add-exports=", "--add-reads=", "--add-modules=", "--limit-modules=", "--module-path=",
+		"--upgrade-module-path=", "--patch-module=", "--enable-native-access=", "--illegal-access=",
+		"--finalization=",
+	];
+	const EXACT: &[&str] = &[
+		"-Xbatch", "-Xcheck:jni", "-Xcomp", "-Xdebug", "-Xdiag", "-Xfuture", "-Xint", "-Xinternalversion", "-Xmixed",
+		"-Xnoagent", "-Xnoclassgc", "-Xprof", "-Xrs", "-Xincgc", "-Xusealtsigs", "--enable-preview",
+		"-verbose", "-verbose:class", "-verbose:gc", "-verbose:jni", "-verbose:module",
+	];
+	const ASSERTIONS: &[&str] = &[
+		"-ea", "-da", "-esa", "-dsa", "-enableassertions", "-disableassertions", "-enablesystemassertions",
+		"-disablesystemassertions",
+	];
+
+	if let Some(property) = opt.strip_prefix("-D") {
+		return !property.is_empty() && !property.starts_with('=');
+	}
+	if let Some(flag) = opt.strip_prefix("-XX:") {
+		let name = match flag.split_once('=') {
+			Some((name, _)) => name,
+			None => match flag.strip_prefix(['+', '-']) {
+				Some(name) => name,
+				None => return false,
+			},
+		};
+		return !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+	}
+	if ASSERTIONS.iter().any(|a| opt == *a || opt.strip_prefix(a).is_some_and(|rest| rest.starts_with(':'))) {
+		return true;
+	}
+	EXACT.contains(&opt) || PREFIXED.iter().any(|prefix| {
+		opt.strip_prefix(prefix).is_some_and(|rest| !rest.is_empty() || !prefix.ends_with([':', '=']))
+	})
+}
+
+/// Finds the option that stops a JVM from starting, by running `java -version` with ever shorter prefixes of
+/// `options` from the install [`locate::find`](crate::locate::find) returns.
+///
+/// This assumes the options are rejected the same way by the launcher as through `JNI_CreateJavaVM`, and that a
+/// single option is at fault. Options with side effects, such as agents, run in each child process.
+#[cfg(feature = "bisect-options")]
+pub fn bisect_options(options: &[&str]) -> Option<String> {
+	let install = crate::locate::find()?;
+	let java = install.home.join("bin").join(if cfg!(windows) { "java.exe" } else { "java" });
+	let starts = |options: &[&str]| -> Option<bool> {
+		let status = std::process::Command::new(&java)
+			.args(options)
+			.arg("-version")
+			.stdin(std::process::Stdio::null())
+			.stdout(std::process::Stdio::null())
+			.stderr(std::process::Stdio::null())
+			.status();
+		match status {
+			Ok(status) => Some(status.success()),
+			Err(e) => {
+				log::debug!("unable to run {} to bisect options: {}", java.display(), e);
+				None
+			},
+		}
+	};
+
+	if starts(options)? {
+		return None;
+	}
+	// the shortest prefix that fails ends with the offending option
+	let (mut starting, mut failing) = (0, options.len());
+	while failing - starting > 1 {
+		let mid = starting + (failing - starting) / 2;
+		if starts(&options[..mid])? {
+			starting = mid;
+		} else {
+			failing = mid;
+		}
+	}
+	log::debug!("bisected VM options to {:?}", options[failing - 1]);
+	Some(options[failing - 1].to_owned())
+}
+
+// HotSpot refuses to start with a smaller heap
+const MIN_HEAP_SIZE: u64 = 2 << 20;
+
+const CLASSPATH_OPTION: &str = "-Djava.class.path=";
+const MODULE_PATH_OPTION: &str = "--module-path=";
+#[cfg(windows)]
+const PATH_SEPARATOR: char = ';';
+#[cfg(not(windows))]
+const PATH_SEPARATOR: char = ':';
+
+fn agent_option(kind: &str, agent: &str, args: Option<&str>) -> String {
+	match args {
+		Some(args) => format!("{}{}={}", kind, agent, args),
+		None => format!("{}{}", kind, agent),
+	}
+}
+
+fn option_path(path: &Path) -> Result<&str, VmError> {
+	let s = path.to_str().ok_or_else(|| VmError::InvalidPath(path.to_owned(), "path is not valid unicode"))?;
+	if s.is_empty() {
+		return Err(VmError::InvalidPath(path.to_owned(), "path is empty"));
+	}
+	Ok(s)
+}
+
+fn search_path_entry(path: &Path) -> Result<&str, VmError> {
+	let entry = option_path(path)?;
+	if entry.contains(PATH_SEPARATOR) {
+		return Err(VmError::InvalidPath(path.to_owned(), "path contains the path separator"));
+	}
+	Ok(entry)
 }
 
 
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum VmError {
 	#[error("an unknown error has occured")]
 	Unknown,
@@ -317,6 +1521,36 @@ pub enum VmError {
 
 	#[error("a JavaVM function returned a malformed CESU8 string")]
 	BadCesu8String(#[from] cesu8::Cesu8DecodingError),
+
+	#[error("invalid size for {option} option: {bytes} bytes")]
+	InvalidMemorySize { option: &'static str, bytes: u64 },
+	#[cfg(feature = "invocation")]
+	#[error(transparent)]
+	Load(#[from] crate::invocation::LoadError),
+
+	#[error("the global JavaVM has already been destroyed")]
+	GlobalDestroyed,
+	#[error("the JavaVM has already been destroyed")]
+	Destroyed,
+	#[error("the JavaVM did not respond within {:?}", .0)]
+	Unresponsive(Duration),
+	#[error("unable to spawn a thread: {}", .0)]
+	Spawn(#[source] std::io::Error),
+	#[error("invalid path {:?} in VM options: {}", .0, .1)]
+	InvalidPath(PathBuf, &'static str),
+	#[error("invalid options in environment variable {}: {}", .0, .1)]
+	InvalidEnvOptions(String, &'static str),
+	#[error("the JVM does not recognize option {:?}", .0)]
+	UnrecognizedOption(String),
+	#[error("option {:?} can't be passed as a string, use VmOptions::{} instead", .0, .1)]
+	ReservedOption(String, &'static str),
+	#[error("the JNI invocation API is unavailable on this platform")]
+	InvocationUnavailable,
+
+	#[error(transparent)]
+	Size(#[from] SizeError),
+	#[error(transparent)]
+	Jni(#[from] crate::env::JniError),
 }
 impl VmError {
 	/// Checks that a given number (likely from the result of a JNI function) does not correspond to an error constant.
@@ -331,9 +1565,10 @@ impl VmError {
 			js::JNI_EVERSION => Err(VmError::BadVersion),
 			js::JNI_ENOMEM => Err(VmError::NotEnoughMemory),
 			js::JNI_EEXIST => {
+				// some JVMs (such as HotSpot 17) hide the existing VM from JNI_GetCreatedJavaVMs after a failed creation
 				let jvm = JavaVM::created_jvms()
 					.map_err(|e| Box::new(e))
-					.map(|lis| *lis.get(0).expect("JavaVM to exist if JNI_EEXIST was returned"));
+					.and_then(|lis| lis.first().copied().ok_or_else(|| Box::new(VmError::Unknown)));
 
 				Err(VmError::VMExists(jvm))
 			},
@@ -347,9 +1582,257 @@ impl VmError {
 
 #[cfg(test)]
 mod tests {
+	use proptest::prelude::*;
 	use crate::jvm::{JavaVM, JniVersion, VmOptions, VmError};
+	use std::time::Duration;
+
+	#[test]
+	fn version_constants_match_jni() {
+		assert_eq!(jni_sys::JNI_VERSION_1_1 as u32, JniVersion::V1_1.as_native());
+		assert_eq!(jni_sys::JNI_VERSION_1_8 as u32, JniVersion::V1_8.as_native());
+		// newer than jni-sys 0.3.0's constants
+		assert_eq!(0x000a_0000, JniVersion::V10.as_native());
+		assert_eq!(0x0015_0000, JniVersion::V21.as_native());
+		assert_eq!(JniVersion::V1_8, JniVersion::from_native(jni_sys::JNI_VERSION_1_8 as u32));
+		assert_eq!(JniVersion::V10, JniVersion::from_native(0x000a_0000));
+	}
+
+	proptest! {
+		#[test]
+		fn version_struct_roundtrip(major: u16, minor: u16) {
+			let version = JniVersion::new(major, minor);
+			prop_assert_eq!(version, JniVersion::from_native(version.as_native()));
+		}
+
+		#[test]
+		fn version_native_roundtrip(native: u32) {
+			prop_assert_eq!(native, JniVersion::from_native(native).as_native());
+		}
+	}
+
+	#[test]
+	fn default_args() {
+		let options = JavaVM::default_args(JniVersion::V10).expect("error getting default args");
+		assert_eq!(JniVersion::V10, options.version);
+	}
+
+	#[test]
+	fn classpath_builder() {
+		let sep = super::PATH_SEPARATOR;
+		let mut options = VmOptions::new(JniVersion::V10);
+		options.add_classpath_entry("classes").expect("error adding directory");
+		options.add_jar("lib/dep.JAR").expect("error adding jar");
+		assert_eq!(options.options(), [format!("-Djava.class.path=classes{}lib/dep.JAR", sep)]);
+
+		assert!(matches!(options.add_jar("lib/dep.zip"), Err(VmError::InvalidPath(..))));
+		assert!(matches!(options.add_classpath_entry(format!("a{}b", sep)), Err(VmError::InvalidPath(..))));
+		#[cfg(unix)]
+		{
+			use std::os::unix::ffi::OsStrExt;
+			let non_unicode = std::ffi::OsStr::from_bytes(b"caf\xe9.jar");
+			assert!(matches!(options.add_jar(non_unicode), Err(VmError::InvalidPath(..))));
+		}
+
+		options.push_property("a", "b");
+		options.set_classpath(["x.jar", "y"]).expect("error setting classpath");
+		assert_eq!(options.options(), ["-Da=b".to_owned(), format!("-Djava.class.path=x.jar{}y", sep)]);
+	}
+
+	#[test]
+	fn option_validation() {
+		for opt in ["-Dx=1", "-Dflag", "-Xmx1g", "-Xcheck:jni", "-Xlog:gc", "-XX:+UseG1GC", "-XX:-UseCompressedOops",
+			"-XX:MaxMetaspaceSize=64m", "-ea", "-da:com.example...", "-verbose:jni", "-agentlib:jdwp=transport=dt_socket",
+			"--add-opens=java.base/java.lang=ALL-UNNAMED", "--enable-preview"]
+		{
+			assert!(super::recognized_option(opt), "rejected {:?}", opt);
+		}
+		for opt in ["-D", "-D=1", "-Xbogus", "-Xcheck:nothing", "-XX:UseG1GC", "-XX:+", "-XX:+Use-G1", "-eax",
+			"-verbose:everything", "-agentlib:", "--add-opens", "--add-opens=", "Xmx1g", ""]
+		{
+			assert!(!super::recognized_option(opt), "accepted {:?}", opt);
+		}
+
+		let mut options = VmOptions::with_opts(JniVersion::V10, vec!["-Xss1m".into(), "-Xfoo".into(), "-Xbar".into()]);
+		assert!(matches!(options.validate(), Err(VmError::UnrecognizedOption(opt)) if opt == "-Xfoo"));
+		options.allow_unrecognized_options(true);
+		assert!(options.validate().is_ok());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde_round_trip() {
+		let mut options = VmOptions::new(JniVersion::V10);
+		options.push_property("greeting", "hello world");
+		options.allow_unrecognized_options(true);
+
+		let json = serde_json::to_string(&options).expect("error serializing options");
+		let parsed: VmOptions = serde_json::from_str(&json).expect("error deserializing options");
+		assert_eq!(JniVersion::V10, parsed.version);
+		assert_eq!(options.options(), parsed.options());
+		assert!(parsed.ignore_unrecognized);
+
+		let parsed: VmOptions = serde_json::from_str(r#"{ "version": { "major": 1, "minor": 8 } }"#).expect("error deserializing options");
+		assert_eq!(JniVersion::V1_8, parsed.version);
+		assert!(parsed.options().is_empty());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde_rejects_hook_options() {
+		let parsed: VmOptions = serde_json::from_str(r#"{ "version": { "major": 10, "minor": 0 }, "options": ["-Xmx64m", "exit"] }"#)
+			.expect("error deserializing options");
+		match JavaVM::create(parsed) {
+			Err(VmError::ReservedOption(opt, setter)) => assert_eq!(("exit", "set_exit_hook"), (opt.as_str(), setter)),
+			other => panic!("expected a reserved option error, got {:?}", other.map(|_| ())),
+		}
+	}
+
+	#[test]
+	fn module_options() {
+		let sep = super::PATH_SEPARATOR;
+		let mut options = VmOptions::new(JniVersion::V10);
+		options.add_opens("java.base", "java.lang", VmOptions::ALL_UNNAMED);
+		options.add_exports("java.desktop", "sun.awt", "my.module");
+		options.add_modules(["java.sql", "jdk.unsupported"]);
+		options.add_module_path_entry("mods").expect("error adding module directory");
+		options.add_module_path_entry("lib/app.jar").expect("error adding modular jar");
+		assert_eq!(options.options(), [
+			"--add-opens=java.base/java.lang=ALL-UNNAMED".to_owned(),
+			"--add-exports=java.desktop/sun.awt=my.module".to_owned(),
+			"--add-modules=java.sql,jdk.unsupported".to_owned(),
+			format!("--module-path=mods{}lib/app.jar", sep),
+		]);
+	}
+
+	#[test]
+	fn agent_options() {
+		let mut options = VmOptions::new(JniVersion::V10);
+		options.add_java_agent("agents/trace.jar", Some("verbose")).expect("error adding java agent");
+		options.add_native_agent("hprof", None);
+		options.enable_jdwp(5005, false);
+		assert_eq!(options.options(), [
+			"-javaagent:agents/trace.jar=verbose",
+			"-agentlib:hprof",
+			"-agentlib:jdwp=transport=dt_socket,server=y,suspend=n,address=5005",
+		]);
+		assert!(matches!(options.add_java_agent("", None), Err(VmError::InvalidPath(..))));
+	}
+
+	#[test]
+	fn memory_options() {
+		let mut options = VmOptions::new(JniVersion::V10);
+		options.max_heap(3 << 30).expect("error setting max heap");
+		options.initial_heap(64 << 20).expect("error setting initial heap");
+		options.thread_stack_size(1536 << 10).expect("error setting stack size");
+		options.max_heap(512 << 20).expect("error replacing max heap");
+		assert_eq!(options.options(), ["-Xms64m", "-Xss1536k", "-Xmx512m"]);
+
+		assert!(matches!(options.max_heap(1 << 20), Err(VmError::InvalidMemorySize { option: "-Xmx", .. })));
+		assert!(matches!(options.thread_stack_size(1000), Err(VmError::InvalidMemorySize { .. })));
+		assert!(matches!(options.thread_stack_size(0), Err(VmError::InvalidMemorySize { .. })));
+	}
 
 	rusty_fork::rusty_fork_test! {
+		// sets environment variables, which other threads of a shared test process may be reading
+		#[test]
+		fn env_options() {
+			use super::split_options;
+
+			assert!(split_options("  ").expect("error splitting blank options").is_empty());
+			let opts = split_options(" -Xmx1g\t-Dgreeting='hello world' -Dempty=\"\" \"it's\" ").expect("error splitting options");
+			assert_eq!(opts, ["-Xmx1g", "-Dgreeting=hello world", "-Dempty=", "it's"]);
+			assert_eq!(Err("unmatched quote"), split_options("-Dname=\"oops"));
+
+			let var = format!("YAJNIR_TEST_OPTIONS_{}", std::process::id());
+			std::env::set_var(&var, "-Da=1 \"-Db=2 3\"");
+			let mut options = VmOptions::new(JniVersion::V10);
+			options.push_property("first", "0");
+			options.merge_env_var(&var).expect("error merging options");
+			options.merge_env_var("YAJNIR_UNSET_OPTIONS").expect("error merging missing options");
+			assert_eq!(options.options(), ["-Dfirst=0", "-Da=1", "-Db=2 3"]);
+
+			std::env::set_var(&var, "-Dc=4 abort");
+			assert!(matches!(options.merge_env_var(&var), Err(VmError::ReservedOption(opt, "set_abort_hook")) if opt == "abort"));
+			assert_eq!(options.options(), ["-Dfirst=0", "-Da=1", "-Db=2 3"]);
+			std::env::remove_var(&var);
+
+			// the JVM reads JAVA_TOOL_OPTIONS itself
+			std::env::set_var("JAVA_TOOL_OPTIONS", "-javaagent:agent.jar");
+			std::env::set_var("JDK_JAVA_OPTIONS", "-Xmx64m");
+			let options = VmOptions::from_env(JniVersion::V10).expect("error reading options");
+			assert_eq!(options.options(), ["-Xmx64m"]);
+		}
+
+		#[test]
+		fn current_thread_env() {
+			use crate::env::JniEnv;
+
+			assert!(JniEnv::current().is_none());
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert!(vm.is_current_thread_attached().expect("error checking attachment"));
+			assert_eq!(Some(env.ptr), JniEnv::current().map(|env| env.ptr));
+
+			std::thread::spawn(move || {
+				assert!(!vm.is_current_thread_attached().expect("error checking attachment"));
+				assert!(JniEnv::current().is_none());
+				let guard = vm.attach_current_thread().expect("error attaching");
+				assert!(vm.is_current_thread_attached().expect("error checking attachment"));
+				assert_eq!(Some(guard.env().ptr), JniEnv::current().map(|env| env.ptr));
+			}).join().expect("worker thread panicked");
+
+			vm.destroy().expect("error destroying vm");
+			assert!(matches!(vm.is_current_thread_attached(), Err(VmError::Destroyed)));
+		}
+
+		#[test]
+		fn on_load_captures_vm() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let res = unsafe {
+				super::on_load(vm.ptr.as_ptr(), JniVersion::V1_8, |loaded, env| {
+					assert_eq!(vm.ptr, loaded.ptr);
+					env.exception_check()?;
+					Ok(())
+				})
+			};
+			assert_eq!(jni_sys::JNI_VERSION_1_8, res);
+			assert_eq!(Some(vm.ptr), super::global().map(|vm| vm.ptr));
+
+			let failed = unsafe { super::on_load(vm.ptr.as_ptr(), JniVersion::V1_8, |_, _| Err("init failed".into())) };
+			assert_eq!(jni_sys::JNI_ERR, failed);
+			let panicked = unsafe { super::on_load(vm.ptr.as_ptr(), JniVersion::V1_8, |_, _| panic!("init panicked")) };
+			assert_eq!(jni_sys::JNI_ERR, panicked);
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn names_unrecognized_option() {
+			let options = VmOptions::with_opts(JniVersion::V10, vec!["-Xss1m".into(), "-Xbogus".into()]);
+			match JavaVM::create(options) {
+				Err(VmError::UnrecognizedOption(opt)) => assert_eq!("-Xbogus", opt),
+				res => panic!("expected the unrecognized option to be named, got {:?}", res.map(|_| ())),
+			}
+		}
+
+		#[test]
+		#[cfg(feature = "bisect-options")]
+		fn bisects_options() {
+			// well-formed, so only starting a JVM can tell it's unrecognized
+			let options = ["-Xss1m", "-Dx=1", "-XX:+NoSuchFlagForYajnir", "-Xint"];
+			assert_eq!(Some("-XX:+NoSuchFlagForYajnir"), super::bisect_options(&options).as_deref());
+			assert_eq!(None, super::bisect_options(&options[..2]));
+		}
+
+		#[test]
+		fn version_probing() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert!(vm.supports(JniVersion::V1_8).expect("error probing version"));
+			assert!(!vm.supports(JniVersion::new(999, 0)).expect("error probing version"));
+			assert!(vm.supported_version().expect("error probing version") >= JniVersion::V10);
+			vm.destroy().expect("error destroying vm");
+		}
+
 		#[test]
 		fn create_destroy_jvm() {
 			// return Ok(());
@@ -361,6 +1844,203 @@ mod tests {
 			vm.destroy().expect("error destroying vm");
 		}
 
+		#[test]
+		fn create_with_module_options() {
+			let mut options = VmOptions::new(JniVersion::V10);
+			options.add_opens("java.base", "java.lang", VmOptions::ALL_UNNAMED);
+			options.add_exports("java.base", "jdk.internal.misc", VmOptions::ALL_UNNAMED);
+			options.add_modules(["java.logging"]);
+			let (vm, env) = JavaVM::create(options).expect("error creating vm with module options");
+
+			let logger = env.find_class("java/util/logging/Logger").expect("added module to be resolved");
+			env.delete_local_ref(logger).expect("error deleting local ref");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn creation_report() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let report = vm.creation_report().expect("creation to be timed");
+			assert!(report.create_vm > std::time::Duration::ZERO);
+			assert_eq!(None, report.first_attach);
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn vfprintf_hook() {
+			use std::sync::atomic::{AtomicUsize, Ordering};
+			static CALLS: AtomicUsize = AtomicUsize::new(0);
+			unsafe extern "C" fn counting(fp: *mut std::ffi::c_void, format: *const std::os::raw::c_char, args: *mut std::ffi::c_void) -> jni_sys::jint {
+				CALLS.fetch_add(1, Ordering::SeqCst);
+				crate::jvm::log_vfprintf(fp, format, args)
+			}
+
+			let mut options = VmOptions::new(JniVersion::V10);
+			options.replace_options(vec!["-XX:+PrintCommandLineFlags".into()]);
+			options.set_vfprintf_hook(Some(counting));
+			let (vm, _env) = JavaVM::create(options).expect("error creating vm");
+			assert!(CALLS.load(Ordering::SeqCst) > 0, "JVM output to bypass the hook");
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn exit_hook() {
+			// runs in a forked process, so the hook reports success through the exit status
+			fn on_exit(code: i32) {
+				std::process::exit(if code == 3 { 0 } else { 1 });
+			}
+
+			let mut options = VmOptions::new(JniVersion::V10);
+			options.set_exit_hook(Some(on_exit));
+			let (_vm, env) = JavaVM::create(options).expect("error creating vm");
+
+			let system = env.find_class("java/lang/System").expect("error finding System");
+			let exit = env.get_static_method_id(system, "exit", "(I)V").expect("error finding System.exit");
+			let _ = env.call_static_void_method(system, exit, &[jni_sys::jvalue { i: 3 }]);
+			unreachable!("System.exit returned");
+		}
+
+		#[test]
+		fn attach_secondary_thread() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let version = crate::lang::JSystem::property(env, "java.version").expect("error reading property");
+
+			std::thread::scope(|s| {
+				std::thread::Builder::new().name("yajnir-worker".to_owned()).spawn_scoped(s, || {
+					let guard = vm.attach_current_thread().expect("error attaching thread");
+					let attached = crate::lang::JSystem::property(guard.env(), "java.version").expect("error reading property");
+					assert_eq!(version, attached);
+
+					let name = crate::lang::JThread::current_name(guard.env()).expect("error reading thread name");
+					assert_eq!("yajnir-worker", name);
+					crate::lang::JThread::set_name(guard.env(), "renamed").expect("error renaming thread");
+					assert_eq!("renamed", crate::lang::JThread::current_name(guard.env()).expect("error reading thread name"));
+				}).expect("error spawning thread");
+			});
+			assert!(vm.creation_report().expect("creation to be timed").first_attach.is_some());
+
+			// the creating thread is already attached, so its guard must not detach it
+			drop(vm.attach_current_thread().expect("error attaching thread"));
+			env.exception_check().expect("creating thread to still be attached");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn with_env_restores_attachment() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			std::thread::spawn(move || {
+				let version = vm.with_env(|env| crate::lang::JSystem::property(env, "java.version"))
+					.expect("error attaching thread")
+					.expect("error reading property");
+				assert!(version.is_some());
+				assert!(vm.get_env().expect("error checking attachment").is_none(), "thread to be detached again");
+			}).join().expect("worker thread panicked");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn ping_responds() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let timeout = Duration::from_secs(10);
+			let latency = vm.ping(timeout).expect("error pinging vm");
+			assert!(latency < timeout);
+
+			vm.destroy().expect("error destroying vm");
+			assert!(matches!(vm.ping(timeout), Err(VmError::Destroyed)));
+		}
+
+		#[test]
+		fn attach_with_name_and_group() {
+			use crate::lang::{JThread, JThreadGroup};
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let group = JThreadGroup::new(env, "yajnir-workers").expect("error creating thread group")
+				.to_global_send().expect("error creating global ref");
+			let args = crate::jvm::AttachArgs::new().name("yajnir-named").group(group);
+
+			std::thread::spawn(move || {
+				let guard = vm.attach_current_thread_with(&args).expect("error attaching thread");
+				let env = guard.env();
+				assert_eq!("yajnir-named", JThread::current_name(env).expect("error reading thread name"));
+				let thread = JThread::current(env).expect("error getting current thread");
+				let group = JThread::group(env, &thread).expect("error reading thread group").expect("thread to have a group");
+				assert_eq!("yajnir-workers", JThreadGroup::name(env, &group).expect("error reading group name"));
+			}).join().expect("worker thread panicked");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn attach_worker_permanently() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			std::thread::spawn(move || {
+				let guard = vm.attach_current_thread().expect("error attaching thread");
+				let env = vm.attach_permanently().expect("error attaching thread");
+				assert_eq!(guard.env().ptr, env.ptr);
+				drop(guard);
+
+				// the guard handed its attachment over, so the env is still usable
+				crate::lang::JSystem::property(env, "java.version").expect("error reading property");
+				assert_eq!(env.ptr, vm.attach_permanently().expect("error attaching thread").ptr);
+			}).join().expect("worker thread panicked");
+
+			// DestroyJavaVM waits for attached threads, so this only returns if the worker detached on exit
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn create_or_attach_existing() {
+			let (vm, _env) = JavaVM::create_or_attach(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			std::thread::spawn(move || {
+				let (existing, env) = JavaVM::create_or_attach(VmOptions::new(JniVersion::V10)).expect("error attaching to vm");
+				assert_eq!(vm.ptr, existing.ptr);
+				crate::lang::JSystem::property(env, "java.version").expect("error reading property");
+			}).join().expect("worker thread panicked");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn global_vm_lifecycle() {
+			assert!(crate::jvm::global().is_none());
+			let vm = crate::jvm::init_global(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			assert_eq!(vm.ptr, crate::jvm::global().expect("global vm to exist").ptr);
+			assert_eq!(vm.ptr, crate::jvm::init_global(VmOptions::new(JniVersion::V10)).expect("error getting vm").ptr);
+
+			vm.destroy().expect("error destroying vm");
+			assert!(crate::jvm::global().is_none());
+			assert!(matches!(crate::jvm::init_global(VmOptions::new(JniVersion::V10)), Err(VmError::GlobalDestroyed)));
+		}
+
+		#[test]
+		fn create_with_destroys_after_panic() {
+			let res = std::panic::catch_unwind(|| {
+				JavaVM::create_with(VmOptions::new(JniVersion::V10), |_vm, _env| panic!("test failure"))
+			});
+			assert!(res.is_err());
+			assert!(JavaVM::created_jvms().expect("error listing vms").is_empty());
+		}
+
+		#[test]
+		fn create_on_secondary_thread() {
+			let main_thread = std::thread::current().id();
+			let handle = JavaVM::create_on_thread(VmOptions::new(JniVersion::V10), move |_vm, _env| {
+				std::thread::current().id() != main_thread
+			}).expect("error spawning vm thread");
+
+			let on_other_thread = handle.join().expect("vm thread panicked").expect("error creating vm");
+			assert!(on_other_thread);
+		}
+
 		#[test]
 		fn destroy_jvm_twice() {
 			let options = VmOptions::new(JniVersion::V10);
@@ -0,0 +1,669 @@
+//! Descriptors for core `java.lang` types.
+
+#[cfg(feature = "collections")]
+use std::collections::HashMap;
+use std::time::Duration;
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, Implements, JavaClass, RawJObject, RichJavaType};
+#[cfg(feature = "collections")]
+use crate::util::{string_map, string_properties};
+
+/// Looks up a class and pins it with a global reference, for use within descriptor objects.
+pub(crate) fn class_ref(env: JniEnv<'_>, name: &str) -> Result<GlobalRef<JClass>, JniError> {
+	let local = env.find_class(name)?;
+	let global = GlobalRef::pinned(env, local);
+	env.delete_local_ref(local)?;
+	let global = global?;
+	crate::names::class_pinned(global.as_raw_nonnull(), name);
+	Ok(global)
+}
+
+/// `java.lang.Object`
+#[derive(Debug)]
+pub struct JObject;
+#[derive(Debug)]
+pub struct ObjectIDs {
+	pub(crate) equals: MethodId,
+	pub(crate) to_string: MethodId,
+}
+impl RichJavaType for JObject {
+	type IDs = ObjectIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ObjectIDs {
+		static IDS: OnceCell<ObjectIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JObject::INTERNAL_NAME)?;
+			let ids = ObjectIDs {
+				equals: env.get_method_id(class, "equals", "(Ljava/lang/Object;)Z")?,
+				to_string: env.get_method_id(class, "toString", "()Ljava/lang/String;")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve java/lang/Object descriptors");
+		ids
+	}
+}
+impl JavaClass for JObject {
+	const INTERNAL_NAME: &'static str = "java/lang/Object";
+}
+// SAFETY: every class extends java.lang.Object
+unsafe impl<T: JavaClass> Implements<JObject> for T {}
+
+impl JObject {
+	/// Compares two objects of any type with `a.equals(b)`.
+	///
+	/// Contract: attached, reported
+	pub fn equals<TA, TB, A, B>(env: JniEnv<'_>, a: &A, b: &B) -> Result<bool, JniError>
+		where TA: RichJavaType, TB: RichJavaType, A: AsRawObject<TA>, B: AsRawObject<TB>
+	{
+		env.call_boolean_method(raw_nonnull(a), JObject::descriptors(env).equals, &[js::jvalue { l: b.as_raw() }])
+	}
+
+	/// Returns `obj.toString()`.
+	///
+	/// Contract: attached, reported
+	pub fn to_string<T: RichJavaType, R: AsRawObject<T>>(env: JniEnv<'_>, obj: &R) -> Result<String, JniError> {
+		let string = env.call_object_method(raw_nonnull(obj), JObject::descriptors(env).to_string, &[])?;
+		Ok(env.take_string(string)?.unwrap_or_else(|| String::from("null")))
+	}
+}
+
+/// `java.lang.Class`
+#[derive(Debug)]
+pub struct JClass;
+impl RichJavaType for JClass {
+	type IDs = ();
+
+	fn descriptors<'thread>(_env: JniEnv<'thread>) -> &'static () {
+		&()
+	}
+}
+impl JavaClass for JClass {
+	const INTERNAL_NAME: &'static str = "java/lang/Class";
+}
+
+/// `java.lang.ClassLoader`
+#[derive(Debug)]
+pub struct JClassLoader;
+impl RichJavaType for JClassLoader {
+	type IDs = ();
+
+	fn descriptors<'thread>(_env: JniEnv<'thread>) -> &'static () {
+		&()
+	}
+}
+impl JavaClass for JClassLoader {
+	const INTERNAL_NAME: &'static str = "java/lang/ClassLoader";
+}
+
+/// `java.lang.Thread`
+#[derive(Debug)]
+pub struct JThread;
+#[derive(Debug)]
+pub struct ThreadIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) current_thread: MethodId,
+	pub(crate) get_context_class_loader: MethodId,
+	pub(crate) set_context_class_loader: MethodId,
+	pub(crate) get_name: MethodId,
+	pub(crate) set_name: MethodId,
+	pub(crate) get_id: MethodId,
+	pub(crate) is_daemon: MethodId,
+	pub(crate) is_alive: MethodId,
+	pub(crate) interrupt: MethodId,
+	pub(crate) join: MethodId,
+	pub(crate) join_timeout: MethodId,
+	pub(crate) get_state: MethodId,
+	pub(crate) get_thread_group: MethodId,
+	pub(crate) enum_name: MethodId,
+}
+impl RichJavaType for JThread {
+	type IDs = ThreadIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ThreadIDs {
+		static IDS: OnceCell<ThreadIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JThread::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+
+			let enum_class = env.find_class("java/lang/Enum")?;
+			let enum_name = env.get_method_id(enum_class, "name", "()Ljava/lang/String;");
+			env.delete_local_ref(enum_class)?;
+			let enum_name = enum_name?;
+
+			Ok(ThreadIDs {
+				current_thread: env.get_static_method_id(raw, "currentThread", "()Ljava/lang/Thread;")?,
+				get_context_class_loader: env.get_method_id(raw, "getContextClassLoader", "()Ljava/lang/ClassLoader;")?,
+				set_context_class_loader: env.get_method_id(raw, "setContextClassLoader", "(Ljava/lang/ClassLoader;)V")?,
+				get_name: env.get_method_id(raw, "getName", "()Ljava/lang/String;")?,
+				set_name: env.get_method_id(raw, "setName", "(Ljava/lang/String;)V")?,
+				get_id: env.get_method_id(raw, "getId", "()J")?,
+				is_daemon: env.get_method_id(raw, "isDaemon", "()Z")?,
+				is_alive: env.get_method_id(raw, "isAlive", "()Z")?,
+				interrupt: env.get_method_id(raw, "interrupt", "()V")?,
+				join: env.get_method_id(raw, "join", "()V")?,
+				join_timeout: env.get_method_id(raw, "join", "(JI)V")?,
+				get_state: env.get_method_id(raw, "getState", "()Ljava/lang/Thread$State;")?,
+				get_thread_group: env.get_method_id(raw, "getThreadGroup", "()Ljava/lang/ThreadGroup;")?,
+				enum_name,
+				class,
+			})
+		}).expect("unable to resolve java/lang/Thread descriptors");
+		ids
+	}
+}
+impl JavaClass for JThread {
+	const INTERNAL_NAME: &'static str = "java/lang/Thread";
+}
+
+impl JThread {
+	pub(crate) fn with_current<O>(env: JniEnv<'_>, func: impl FnOnce(&ThreadIDs, RawJObject) -> Result<O, JniError>) -> Result<O, JniError> {
+		let ids = JThread::descriptors(env);
+		let thread = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.current_thread, &[])?
+			.ok_or(JniError::NullPointer("Thread.currentThread"))?;
+		let res = func(ids, thread);
+		env.delete_local_ref(thread)?;
+		res
+	}
+
+	/// Returns the Java name of the current thread.
+	///
+	/// Contract: attached, reported
+	pub fn current_name(env: JniEnv<'_>) -> Result<String, JniError> {
+		JThread::with_current(env, |ids, thread| {
+			let name = env.call_object_method(thread, ids.get_name, &[])?;
+			env.take_string(name)?.ok_or(JniError::NullPointer("Thread.getName"))
+		})
+	}
+
+	/// Renames the current thread, as seen from Java. The Rust thread's name is unaffected.
+	///
+	/// Contract: attached, reported
+	pub fn set_name(env: JniEnv<'_>, name: &str) -> Result<(), JniError> {
+		JThread::with_current(env, |ids, thread| {
+			let name = env.new_string(name)?;
+			let res = env.call_void_method(thread, ids.set_name, &[js::jvalue { l: name.as_ptr() }]);
+			env.delete_local_ref(name)?;
+			res
+		})
+	}
+
+	/// Returns the `Thread` object for the current thread.
+	///
+	/// Contract: attached, reported
+	pub fn current(env: JniEnv<'_>) -> Result<AutoObj<'_, JThread>, JniError> {
+		let ids = JThread::descriptors(env);
+		let thread = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.current_thread, &[])?
+			.ok_or(JniError::NullPointer("Thread.currentThread"))?;
+		Ok(AutoObj::from_raw(env, thread))
+	}
+
+	/// Returns the thread's name.
+	///
+	/// Contract: attached, reported
+	pub fn name<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R) -> Result<String, JniError> {
+		let name = env.call_object_method(raw_nonnull(thread), JThread::descriptors(env).get_name, &[])?;
+		env.take_string(name)?.ok_or(JniError::NullPointer("Thread.getName"))
+	}
+
+	/// Returns the thread's ID, which is unique while the thread is alive.
+	///
+	/// Contract: attached, reported
+	pub fn id<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R) -> Result<i64, JniError> {
+		env.call_long_method(raw_nonnull(thread), JThread::descriptors(env).get_id, &[])
+	}
+
+	/// Returns whether the thread is a daemon thread, which does not keep the VM from exiting.
+	///
+	/// Contract: attached, reported
+	pub fn is_daemon<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R) -> Result<bool, JniError> {
+		env.call_boolean_method(raw_nonnull(thread), JThread::descriptors(env).is_daemon, &[])
+	}
+
+	/// Interrupts the thread, waking it with an `InterruptedException` if it is waiting, sleeping, or joining.
+	///
+	/// Contract: attached, reported
+	pub fn interrupt<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R) -> Result<(), JniError> {
+		env.call_void_method(raw_nonnull(thread), JThread::descriptors(env).interrupt, &[])
+	}
+
+	/// Waits for the thread to die, for at most `timeout` if given. Returns whether the thread has died.
+	///
+	/// If the current thread is interrupted while waiting, an `InterruptedException` is left pending.
+	///
+	/// Contract: attached, reported
+	pub fn join<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R, timeout: Option<Duration>) -> Result<bool, JniError> {
+		let ids = JThread::descriptors(env);
+		let thread = raw_nonnull(thread);
+		match timeout {
+			None => env.call_void_method(thread, ids.join, &[])?,
+			// Thread.join(0) waits forever, rather than not at all
+			Some(timeout) if timeout.is_zero() => {},
+			Some(timeout) => {
+				use std::convert::TryFrom;
+				let millis = js::jlong::try_from(timeout.as_millis()).unwrap_or(js::jlong::MAX);
+				let nanos = (timeout.subsec_nanos() % 1_000_000) as js::jint;
+				env.call_void_method(thread, ids.join_timeout, &[js::jvalue { j: millis }, js::jvalue { i: nanos }])?;
+			},
+		}
+		Ok(!env.call_boolean_method(thread, ids.is_alive, &[])?)
+	}
+
+	/// Returns the thread's group, or `None` if the thread has terminated.
+	///
+	/// Contract: attached, reported
+	pub fn group<'a, R: AsRawObject<JThread>>(env: JniEnv<'a>, thread: &R) -> Result<Option<AutoObj<'a, JThreadGroup>>, JniError> {
+		let group = env.call_object_method(raw_nonnull(thread), JThread::descriptors(env).get_thread_group, &[])?;
+		Ok(group.map(|group| AutoObj::from_raw(env, group)))
+	}
+
+	/// Returns the thread's current state.
+	///
+	/// Contract: attached, reported
+	pub fn state<R: AsRawObject<JThread>>(env: JniEnv<'_>, thread: &R) -> Result<ThreadState, JniError> {
+		let ids = JThread::descriptors(env);
+		let state = env.call_object_method(raw_nonnull(thread), ids.get_state, &[])?
+			.ok_or(JniError::NullPointer("Thread.getState"))?;
+		let name = env.call_object_method(state, ids.enum_name, &[]);
+		env.delete_local_ref(state)?;
+		let name = env.take_string(name?)?.ok_or(JniError::NullPointer("Enum.name"))?;
+		Ok(match name.as_str() {
+			"NEW" => ThreadState::New,
+			"RUNNABLE" => ThreadState::Runnable,
+			"BLOCKED" => ThreadState::Blocked,
+			"WAITING" => ThreadState::Waiting,
+			"TIMED_WAITING" => ThreadState::TimedWaiting,
+			"TERMINATED" => ThreadState::Terminated,
+			_ => ThreadState::Unknown(name),
+		})
+	}
+}
+
+/// `java.lang.ThreadGroup`
+#[derive(Debug)]
+pub struct JThreadGroup;
+#[derive(Debug)]
+pub struct ThreadGroupIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	pub(crate) get_name: MethodId,
+}
+impl RichJavaType for JThreadGroup {
+	type IDs = ThreadGroupIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ThreadGroupIDs {
+		static IDS: OnceCell<ThreadGroupIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JThreadGroup::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(ThreadGroupIDs {
+				ctor: env.get_method_id(raw, "<init>", "(Ljava/lang/String;)V")?,
+				get_name: env.get_method_id(raw, "getName", "()Ljava/lang/String;")?,
+				class,
+			})
+		}).expect("unable to resolve java/lang/ThreadGroup descriptors");
+		ids
+	}
+}
+impl JavaClass for JThreadGroup {
+	const INTERNAL_NAME: &'static str = "java/lang/ThreadGroup";
+}
+
+impl JThreadGroup {
+	/// Creates a thread group named `name`, as a child of the current thread's group.
+	pub fn new<'a>(env: JniEnv<'a>, name: &str) -> Result<AutoObj<'a, JThreadGroup>, JniError> {
+		let ids = JThreadGroup::descriptors(env);
+		let name = env.new_string(name)?;
+		let group = env.new_object(ids.class.as_raw_nonnull(), ids.ctor, &[js::jvalue { l: name.as_ptr() }]);
+		env.delete_local_ref(name)?;
+		Ok(AutoObj::from_raw(env, group?))
+	}
+
+	/// Returns the thread group's name.
+	pub fn name<R: AsRawObject<JThreadGroup>>(env: JniEnv<'_>, group: &R) -> Result<String, JniError> {
+		let name = env.call_object_method(raw_nonnull(group), JThreadGroup::descriptors(env).get_name, &[])?;
+		env.take_string(name)?.ok_or(JniError::NullPointer("ThreadGroup.getName"))
+	}
+}
+
+/// `java.lang.Thread.State`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ThreadState {
+	/// Not yet started
+	New,
+	/// Running, or ready to run (including while in native code)
+	Runnable,
+	/// Waiting to enter a `synchronized` block
+	Blocked,
+	/// Waiting indefinitely, such as in `Object.wait()` or `Thread.join()`
+	Waiting,
+	/// Waiting with a timeout, such as in `Thread.sleep`
+	TimedWaiting,
+	/// Finished running
+	Terminated,
+	/// A state added by a newer JDK, by its name
+	Unknown(String),
+}
+
+/// `java.lang.Throwable`, along with the exceptions most commonly thrown from native code.
+#[derive(Debug)]
+pub struct JThrowable;
+/// A cached exception class and its `(String)` constructor
+#[derive(Debug)]
+pub struct ExceptionCtor {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+}
+#[derive(Debug)]
+pub struct ThrowableIDs {
+	pub(crate) print_stack_trace: MethodId,
+	pub(crate) string_writer_class: GlobalRef<JClass>,
+	pub(crate) string_writer_ctor: MethodId,
+	pub(crate) print_writer_class: GlobalRef<JClass>,
+	pub(crate) print_writer_ctor: MethodId,
+	pub(crate) illegal_argument: ExceptionCtor,
+	pub(crate) illegal_state: ExceptionCtor,
+	pub(crate) null_pointer: ExceptionCtor,
+	pub(crate) io: ExceptionCtor,
+	pub(crate) unsupported_operation: ExceptionCtor,
+	pub(crate) runtime: ExceptionCtor,
+}
+impl RichJavaType for JThrowable {
+	type IDs = ThrowableIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ThrowableIDs {
+		static IDS: OnceCell<ThrowableIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let ctor = |name: &str| -> Result<ExceptionCtor, JniError> {
+				let class = class_ref(env, name)?;
+				let ctor = env.get_method_id(class.as_raw_nonnull(), "<init>", "(Ljava/lang/String;)V")?;
+				Ok(ExceptionCtor { class, ctor })
+			};
+			let throwable = env.find_class(JThrowable::INTERNAL_NAME)?;
+			let print_stack_trace = env.get_method_id(throwable, "printStackTrace", "(Ljava/io/PrintWriter;)V")?;
+			env.delete_local_ref(throwable)?;
+
+			let string_writer_class = class_ref(env, "java/io/StringWriter")?;
+			let print_writer_class = class_ref(env, "java/io/PrintWriter")?;
+			Ok(ThrowableIDs {
+				print_stack_trace,
+				string_writer_ctor: env.get_method_id(string_writer_class.as_raw_nonnull(), "<init>", "()V")?,
+				print_writer_ctor: env.get_method_id(print_writer_class.as_raw_nonnull(), "<init>", "(Ljava/io/Writer;)V")?,
+				string_writer_class,
+				print_writer_class,
+				illegal_argument: ctor("java/lang/IllegalArgumentException")?,
+				illegal_state: ctor("java/lang/IllegalStateException")?,
+				null_pointer: ctor("java/lang/NullPointerException")?,
+				io: ctor("java/io/IOException")?,
+				unsupported_operation: ctor("java/lang/UnsupportedOperationException")?,
+				runtime: ctor("java/lang/RuntimeException")?,
+			})
+		}).expect("unable to resolve java/lang/Throwable descriptors");
+		ids
+	}
+}
+impl JavaClass for JThrowable {
+	const INTERNAL_NAME: &'static str = "java/lang/Throwable";
+}
+
+impl JThrowable {
+	/// Formats a throwable the way `printStackTrace` does: its class and message, stack trace, and any causes.
+	///
+	/// Must not be called while an exception is pending.
+	///
+	/// Contract: attached, reported
+	pub fn stack_trace<R: AsRawObject<JThrowable>>(env: JniEnv<'_>, throwable: &R) -> Result<String, JniError> {
+		let ids = JThrowable::descriptors(env);
+		let writer = env.new_object(ids.string_writer_class.as_raw_nonnull(), ids.string_writer_ctor, &[])?;
+		let printer = env.new_object(ids.print_writer_class.as_raw_nonnull(), ids.print_writer_ctor, &[js::jvalue { l: writer.as_ptr() }]);
+
+		let trace = printer.and_then(|printer| {
+			let res = env.call_void_method(raw_nonnull(throwable), ids.print_stack_trace, &[js::jvalue { l: printer.as_ptr() }]);
+			env.delete_local_ref(printer)?;
+			res
+		}).and_then(|()| {
+			let trace = env.call_object_method(writer, JObject::descriptors(env).to_string, &[])?;
+			env.take_string(trace)
+		});
+		env.delete_local_ref(writer)?;
+		Ok(trace?.unwrap_or_default())
+	}
+}
+
+/// Handling for the exception pending on the current thread.
+#[derive(Debug)]
+pub struct JavaException;
+
+impl JavaException {
+	/// Clears the pending exception and logs it at `level`, in the same format as `ExceptionDescribe` but through the
+	/// `log` facade instead of stderr. Returns whether an exception was pending.
+	///
+	/// Contract: attached, inspects, pending
+	pub fn describe_to_log(env: JniEnv<'_>, level: log::Level) -> Result<bool, JniError> {
+		let exc = match env.exception_occurred()? {
+			Some(exc) => exc,
+			None => return Ok(false),
+		};
+		env.exception_clear()?;
+
+		let trace = JThrowable::stack_trace(env, &exc);
+		drop(exc);
+		match trace {
+			Ok(trace) => log::log!(level, "uncaught Java exception: {}", trace.trim_end()),
+			Err(e) => {
+				// formatting can itself throw, e.g. from an overridden toString
+				env.exception_clear()?;
+				log::log!(level, "uncaught Java exception (unable to format stack trace: {})", e);
+			},
+		}
+		Ok(true)
+	}
+}
+
+/// `java.lang.System`
+#[derive(Debug)]
+pub struct JSystem;
+#[derive(Debug)]
+pub struct SystemIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	#[cfg(feature = "collections")]
+	pub(crate) getenv: MethodId,
+	pub(crate) getenv_name: MethodId,
+	#[cfg(feature = "collections")]
+	pub(crate) get_properties: MethodId,
+	pub(crate) get_property: MethodId,
+	pub(crate) set_property: MethodId,
+	pub(crate) gc: MethodId,
+}
+impl RichJavaType for JSystem {
+	type IDs = SystemIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static SystemIDs {
+		static IDS: OnceCell<SystemIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JSystem::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(SystemIDs {
+				#[cfg(feature = "collections")]
+				getenv: env.get_static_method_id(raw, "getenv", "()Ljava/util/Map;")?,
+				getenv_name: env.get_static_method_id(raw, "getenv", "(Ljava/lang/String;)Ljava/lang/String;")?,
+				#[cfg(feature = "collections")]
+				get_properties: env.get_static_method_id(raw, "getProperties", "()Ljava/util/Properties;")?,
+				get_property: env.get_static_method_id(raw, "getProperty", "(Ljava/lang/String;)Ljava/lang/String;")?,
+				set_property: env.get_static_method_id(raw, "setProperty", "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;")?,
+				gc: env.get_static_method_id(raw, "gc", "()V")?,
+				class,
+			})
+		}).expect("unable to resolve java/lang/System descriptors");
+		ids
+	}
+}
+impl JavaClass for JSystem {
+	const INTERNAL_NAME: &'static str = "java/lang/System";
+}
+
+impl JSystem {
+	/// Returns the process environment, as seen by Java's `System.getenv()`
+	///
+	/// Contract: attached, reported
+	#[cfg(feature = "collections")]
+	pub fn env_vars(env: JniEnv<'_>) -> Result<HashMap<String, String>, JniError> {
+		let ids = JSystem::descriptors(env);
+		let map = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.getenv, &[])?
+			.ok_or(JniError::NullPointer("System.getenv"))?;
+		let res = string_map(env, map);
+		env.delete_local_ref(map)?;
+		res
+	}
+
+	/// Returns a single environment variable, as seen by Java's `System.getenv(String)`
+	///
+	/// Contract: attached, reported
+	pub fn env_var(env: JniEnv<'_>, name: &str) -> Result<Option<String>, JniError> {
+		let ids = JSystem::descriptors(env);
+		let name = env.new_string(name)?;
+		let value = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.getenv_name, &[js::jvalue { l: name.as_ptr() }]);
+		env.delete_local_ref(name)?;
+		env.take_string(value?)
+	}
+
+	/// Returns a snapshot of the JVM's system properties, including any defaults.
+	///
+	/// Properties with non-String keys or values are skipped.
+	///
+	/// Contract: attached, reported
+	#[cfg(feature = "collections")]
+	pub fn properties(env: JniEnv<'_>) -> Result<HashMap<String, String>, JniError> {
+		let ids = JSystem::descriptors(env);
+		let props = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_properties, &[])?
+			.ok_or(JniError::NullPointer("System.getProperties"))?;
+		let res = string_properties(env, props);
+		env.delete_local_ref(props)?;
+		res
+	}
+
+	/// Returns a single system property
+	///
+	/// Contract: attached, reported
+	pub fn property(env: JniEnv<'_>, name: &str) -> Result<Option<String>, JniError> {
+		let ids = JSystem::descriptors(env);
+		let name = env.new_string(name)?;
+		let value = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_property, &[js::jvalue { l: name.as_ptr() }]);
+		env.delete_local_ref(name)?;
+		env.take_string(value?)
+	}
+
+	/// Sets a system property, returning its previous value.
+	///
+	/// Contract: attached, reported
+	pub fn set_property(env: JniEnv<'_>, name: &str, value: &str) -> Result<Option<String>, JniError> {
+		let ids = JSystem::descriptors(env);
+		let name = env.new_string(name)?;
+		let value = env.new_string(value)?;
+		let previous = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.set_property, &[js::jvalue { l: name.as_ptr() }, js::jvalue { l: value.as_ptr() }]);
+		env.delete_local_ref(name)?;
+		env.delete_local_ref(value)?;
+		env.take_string(previous?)
+	}
+
+	/// Suggests that the JVM run the garbage collector, as `System.gc()` does.
+	///
+	/// Contract: attached, reported
+	pub fn gc(env: JniEnv<'_>) -> Result<(), JniError> {
+		let ids = JSystem::descriptors(env);
+		env.call_static_void_method(ids.class.as_raw_nonnull(), ids.gc, &[])
+	}
+}
+
+/// Runs the garbage collector until `done` returns true, giving up after about five seconds. Returns whether `done`
+/// ever returned true.
+#[cfg(test)]
+pub(crate) fn gc_until(env: JniEnv<'_>, mut done: impl FnMut() -> bool) -> bool {
+	for _ in 0..50 {
+		JSystem::gc(env).expect("error running gc");
+		if done() {
+			return true;
+		}
+		// collected objects may only be processed (such as by a Cleaner's thread) some time after the collection
+		std::thread::sleep(std::time::Duration::from_millis(100));
+	}
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jref::AutoObj;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use std::time::Duration;
+
+	use crate::lang::{JavaException, JObject, JSystem, JThread, JThrowable, ThreadState};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn system_properties_and_env() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			#[cfg(feature = "collections")]
+			let props = JSystem::properties(env).expect("error reading properties");
+			#[cfg(feature = "collections")]
+			assert!(props.contains_key("java.version"));
+
+			assert_eq!(None, JSystem::set_property(env, "yajnir.test", "ünïcødé").expect("error setting property"));
+			assert_eq!(Some("ünïcødé"), JSystem::property(env, "yajnir.test").expect("error reading property").as_deref());
+
+			#[cfg(feature = "collections")]
+			let vars = JSystem::env_vars(env).expect("error reading environment");
+			#[cfg(feature = "collections")]
+			assert_eq!(std::env::var("PATH").ok(), vars.get("PATH").cloned());
+			assert_eq!(std::env::var("PATH").ok(), JSystem::env_var(env, "PATH").expect("error reading env var"));
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn assertion_macros() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let a: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("same").expect("error creating string"));
+			let b: AutoObj<JObject> = AutoObj::from_raw(env, env.new_string("same").expect("error creating string"));
+			crate::assert_java_eq!(env, &a, &b);
+
+			let exc = crate::assert_throws!(env, "java/lang/RuntimeException", env.throw_illegal_state("state"));
+			assert_eq!("java.lang.IllegalStateException: state", JObject::to_string(env, &exc).expect("error calling toString"));
+			env.exception_check().expect("exception to be cleared");
+
+			assert!(!JavaException::describe_to_log(env, log::Level::Warn).expect("error describing exception"));
+			let exc = crate::assert_throws!(env, "java/io/IOException", env.throw_io("disk on fire"));
+			let trace = JThrowable::stack_trace(env, &exc).expect("error formatting stack trace");
+			assert!(trace.starts_with("java.io.IOException: disk on fire"), "unexpected trace: {}", trace);
+			env.throw_io("disk on fire").expect("error throwing");
+			assert!(JavaException::describe_to_log(env, log::Level::Warn).expect("error describing exception"));
+			env.exception_check().expect("exception to be cleared");
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn thread_accessors() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let thread = JThread::current(env).expect("error getting current thread");
+			assert_eq!(JThread::current_name(env).expect("error reading name"), JThread::name(env, &thread).expect("error reading name"));
+			assert!(JThread::id(env, &thread).expect("error reading id") > 0);
+			assert!(!JThread::is_daemon(env, &thread).expect("error reading daemon flag"));
+			assert_eq!(ThreadState::Runnable, JThread::state(env, &thread).expect("error reading state"));
+			assert!(!JThread::join(env, &thread, Some(Duration::ZERO)).expect("error polling thread"));
+
+			// joining itself would wait forever, unless interrupted
+			JThread::interrupt(env, &thread).expect("error interrupting thread");
+			crate::assert_throws!(env, "java/lang/InterruptedException", JThread::join(env, &thread, None));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -1,3 +1,29 @@
+//! Yet another JNI crate, wrapping the JNI and invocation APIs and common Java types.
+//!
+//! # Features
+//!
+//! Subsystems that not every user needs can be left out for a smaller build, such as on embedded or Android targets,
+//! with `default-features = false`. The minimal core (`jvm`, `env`, `jref`, `lang`, `array` and the other modules
+//! without a feature) is always compiled.
+//!
+//! | Feature | Default | Enables |
+//! |---|---|---|
+//! | `collections` | yes | [`util`]'s `java.util` wrappers, and [`JSystem::properties`](lang::JSystem::properties) and [`JSystem::env_vars`](lang::JSystem::env_vars) |
+//! | `diagnostics` | yes | [`debug`] reports and their JNI call history, [`panic_hook`] and [`introspect`] |
+//! | `nio` | yes | [`nio`], requires `collections` |
+//! | `security` | yes | [`security`], requires `collections` |
+//! | `zip` | yes | [`zip`], requires `collections` |
+//! | `experimental` | | `experimental` APIs, which may change in any release |
+//! | `pool` | | `pool`'s attached worker threads |
+//! | `invocation` | | `invocation`, loading the JVM library at runtime |
+//! | `serde` | | `Serialize`/`Deserialize` for [`VmOptions`](jvm::VmOptions), and `Serialize` for [`debug`] reports |
+//! | `bisect-options` | | `jvm::bisect_options` |
+//! | `android` | | `android` support |
+//! | `ndk-context` | | finding the Android app's VM through the `ndk-context` crate, implies `android` |
+//! | `jvm-test` | | `testing` and `#[jvm_test]` |
+//!
+//! Each feature on its own should build with `cargo check --no-default-features --features <feature>`.
+
 use std::fmt;
 use jtypes::InternalClassname;
 
@@ -10,13 +36,56 @@ extern crate rusty_fork;
 #[cfg(test)]
 extern crate jvm_link;
 
+// lets `#[jvm_test]`, which names this crate as `::yajnir`, be used in its own tests
+extern crate self as yajnir;
+
+/// Marks a test function that takes a `JniEnv`, running it attached to a JavaVM shared between tests. See
+/// [`testing`].
+#[cfg(feature = "jvm-test")]
+pub use yajnir_macros::jvm_test;
+
 use log;
 
 
 #[macro_use] mod macros;
 pub mod jvm;
-mod env;
-mod jref;
+pub mod env;
+#[cfg(feature = "experimental")]
+pub mod experimental;
+#[cfg(feature = "pool")]
+pub mod pool;
+#[cfg(feature = "invocation")]
+pub mod invocation;
+#[cfg(feature = "android")]
+pub mod android;
+#[cfg(feature = "jvm-test")]
+pub mod testing;
+pub mod array;
+pub mod buffer;
+pub mod cleaner;
+mod classfile;
+#[cfg(feature = "diagnostics")]
+pub mod debug;
+#[cfg(unix)]
+pub mod io;
+#[cfg(feature = "diagnostics")]
+pub mod introspect;
+pub mod jref;
+pub mod lang;
+pub mod locate;
+mod names;
+#[cfg(feature = "nio")]
+pub mod nio;
+#[cfg(feature = "diagnostics")]
+pub mod panic_hook;
+pub mod scope;
+#[cfg(feature = "security")]
+pub mod security;
+pub mod shutdown;
+#[cfg(feature = "collections")]
+pub mod util;
+#[cfg(feature = "zip")]
+pub mod zip;
 
 #[derive(Debug, PartialEq, Eq)]
 struct NativeEscapeError {
@@ -38,12 +107,18 @@ fn _native_name(class: InternalClassname, method: &str, overload_signature: Opti
 				'_' => result.push_str("_1"),
 				';' => result.push_str("_2"),
 				'[' => result.push_str("_3"),
-				c if c.is_numeric() && result.chars().last().map(|ch| ch == '_').unwrap_or(false) => {
+				c if c.is_ascii_digit() && result.chars().last().map(|ch| ch == '_').unwrap_or(false) => {
 					// if the char is a number and will follow an underscore
 					return None;
 				},
 				c if c.is_ascii_alphanumeric() => result.push(c),
-				c => result.push_str(&format!("_0{:04x}", c as u16)),
+				c => {
+					// Java strings are UTF-16, so characters outside the BMP are escaped as their surrogate pair
+					let mut units = [0u16; 2];
+					for unit in c.encode_utf16(&mut units) {
+						result.push_str(&format!("_0{:04x}", unit));
+					}
+				},
 			}
 		}
 		Some(result)
@@ -77,6 +152,116 @@ fn native_names() {
 	assert_eq!(Ok("Java_p_q_r_A_f__ILjava_lang_String_2"), _native_name(InternalClassname::new_unchecked("p/q/r/A"), "f", Some("ILjava/lang/String;")).as_ref().map(|s| s.as_str()));
 }
 
+#[test]
+fn native_names_non_bmp() {
+	// CJK Unified Ideographs Extension B, outside the BMP
+	assert_eq!(Ok("Java_p_A_f_0d840_0dc00"), _native_name(InternalClassname::new_unchecked("p/A"), "f\u{20000}", None).as_ref().map(|s| s.as_str()));
+	// BMP CJK ideograph, and an emoji
+	assert_eq!(Ok("Java_p__0732b_A_0d83d_0de00"), _native_name(InternalClassname::new_unchecked("p/\u{732b}"), "A\u{1f600}", None).as_ref().map(|s| s.as_str()));
+	// escapes ending in a digit do not count as a digit following an underscore
+	assert_eq!(Ok("Java_p_A_f_0d83d_0de003"), _native_name(InternalClassname::new_unchecked("p/A"), "f\u{1f600}3", None).as_ref().map(|s| s.as_str()));
+}
+
+#[test]
+fn native_names_match_reference() {
+	// javah-style reference: mangle each UTF-16 code unit independently
+	fn reference(s: &str) -> String {
+		s.encode_utf16().map(|unit| match unit {
+			0x2f => "_".to_owned(),
+			0x5f => "_1".to_owned(),
+			0x3b => "_2".to_owned(),
+			0x5b => "_3".to_owned(),
+			u if u < 0x80 && (u as u8).is_ascii_alphanumeric() => (u as u8 as char).to_string(),
+			u => format!("_0{:04x}", u),
+		}).collect()
+	}
+
+	const ALPHABET: &[char] = &['a', 'Z', '0', '7', '/', '_', ';', '[', '$', '\u{e9}', '\u{732b}', '\u{ffff}', '\u{10000}', '\u{1f600}', '\u{10ffff}'];
+
+	// deterministic xorshift, to keep the test reproducible without extra dependencies
+	let mut state: u32 = 0x9e3779b9;
+	let mut next = || {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state
+	};
+
+	for _ in 0..2000 {
+		let len = next() % 12;
+		let method: String = (0..len).map(|_| ALPHABET[next() as usize % ALPHABET.len()]).collect();
+		let expected = reference(&method);
+
+		match _native_name(InternalClassname::new_unchecked("p/A"), &method, None) {
+			Ok(mangled) => assert_eq!(format!("Java_p_A_{}", expected), mangled, "mangling {:?}", method),
+			// only rejected when a literal digit follows an unescaped underscore
+			Err(_) => assert!(method.contains("/0") || method.contains("/7"), "unexpectedly rejected {:?}", method),
+		}
+	}
+}
+
+/// Dependencies of the crate's exported macros, which are not part of its public API
+#[doc(hidden)]
+pub mod __macro_support {
+	pub use jni_sys::{jint, JavaVM};
+}
+
+/// Internal entry points exposed for the fuzz targets under `fuzz/`
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing {
+	use jtypes::InternalClassname;
+
+	pub fn native_name(class: &str, method: &str, overload_signature: Option<&str>) -> Option<String> {
+		crate::_native_name(InternalClassname::new_unchecked(class), method, overload_signature).ok()
+	}
+
+	pub fn java_cstr(s: &str) -> Vec<u8> {
+		crate::env::java_cstr(s)
+	}
+
+	pub fn split_options(value: &str) -> Result<Vec<String>, &'static str> {
+		crate::jvm::split_options(value)
+	}
+}
+
+/// A length that could not be converted between Rust's `usize` and JNI's `jsize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum SizeError {
+	#[error("length {} is too large to pass to JNI as a jsize", .0)]
+	TooLarge(usize),
+	#[error("JNI returned negative length {}", .0)]
+	Negative(jni_sys::jsize),
+	#[error("a zero length buffer can't hold any data")]
+	Empty,
+}
+
+/// Converts a Rust length to a `jsize`, which is 32-bit on all platforms.
+pub(crate) fn to_jsize(len: usize) -> Result<jni_sys::jsize, SizeError> {
+	use std::convert::TryFrom;
+	jni_sys::jsize::try_from(len).map_err(|_| SizeError::TooLarge(len))
+}
+
+/// Converts a `jsize` returned by JNI to a Rust length.
+pub(crate) fn from_jsize(len: jni_sys::jsize) -> Result<usize, SizeError> {
+	use std::convert::TryFrom;
+	usize::try_from(len).map_err(|_| SizeError::Negative(len))
+}
+
+#[test]
+fn jsize_conversions() {
+	assert_eq!(Ok(0), to_jsize(0));
+	assert_eq!(Ok(jni_sys::jsize::MAX), to_jsize(jni_sys::jsize::MAX as usize));
+	// fits in usize on both 32 and 64-bit targets
+	assert_eq!(Err(SizeError::TooLarge(1 << 31)), to_jsize(1 << 31));
+	assert_eq!(Err(SizeError::TooLarge(usize::MAX)), to_jsize(usize::MAX));
+
+	assert_eq!(Ok(jni_sys::jsize::MAX as usize), from_jsize(jni_sys::jsize::MAX));
+	assert_eq!(Err(SizeError::Negative(-1)), from_jsize(-1));
+	assert_eq!(Err(SizeError::Negative(jni_sys::jsize::MIN)), from_jsize(jni_sys::jsize::MIN));
+}
+
 /// Translates a Rust bool to a Java boolean
 pub(crate) fn r2j_bool(val: bool) -> jni_sys::jboolean {
 	if val {
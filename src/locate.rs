@@ -0,0 +1,251 @@
+//! Finding an installed JVM, for use with the `invocation` feature's runtime loading.
+//!
+//! ```no_run
+//! # #[cfg(feature = "invocation")]
+//! # fn example() -> Result<(), yajnir::invocation::LoadError> {
+//! if let Some(install) = yajnir::locate::find() {
+//!     yajnir::invocation::load(&install.libjvm)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Where a [`JavaInstall`] was found
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstallSource {
+	/// The `JAVA_HOME` environment variable
+	JavaHome,
+	/// The `HKEY_LOCAL_MACHINE\SOFTWARE\JavaSoft` registry keys, on Windows
+	Registry,
+	/// The output of `/usr/libexec/java_home`, on macOS
+	MacJavaHome,
+	/// A conventional install directory, such as `/usr/lib/jvm/*` on Linux
+	SystemPath,
+}
+
+/// A JVM installation with a loadable JVM library
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct JavaInstall {
+	/// The Java home directory, containing `lib` and `bin`
+	pub home: PathBuf,
+	/// The path of `libjvm.so`, `jvm.dll`, or `libjvm.dylib`
+	pub libjvm: PathBuf,
+	/// The `JAVA_VERSION` from the install's `release` file, such as `17.0.2` or `1.8.0_292`
+	pub version: Option<String>,
+	pub source: InstallSource,
+}
+
+impl JavaInstall {
+	/// Returns the feature release number, such as `17`, treating legacy `1.x` versions as `x`.
+	pub fn major_version(&self) -> Option<u32> {
+		let version = self.version.as_deref()?;
+		let mut parts = version.split(|c: char| !c.is_ascii_digit());
+		match parts.next()?.parse().ok()? {
+			1 => parts.next()?.parse().ok(),
+			major => Some(major),
+		}
+	}
+}
+
+// relative to the Java home, newer layouts first
+#[cfg(target_os = "windows")]
+const LIBJVM_PATHS: &[&str] = &["bin/server/jvm.dll", "bin/client/jvm.dll", "jre/bin/server/jvm.dll", "jre/bin/client/jvm.dll"];
+#[cfg(target_os = "macos")]
+const LIBJVM_PATHS: &[&str] = &[
+	"lib/server/libjvm.dylib", "jre/lib/server/libjvm.dylib",
+	// Apple's Java 6 bundles keep it beside `Contents/Home`
+	"../Libraries/libjvm.dylib",
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBJVM_PATHS: &[&str] = &[
+	"lib/server/libjvm.so", "lib/client/libjvm.so",
+	"jre/lib/amd64/server/libjvm.so", "jre/lib/aarch64/server/libjvm.so", "jre/lib/i386/client/libjvm.so",
+];
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const SYSTEM_DIRS: &[&str] = &["/usr/lib/jvm", "/usr/lib64/jvm", "/usr/java", "/opt/java"];
+
+/// Inspects a Java home directory, returning `None` if it does not contain a JVM library.
+pub fn from_home<P: AsRef<Path>>(home: P, source: InstallSource) -> Option<JavaInstall> {
+	let home = home.as_ref();
+	let libjvm = LIBJVM_PATHS.iter().map(|rel| home.join(rel)).find(|path| path.is_file())?;
+	Some(JavaInstall {
+		home: home.to_owned(),
+		libjvm,
+		version: release_version(home),
+		source,
+	})
+}
+
+/// Reads `JAVA_VERSION` from the `release` file shipped in every JDK and JRE since Java 8.
+fn release_version(home: &Path) -> Option<String> {
+	let release = std::fs::read_to_string(home.join("release")).ok()?;
+	release.lines()
+		.find_map(|line| line.strip_prefix("JAVA_VERSION="))
+		.map(|version| version.trim().trim_matches('"').to_owned())
+}
+
+/// Returns the first usable JVM, checking `JAVA_HOME` before any platform-specific locations.
+pub fn find() -> Option<JavaInstall> {
+	find_all().into_iter().next()
+}
+
+/// Returns every usable JVM that can be found, in the order [`find`] prefers them, without duplicates.
+///
+/// After `JAVA_HOME`, each platform's default JVM comes first, then the rest from newest to oldest.
+pub fn find_all() -> Vec<JavaInstall> {
+	let mut found: Vec<JavaInstall> = Vec::new();
+	let mut add = |install: JavaInstall| {
+		let canonical = std::fs::canonicalize(&install.libjvm).ok();
+		let duplicate = found.iter().any(|other| std::fs::canonicalize(&other.libjvm).ok() == canonical);
+		if !duplicate {
+			found.push(install);
+		}
+	};
+
+	if let Some(home) = std::env::var_os("JAVA_HOME").filter(|home| !home.is_empty()) {
+		if let Some(install) = from_home(home, InstallSource::JavaHome) {
+			add(install);
+		}
+	}
+	for install in platform_installs() {
+		add(install);
+	}
+	found
+}
+
+/// Inspects each home, ordering the usable ones from the newest feature release to the oldest, and those without a
+/// known version last.
+fn newest_first<I: IntoIterator<Item = PathBuf>>(homes: I, source: InstallSource) -> Vec<JavaInstall> {
+	let mut installs: Vec<JavaInstall> = homes.into_iter().filter_map(|home| from_home(home, source)).collect();
+	installs.sort_by_key(|install| std::cmp::Reverse(install.major_version()));
+	installs
+}
+
+/// The subdirectories of `dir`, or nothing if it can't be read.
+#[cfg(not(target_os = "windows"))]
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+	match std::fs::read_dir(dir) {
+		Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|path| path.is_dir()).collect(),
+		Err(_) => Vec::new(),
+	}
+}
+
+#[cfg(target_os = "windows")]
+fn platform_installs() -> Vec<JavaInstall> {
+	// `reg` ships with every supported Windows version, and avoids binding the registry API for one lookup
+	let mut homes = Vec::new();
+	for key in [r"HKLM\SOFTWARE\JavaSoft\JDK", r"HKLM\SOFTWARE\JavaSoft\Java Runtime Environment", r"HKLM\SOFTWARE\JavaSoft\Java Development Kit"] {
+		let output = match std::process::Command::new("reg").args(["query", key, "/s", "/v", "JavaHome"]).output() {
+			Ok(output) if output.status.success() => output,
+			_ => continue,
+		};
+		// value lines look like `    JavaHome    REG_SZ    C:\Program Files\Java\jdk-17`
+		for line in String::from_utf8_lossy(&output.stdout).lines() {
+			if let Some((_, home)) = line.trim().split_once("REG_SZ") {
+				homes.push(PathBuf::from(home.trim()));
+			}
+		}
+	}
+	newest_first(homes, InstallSource::Registry)
+}
+
+/// Directories holding one `*.jdk` bundle per JVM: user installs, then Apple's legacy Java 6
+#[cfg(target_os = "macos")]
+const BUNDLE_DIRS: &[&str] = &["/Library/Java/JavaVirtualMachines", "/System/Library/Java/JavaVirtualMachines"];
+
+#[cfg(target_os = "macos")]
+fn platform_installs() -> Vec<JavaInstall> {
+	let mut installs = Vec::new();
+	// the user's selected JVM
+	if let Ok(output) = std::process::Command::new("/usr/libexec/java_home").output() {
+		if output.status.success() {
+			let home = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+			installs.extend(from_home(home, InstallSource::MacJavaHome));
+		}
+	}
+
+	let bundles = BUNDLE_DIRS.iter().flat_map(|dir| subdirectories(Path::new(dir)));
+	installs.extend(newest_first(bundles.map(|bundle| bundle.join("Contents/Home")), InstallSource::SystemPath));
+	// the legacy JavaVM.framework links to the current Apple JVM
+	installs.extend(from_home("/System/Library/Frameworks/JavaVM.framework/Versions/CurrentJDK/Home", InstallSource::SystemPath));
+	installs
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_installs() -> Vec<JavaInstall> {
+	let mut installs = Vec::new();
+	// Debian and Ubuntu link the JVM selected with update-java-alternatives here
+	installs.extend(from_home("/usr/lib/jvm/default-java", InstallSource::SystemPath));
+	let homes = SYSTEM_DIRS.iter().flat_map(|dir| subdirectories(Path::new(dir)));
+	installs.extend(newest_first(homes, InstallSource::SystemPath));
+	installs
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::locate::{self, InstallSource, JavaInstall, LIBJVM_PATHS};
+
+	#[test]
+	fn inspect_home() {
+		let home = std::env::temp_dir().join(format!("yajnir-locate-{}", std::process::id()));
+		let libjvm = home.join(LIBJVM_PATHS[0]);
+		std::fs::create_dir_all(libjvm.parent().unwrap()).expect("error creating fake home");
+		std::fs::write(&libjvm, b"").expect("error creating fake libjvm");
+		std::fs::write(home.join("release"), "IMPLEMENTOR=\"Test\"\nJAVA_VERSION=\"1.8.0_292\"\n").expect("error writing release");
+
+		let install = locate::from_home(&home, InstallSource::JavaHome).expect("fake home to be usable");
+		assert_eq!(libjvm, install.libjvm);
+		assert_eq!(Some("1.8.0_292"), install.version.as_deref());
+		assert_eq!(Some(8), install.major_version());
+
+		assert_eq!(None, locate::from_home(home.join("lib"), InstallSource::JavaHome));
+		std::fs::remove_dir_all(&home).expect("error removing fake home");
+	}
+
+	#[test]
+	fn major_versions() {
+		let install = |version: &str| JavaInstall {
+			home: Default::default(),
+			libjvm: Default::default(),
+			version: Some(version.to_owned()),
+			source: InstallSource::SystemPath,
+		};
+		assert_eq!(Some(17), install("17.0.2").major_version());
+		assert_eq!(Some(21), install("21").major_version());
+		assert_eq!(Some(11), install("11-ea").major_version());
+		assert_eq!(Some(7), install("1.7.0").major_version());
+	}
+
+	#[test]
+	fn newest_first() {
+		let root = std::env::temp_dir().join(format!("yajnir-locate-order-{}", std::process::id()));
+		let fake_home = |name: &str, version: Option<&str>| {
+			let home = root.join(name);
+			let libjvm = home.join(LIBJVM_PATHS[0]);
+			std::fs::create_dir_all(libjvm.parent().unwrap()).expect("error creating fake home");
+			std::fs::write(&libjvm, b"").expect("error creating fake libjvm");
+			if let Some(version) = version {
+				std::fs::write(home.join("release"), format!("JAVA_VERSION=\"{}\"\n", version)).expect("error writing release");
+			}
+			home
+		};
+		// sorting these names would put the oldest first
+		let homes = vec![
+			fake_home("java-11-openjdk", Some("11.0.20")),
+			fake_home("java-unknown", None),
+			fake_home("java-17-openjdk", Some("17.0.8")),
+			fake_home("java-8-openjdk", Some("1.8.0_382")),
+			root.join("not-a-jvm"),
+		];
+
+		let installs = locate::newest_first(homes, InstallSource::SystemPath);
+		let majors: Vec<_> = installs.iter().map(JavaInstall::major_version).collect();
+		assert_eq!(majors, [Some(17), Some(11), Some(8), None]);
+		std::fs::remove_dir_all(&root).expect("error removing fake homes");
+	}
+}
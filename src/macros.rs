@@ -5,6 +5,8 @@
 macro_rules! java_vm_unchecked {
     ( $jvm:expr, $name:tt $(, $args:expr )* ) => ({
         log::trace!(concat!("calling unchecked JavaVM method: ", stringify!($name)));
+		#[cfg(feature = "diagnostics")]
+		crate::debug::record_call(stringify!($name));
 		let jvm: JavaVM = $jvm;
 
         // SAFETY: JavaVM is always assumed to be a non-null, valid pointer to a JavaVM struct
@@ -35,3 +37,141 @@ macro_rules! java_vm_method {
         }
     }};
 }
+
+// Modeled after the JavaVM macros above
+macro_rules! jni_unchecked {
+	( $env:expr, $name:tt $(, $args:expr )* ) => ({
+		log::trace!(concat!("calling unchecked JNIEnv method: ", stringify!($name)));
+		#[cfg(feature = "diagnostics")]
+		crate::debug::record_call(stringify!($name));
+		let env: crate::env::JniEnv = $env;
+
+		// SAFETY: JniEnv is always assumed to be a non-null, valid pointer to a JNIEnv struct for the current thread.
+		//         Each function pointer is checked for null (Option as None) before use, returning an Err if so.
+		unsafe { jni_method!(env, $name)(env.ptr.as_ptr(), $($args),*) }
+	})
+}
+
+// Modeled after the JavaVM macros above
+macro_rules! jni_method {
+	( $env:expr, $name:tt ) => {{
+		log::trace!(concat!("looking up JNIEnv method ", stringify!($name)));
+		let env: crate::env::JniEnv = $env;
+
+		match (**env.ptr.as_ptr()).$name {
+			Some(meth) => meth,
+			None => {
+				log::trace!(concat!("JNIEnv method ", stringify!($name), " not defined, returning error"));
+				return Err(crate::env::JniError::MissingFunction(stringify!($name)));
+			}
+		}
+	}};
+}
+
+/// Asserts that two Java objects are equal according to `a.equals(b)`, printing both with `toString()` if not.
+///
+/// ```no_run
+/// # use yajnir::{assert_java_eq, env::JniEnv, jref::AutoObj, lang::JObject};
+/// # fn example(env: JniEnv<'_>, a: AutoObj<'_, JObject>, b: AutoObj<'_, JObject>) {
+/// assert_java_eq!(env, &a, &b);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_java_eq {
+	( $env:expr, $a:expr, $b:expr $(,)? ) => {{
+		let env: $crate::env::JniEnv = $env;
+		let (a, b) = ($a, $b);
+		if !$crate::lang::JObject::equals(env, a, b).expect("error calling Object.equals") {
+			panic!(
+				"assertion failed: `a.equals(b)`\n  a: `{}`\n  b: `{}`",
+				$crate::lang::JObject::to_string(env, a).expect("error calling Object.toString"),
+				$crate::lang::JObject::to_string(env, b).expect("error calling Object.toString"),
+			);
+		}
+	}};
+}
+
+/// Evaluates an expression and asserts that it left a pending exception of the given class, which is then cleared.
+///
+/// The class is given by its internal name, and subclasses also match. Evaluates to the caught exception.
+///
+/// ```no_run
+/// # use yajnir::{assert_throws, env::JniEnv};
+/// # fn example(env: JniEnv<'_>) {
+/// assert_throws!(env, "java/lang/IllegalArgumentException", env.throw_illegal_argument("bad"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_throws {
+	( $env:expr, $class:expr, $e:expr $(,)? ) => {{
+		let env: $crate::env::JniEnv = $env;
+		let _ = $e;
+		match env.catch_exception($class).expect("error checking for exception") {
+			Some(exc) => exc,
+			None => match env.exception_occurred().expect("error checking for exception") {
+				Some(other) => {
+					env.exception_clear().expect("error clearing exception");
+					panic!(
+						"assertion failed: expected `{}` to throw {}, but it threw `{}`",
+						stringify!($e), $class,
+						$crate::lang::JObject::to_string(env, &other).expect("error calling Throwable.toString"),
+					)
+				},
+				None => panic!("assertion failed: expected `{}` to throw {}, but nothing was thrown", stringify!($e), $class),
+			},
+		}
+	}};
+}
+
+/// Exports the `JNI_OnLoad` function the JVM calls when it loads this library (through `System.loadLibrary`), and
+/// optionally `JNI_OnUnload`.
+///
+/// `JNI_OnLoad` makes the JavaVM available through [`jvm::global`](crate::jvm::global), checks that it supports the
+/// given JNI version, then runs the initialization block, which may use `?` on any error type. Errors and panics are
+/// logged and fail the library load with an `UnsatisfiedLinkError`, instead of unwinding into the JVM.
+///
+/// ```no_run
+/// yajnir::on_load! {
+///     version: V1_8,
+///     |vm, env| {
+///         env.exception_check()?;
+///         log::info!("loaded into {:?}", vm);
+///     },
+///     on_unload: |_vm, _env| {
+///         log::info!("unloading");
+///     },
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Only one crate in a library may use this, as the exported names must be unique.
+#[macro_export]
+macro_rules! on_load {
+	(
+		version: $version:ident,
+		| $vm:pat_param, $env:pat_param | $init:block
+		$(, on_unload: | $uvm:pat_param, $uenv:pat_param | $fini:block )?
+		$(,)?
+	) => {
+		#[no_mangle]
+		pub unsafe extern "system" fn JNI_OnLoad(
+			vm: *mut $crate::__macro_support::JavaVM,
+			_reserved: *mut ::std::ffi::c_void,
+		) -> $crate::__macro_support::jint {
+			$crate::jvm::on_load(vm, $crate::jvm::JniVersion::$version, |$vm, $env| -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+				$init;
+				::std::result::Result::Ok(())
+			})
+		}
+
+		$(
+			#[no_mangle]
+			pub unsafe extern "system" fn JNI_OnUnload(
+				vm: *mut $crate::__macro_support::JavaVM,
+				_reserved: *mut ::std::ffi::c_void,
+			) {
+				$crate::jvm::on_unload(vm, |$uvm, $uenv| $fini)
+			}
+		)?
+	};
+}
@@ -0,0 +1,140 @@
+//! Human-readable names for method IDs, so diagnostics can show `java.lang.Thread#getName()Ljava/lang/String;`
+//! instead of a pointer.
+//!
+//! JNI can't name a method ID after the fact, so names are recorded as IDs are looked up. The class is named by
+//! whichever `FindClass` call produced the reference the ID was looked up through: pinned class references are
+//! remembered for the life of the process, and local ones until they are deleted.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+
+use crate::env::MethodId;
+use crate::jref::RawJObject;
+
+/// How many undeleted local class references each thread remembers names for
+const LOCAL_CLASSES: usize = 32;
+
+/// The method that a [`MethodId`] was looked up as
+#[derive(Debug, Clone)]
+pub(crate) struct MethodName {
+	/// The class's internal name, or `None` if it was looked up through a class reference of unknown origin
+	class: Option<Arc<str>>,
+	name: Box<str>,
+	sig: Box<str>,
+}
+
+impl fmt::Display for MethodName {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.class {
+			Some(class) => {
+				for (i, part) in class.split('/').enumerate() {
+					if i > 0 {
+						f.write_str(".")?;
+					}
+					f.write_str(part)?;
+				}
+			},
+			None => f.write_str("?")?,
+		}
+		write!(f, "#{}{}", self.name, self.sig)
+	}
+}
+
+#[derive(Default)]
+struct Names {
+	/// Pinned global class references, by address
+	classes: HashMap<usize, Arc<str>>,
+	methods: HashMap<usize, MethodName>,
+}
+
+static NAMES: Lazy<RwLock<Names>> = Lazy::new(Default::default);
+
+thread_local! {
+	/// Local class references from `FindClass` on this thread, most recent last
+	static LOCALS: RefCell<Vec<(usize, Arc<str>)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn names() -> std::sync::RwLockReadGuard<'static, Names> {
+	NAMES.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn names_mut() -> std::sync::RwLockWriteGuard<'static, Names> {
+	NAMES.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records the name of a local class reference returned by `FindClass`.
+pub(crate) fn local_class_found(class: RawJObject, name: &str) {
+	let _ = LOCALS.try_with(|locals| {
+		let mut locals = locals.borrow_mut();
+		if locals.len() == LOCAL_CLASSES {
+			locals.remove(0);
+		}
+		locals.push((class.as_ptr() as usize, Arc::from(name)));
+	});
+}
+
+/// Forgets a deleted local reference's name, as its address may be reused for another object.
+pub(crate) fn local_deleted(obj: RawJObject) {
+	let _ = LOCALS.try_with(|locals| {
+		let mut locals = locals.borrow_mut();
+		if !locals.is_empty() {
+			locals.retain(|(addr, _)| *addr != obj.as_ptr() as usize);
+		}
+	});
+}
+
+/// Records the name of a global class reference that is never deleted.
+pub(crate) fn class_pinned(class: RawJObject, name: &str) {
+	names_mut().classes.insert(class.as_ptr() as usize, Arc::from(name));
+}
+
+/// Records the name of a method ID looked up through `class`.
+pub(crate) fn method_found(class: RawJObject, method: MethodId, name: &str, sig: &str) {
+	let addr = class.as_ptr() as usize;
+	let local = LOCALS.try_with(|locals| {
+		locals.borrow().iter().rev().find(|(a, _)| *a == addr).map(|(_, name)| Arc::clone(name))
+	}).ok().flatten();
+	let mut names = names_mut();
+	let class = local.or_else(|| names.classes.get(&addr).cloned());
+	names.methods.entry(method.as_raw() as usize).or_insert_with(|| MethodName {
+		class,
+		name: Box::from(name),
+		sig: Box::from(sig),
+	});
+}
+
+/// Returns the name `method` was looked up as, if it was looked up through this crate.
+pub(crate) fn method_name(method: MethodId) -> Option<MethodName> {
+	names().methods.get(&(method.as_raw() as usize)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jref::RichJavaType;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JThread;
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn names_methods() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			// through a pinned class reference
+			let ids = JThread::descriptors(env);
+			assert_eq!("java.lang.Thread#getName()Ljava/lang/String;", ids.get_name.to_string());
+
+			// through a local class reference
+			let class = env.find_class("java/lang/Runnable").expect("error finding class");
+			let run = env.get_method_id(class, "run", "()V").expect("error finding method");
+			env.delete_local_ref(class).expect("error deleting local ref");
+			assert_eq!("java.lang.Runnable#run()V", run.to_string());
+			assert_eq!("MethodId(java.lang.Runnable#run()V)", format!("{:?}", run));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -0,0 +1,91 @@
+//! Descriptors for `java.nio` types.
+
+
+use once_cell::sync::OnceCell;
+
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{GlobalRef, JavaClass, RichJavaType};
+use crate::lang::{class_ref, JClass};
+use crate::util::string_keys;
+
+/// `java.nio.charset.Charset`
+#[derive(Debug)]
+pub struct JCharset;
+#[derive(Debug)]
+pub struct CharsetIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) default_charset: MethodId,
+	pub(crate) available_charsets: MethodId,
+	pub(crate) name: MethodId,
+}
+impl RichJavaType for JCharset {
+	type IDs = CharsetIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static CharsetIDs {
+		static IDS: OnceCell<CharsetIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JCharset::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(CharsetIDs {
+				default_charset: env.get_static_method_id(raw, "defaultCharset", "()Ljava/nio/charset/Charset;")?,
+				available_charsets: env.get_static_method_id(raw, "availableCharsets", "()Ljava/util/SortedMap;")?,
+				name: env.get_method_id(raw, "name", "()Ljava/lang/String;")?,
+				class,
+			})
+		}).expect("unable to resolve java/nio/charset/Charset descriptors");
+		ids
+	}
+}
+impl JavaClass for JCharset {
+	const INTERNAL_NAME: &'static str = "java/nio/charset/Charset";
+}
+
+impl JCharset {
+	/// Returns the canonical name of the JVM's default charset, such as `UTF-8`.
+	///
+	/// This is what Java uses when converting bytes and strings without an explicit charset, and may differ from
+	/// the host's locale. It can be set with [`VmOptions::set_default_charset`](crate::jvm::VmOptions::set_default_charset).
+	pub fn default_name(env: JniEnv<'_>) -> Result<String, JniError> {
+		let ids = JCharset::descriptors(env);
+		let charset = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.default_charset, &[])?
+			.ok_or(JniError::NullPointer("Charset.defaultCharset"))?;
+		let name = env.call_object_method(charset, ids.name, &[]);
+		env.delete_local_ref(charset)?;
+		env.take_string(name?)?.ok_or(JniError::NullPointer("Charset.name"))
+	}
+
+	/// Returns the canonical names of every charset supported by the JVM, sorted.
+	pub fn available_names(env: JniEnv<'_>) -> Result<Vec<String>, JniError> {
+		let ids = JCharset::descriptors(env);
+		let charsets = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.available_charsets, &[])?
+			.ok_or(JniError::NullPointer("Charset.availableCharsets"))?;
+		let res = string_keys(env, charsets);
+		env.delete_local_ref(charsets)?;
+		res
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::nio::JCharset;
+	use crate::util::{JLocale, JTimeZone};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn locale_presets() {
+			let mut options = VmOptions::new(JniVersion::V10);
+			options.set_default_charset("ISO-8859-1");
+			options.set_default_locale("fr", Some("CA"));
+			options.set_default_time_zone("Asia/Tokyo");
+			let (vm, env) = JavaVM::create(options).expect("error creating vm");
+
+			assert_eq!("ISO-8859-1", JCharset::default_name(env).expect("error reading charset"));
+			assert!(JCharset::available_names(env).expect("error listing charsets").iter().any(|name| name == "UTF-8"));
+			assert_eq!("fr-CA", JLocale::default_tag(env).expect("error reading locale"));
+			assert_eq!("Asia/Tokyo", JTimeZone::default_id(env).expect("error reading time zone"));
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
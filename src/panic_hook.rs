@@ -0,0 +1,100 @@
+//! An opt-in panic hook that reports the JNI state of the panicking thread.
+//!
+//! Panics on threads that mix Rust and Java frames are hard to place from the Rust backtrace alone. Once
+//! [`install`]ed, every panic message is followed by a note such as:
+//!
+//! ```text
+//! note: JNI context: Java thread "worker-3" (attached), last JNI call: CallObjectMethodA
+//! ```
+
+use std::sync::Once;
+
+use crate::env::{JniEnv, JniError};
+use crate::jref::JavaClass;
+use crate::jvm::JavaVM;
+use crate::lang::JThread;
+
+/// Installs the panic hook, wrapping whichever hook was installed before it. Calling this more than once has no
+/// further effect.
+pub fn install() {
+	static INSTALLED: Once = Once::new();
+	INSTALLED.call_once(|| {
+		let previous = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			previous(info);
+			eprintln!("note: JNI context: {}", describe());
+		}));
+	});
+}
+
+/// Describes the current thread's JNI state. This must not panic, as it runs within the panic hook.
+fn describe() -> String {
+	let last = crate::debug::recent_calls().pop();
+	// describing the thread makes JNI calls of its own
+	let thread = crate::debug::untraced(describe_thread);
+
+	format!("{}, last JNI call: {}", thread, last.unwrap_or("none"))
+}
+
+fn describe_thread() -> String {
+	let vm = match JavaVM::created_jvms() {
+		Ok(vms) => match vms.first() {
+			Some(vm) => *vm,
+			None => return String::from("no JavaVM"),
+		},
+		Err(e) => return format!("no JavaVM ({})", e),
+	};
+	let env = match vm.get_env() {
+		Ok(Some(env)) => JniEnv { ptr: env, _phantom: std::marker::PhantomData },
+		Ok(None) => return String::from("thread not attached"),
+		Err(e) => return format!("thread attachment unknown ({})", e),
+	};
+	// Java can't be called into while an exception is pending
+	if env.exception_check().is_err() {
+		return String::from("Java thread with a pending exception (attached)");
+	}
+	match java_thread_name(env) {
+		Ok(name) => format!("Java thread {:?} (attached)", name),
+		Err(e) => format!("Java thread with unknown name (attached, {})", e),
+	}
+}
+
+/// Looks up the thread's name without the cached descriptors, which the panicking code may be initializing.
+fn java_thread_name(env: JniEnv<'_>) -> Result<String, JniError> {
+	let class = env.find_class(JThread::INTERNAL_NAME)?;
+	let res = (|| -> Result<String, JniError> {
+		let current_thread = env.get_static_method_id(class, "currentThread", "()Ljava/lang/Thread;")?;
+		let get_name = env.get_method_id(class, "getName", "()Ljava/lang/String;")?;
+		let thread = env.call_static_object_method(class, current_thread, &[])?
+			.ok_or(JniError::NullPointer("Thread.currentThread"))?;
+		let name = env.call_object_method(thread, get_name, &[]);
+		env.delete_local_ref(thread)?;
+		env.take_string(name?)?.ok_or(JniError::NullPointer("Thread.getName"))
+	})();
+	env.delete_local_ref(class)?;
+	res
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JSystem;
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn describes_jni_context() {
+			assert_eq!("no JavaVM, last JNI call: none", super::describe());
+
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			JSystem::property(env, "java.version").expect("error reading property");
+			env.exception_check().expect("no exception to be pending");
+			assert_eq!("Java thread \"main\" (attached), last JNI call: ExceptionCheck", super::describe());
+
+			std::thread::spawn(move || {
+				assert_eq!("thread not attached, last JNI call: none", super::describe());
+			}).join().expect("worker thread panicked");
+
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
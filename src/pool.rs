@@ -0,0 +1,168 @@
+//! A thread pool whose workers attach to the JavaVM once, rather than around every task.
+//!
+//! Only compiled with the `pool` feature enabled.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::env::JniEnv;
+use crate::jvm::JavaVM;
+use crate::lang::JavaException;
+
+type Job = Box<dyn FnOnce(JniEnv<'_>) + Send>;
+
+/// A fixed-size pool of threads, each permanently attached to a JavaVM, that run tasks given a JniEnv.
+///
+/// Dropping the pool waits for queued tasks to finish, then detaches and joins its workers.
+///
+/// ```no_run
+/// # use yajnir::{jvm::JavaVM, pool::AttachedPool};
+/// # fn example(vm: JavaVM) -> std::io::Result<()> {
+/// let pool = AttachedPool::new(vm, 4)?;
+/// pool.spawn(|env| {
+///     let version = yajnir::lang::JSystem::property(env, "java.version");
+///     log::info!("running on java {:?}", version);
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AttachedPool {
+	sender: Option<mpsc::Sender<Job>>,
+	workers: Vec<JoinHandle<()>>,
+}
+
+impl AttachedPool {
+	/// Starts `threads` workers, named `yajnir-pool-N`, attached to `vm`.
+	///
+	/// Waits for each worker to attach. Workers that fail to attach are logged and exit, and the pool runs with the
+	/// rest. Returns an error if `threads` is zero or no worker could attach.
+	pub fn new(vm: JavaVM, threads: usize) -> std::io::Result<AttachedPool> {
+		if threads == 0 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "an AttachedPool needs at least one worker"));
+		}
+		let (sender, receiver) = mpsc::channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		let (attached_tx, attached_rx) = mpsc::channel::<bool>();
+
+		let mut pool = AttachedPool { sender: Some(sender), workers: Vec::with_capacity(threads) };
+		for i in 0..threads {
+			let receiver = Arc::clone(&receiver);
+			let attached_tx = attached_tx.clone();
+			let worker = std::thread::Builder::new()
+				.name(format!("yajnir-pool-{}", i))
+				.spawn(move || AttachedPool::work(vm, &receiver, attached_tx))?;
+			pool.workers.push(worker);
+		}
+		drop(attached_tx);
+
+		// each worker reports once, or drops its sender if it panics first
+		if !attached_rx.iter().any(|attached| attached) {
+			return Err(std::io::Error::other("no pool worker could attach to the JavaVM"));
+		}
+		Ok(pool)
+	}
+
+	/// Queues `task` to run on the next idle worker.
+	///
+	/// A panicking task is logged and does not take its worker down, and any exception it leaves pending is logged
+	/// and cleared. Returns an error if every worker has exited, in which case `task` is dropped without running.
+	pub fn spawn<F: FnOnce(JniEnv<'_>) + Send + 'static>(&self, task: F) -> std::io::Result<()> {
+		self.sender.as_ref()
+			.expect("sender to exist until the pool is dropped")
+			.send(Box::new(task))
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "every pool worker has exited"))
+	}
+
+	fn work(vm: JavaVM, receiver: &Mutex<mpsc::Receiver<Job>>, attached: mpsc::Sender<bool>) {
+		let env = match vm.attach_permanently() {
+			Ok(env) => env,
+			Err(e) => {
+				log::error!("unable to attach pool worker to JavaVM: {}", e);
+				let _ = attached.send(false);
+				return;
+			},
+		};
+		let _ = attached.send(true);
+		drop(attached);
+
+		loop {
+			// only hold the lock while waiting, so other workers can pick up tasks while this one runs
+			let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+			let job = match job {
+				Ok(job) => job,
+				Err(mpsc::RecvError) => return,
+			};
+
+			if std::panic::catch_unwind(AssertUnwindSafe(|| job(env))).is_err() {
+				log::error!("pool task panicked");
+			}
+			if let Err(e) = JavaException::describe_to_log(env, log::Level::Warn) {
+				log::error!("unable to check for exceptions left by pool task: {}", e);
+			}
+		}
+	}
+}
+
+impl Drop for AttachedPool {
+	fn drop(&mut self) {
+		// closing the channel lets each worker finish the queue and exit, detaching it
+		drop(self.sender.take());
+		for worker in self.workers.drain(..) {
+			if worker.join().is_err() {
+				log::error!("pool worker panicked");
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc;
+
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::{JSystem, JThread};
+	use crate::pool::AttachedPool;
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn tasks_run_on_attached_workers() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let pool = AttachedPool::new(vm, 2).expect("error starting pool");
+			let (tx, rx) = mpsc::channel();
+			for _ in 0..4 {
+				let tx = tx.clone();
+				pool.spawn(move |env| {
+					let name = JThread::current_name(env).expect("error reading thread name");
+					tx.send(name).expect("error reporting result");
+				}).expect("error queueing task");
+			}
+			pool.spawn(|_env| panic!("task failure")).expect("error queueing task");
+			pool.spawn(|env| { JSystem::property(env, "").ok(); }).expect("error queueing task");
+			drop(tx);
+
+			let names: Vec<String> = rx.iter().collect();
+			assert_eq!(4, names.len());
+			assert!(names.iter().all(|n| n.starts_with("yajnir-pool-")));
+
+			// workers detach as they exit, otherwise destroying the VM would hang
+			drop(pool);
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn needs_an_attached_worker() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+			let err = AttachedPool::new(vm, 0).expect_err("pool without workers to be rejected");
+			assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+			vm.destroy().expect("error destroying vm");
+			// workers can't attach to a destroyed VM
+			let err = AttachedPool::new(vm, 2).expect_err("pool without attached workers to be rejected");
+			assert_eq!(std::io::ErrorKind::Other, err.kind());
+		}
+	}
+}
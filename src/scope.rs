@@ -0,0 +1,86 @@
+//! Offloading pure-Rust work from a JNI thread onto scoped worker threads.
+//!
+//! A [`JniEnv`] is only valid on its own thread. It is neither `Send` nor `Sync`, so the compiler already rejects
+//! worker closures that capture it:
+//!
+//! ```compile_fail
+//! # use yajnir::env::JniEnv;
+//! # fn example(env: JniEnv<'_>) {
+//! yajnir::scope::scope(env, |workers| {
+//!     workers.spawn(|| env.exception_check().is_ok());
+//! }, |_env, _ok| Ok(()));
+//! # }
+//! ```
+//!
+//! [`scope`] packages the pattern that follows from this: workers compute plain Rust values and send them back over
+//! a channel, and the JNI thread turns each result into Java objects as it arrives.
+
+use std::sync::mpsc;
+
+use crate::env::{JniEnv, JniError};
+
+/// Spawns worker threads within [`scope`], sending each one's result back to the JNI thread.
+#[derive(Debug)]
+pub struct Workers<'scope, 'data, R> {
+	scope: &'scope std::thread::Scope<'scope, 'data>,
+	results: mpsc::Sender<R>,
+}
+
+impl<'scope, 'data, R: Send + 'scope> Workers<'scope, 'data, R> {
+	/// Runs `work` on a new thread, which may borrow data that outlives the scope but never the JniEnv.
+	pub fn spawn<F: FnOnce() -> R + Send + 'scope>(&self, work: F) {
+		let results = self.results.clone();
+		self.scope.spawn(move || {
+			// the JNI thread stops receiving if a handler fails, in which case the result is discarded
+			let _ = results.send(work());
+		});
+	}
+}
+
+/// Runs `spawn` to start workers on scoped threads, then calls `handle` on the current thread with each result, in
+/// the order they finish.
+///
+/// Returns once every worker has finished, or with the first error from `handle` (after waiting for the remaining
+/// workers). A panic in a worker is resumed on the current thread once all workers have finished.
+pub fn scope<'env, 'data, R, S, H>(env: JniEnv<'env>, spawn: S, mut handle: H) -> Result<(), JniError>
+	where R: Send, S: for<'scope> FnOnce(&Workers<'scope, 'data, R>), H: FnMut(JniEnv<'env>, R) -> Result<(), JniError>
+{
+	std::thread::scope(|scope| {
+		let (results, received) = mpsc::channel();
+		spawn(&Workers { scope, results });
+
+		// every sender has been dropped or moved into a worker, so this ends when the last worker does
+		for result in received {
+			handle(env, result)?;
+		}
+		Ok(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn results_return_to_jni_thread() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let inputs = vec![1u64, 2, 3, 4];
+			let mut strings = Vec::new();
+			crate::scope::scope(env, |workers| {
+				for n in &inputs {
+					workers.spawn(move || n * n);
+				}
+			}, |env, square| {
+				let s = env.new_string(&square.to_string())?;
+				strings.push(env.take_string(Some(s))?.expect("string to be non-null"));
+				Ok(())
+			}).expect("error handling results");
+
+			strings.sort();
+			assert_eq!(vec!["1", "16", "4", "9"], strings);
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
@@ -0,0 +1,325 @@
+//! Helpers for sharing certificates and trust material between Rust and `java.security`.
+//!
+//! Certificates cross the boundary DER-encoded, which most Rust TLS stacks accept directly.
+
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::array::JPrimitiveArray;
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, JavaClass, RawJObject, RichJavaType};
+use crate::lang::{class_ref, JClass};
+use crate::util::JEnumeration;
+
+/// Calls `Certificate.getEncoded()` and copies the DER bytes out.
+fn encoded(env: JniEnv<'_>, get_encoded: MethodId, cert: RawJObject) -> Result<Vec<u8>, JniError> {
+	let der = env.call_object_method(cert, get_encoded, &[])?
+		.ok_or(JniError::NullPointer("Certificate.getEncoded"))?;
+	let der: AutoObj<JPrimitiveArray<js::jbyte>> = AutoObj::from_raw(env, der);
+	let res = JPrimitiveArray::to_bytes(env, &der);
+	drop(der);
+	res
+}
+
+/// `java.security.KeyStore`
+#[derive(Debug)]
+pub struct JKeyStore;
+#[derive(Debug)]
+pub struct KeyStoreIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) get_instance: MethodId,
+	pub(crate) load: MethodId,
+	pub(crate) aliases: MethodId,
+	pub(crate) get_certificate: MethodId,
+	pub(crate) set_certificate_entry: MethodId,
+	pub(crate) cert_get_encoded: MethodId,
+	pub(crate) cert_factory_class: GlobalRef<JClass>,
+	pub(crate) cert_factory_get_instance: MethodId,
+	pub(crate) cert_factory_generate: MethodId,
+	pub(crate) byte_stream_class: GlobalRef<JClass>,
+	pub(crate) byte_stream_ctor: MethodId,
+}
+impl RichJavaType for JKeyStore {
+	type IDs = KeyStoreIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static KeyStoreIDs {
+		static IDS: OnceCell<KeyStoreIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JKeyStore::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+
+			let cert = env.find_class("java/security/cert/Certificate")?;
+			let cert_get_encoded = env.get_method_id(cert, "getEncoded", "()[B")?;
+			env.delete_local_ref(cert)?;
+
+			let cert_factory_class = class_ref(env, "java/security/cert/CertificateFactory")?;
+			let byte_stream_class = class_ref(env, "java/io/ByteArrayInputStream")?;
+
+			Ok(KeyStoreIDs {
+				get_instance: env.get_static_method_id(raw, "getInstance", "(Ljava/lang/String;)Ljava/security/KeyStore;")?,
+				load: env.get_method_id(raw, "load", "(Ljava/io/InputStream;[C)V")?,
+				aliases: env.get_method_id(raw, "aliases", "()Ljava/util/Enumeration;")?,
+				get_certificate: env.get_method_id(raw, "getCertificate", "(Ljava/lang/String;)Ljava/security/cert/Certificate;")?,
+				set_certificate_entry: env.get_method_id(raw, "setCertificateEntry", "(Ljava/lang/String;Ljava/security/cert/Certificate;)V")?,
+				cert_get_encoded,
+				cert_factory_get_instance: env.get_static_method_id(cert_factory_class.as_raw_nonnull(), "getInstance", "(Ljava/lang/String;)Ljava/security/cert/CertificateFactory;")?,
+				cert_factory_generate: env.get_method_id(cert_factory_class.as_raw_nonnull(), "generateCertificate", "(Ljava/io/InputStream;)Ljava/security/cert/Certificate;")?,
+				byte_stream_ctor: env.get_method_id(byte_stream_class.as_raw_nonnull(), "<init>", "([B)V")?,
+				cert_factory_class,
+				byte_stream_class,
+				class,
+			})
+		}).expect("unable to resolve java/security/KeyStore descriptors");
+		ids
+	}
+}
+impl JavaClass for JKeyStore {
+	const INTERNAL_NAME: &'static str = "java/security/KeyStore";
+}
+
+impl JKeyStore {
+	/// Creates a new, empty keystore of the given type, such as `PKCS12`.
+	pub fn new_empty<'a>(env: JniEnv<'a>, store_type: &str) -> Result<AutoObj<'a, JKeyStore>, JniError> {
+		let ids = JKeyStore::descriptors(env);
+		let store_type = env.new_string(store_type)?;
+		let store = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_instance, &[js::jvalue { l: store_type.as_ptr() }]);
+		env.delete_local_ref(store_type)?;
+		let store: AutoObj<JKeyStore> = AutoObj::from_raw(env, store?.ok_or(JniError::NullPointer("KeyStore.getInstance"))?);
+
+		// a keystore must be loaded before use, and loading from null initializes it empty
+		env.call_void_method(store.as_raw_nonnull(), ids.load, &[js::jvalue { l: std::ptr::null_mut() }, js::jvalue { l: std::ptr::null_mut() }])?;
+		Ok(store)
+	}
+
+	/// Returns every certificate in the keystore along with its alias, DER-encoded.
+	///
+	/// For key entries, this is the first certificate of the entry's chain.
+	pub fn certificates<R: AsRawObject<JKeyStore>>(env: JniEnv<'_>, store: &R) -> Result<Vec<(String, Vec<u8>)>, JniError> {
+		let ids = JKeyStore::descriptors(env);
+		let enum_ids = JEnumeration::descriptors(env);
+		let aliases = env.call_object_method(raw_nonnull(store), ids.aliases, &[])?
+			.ok_or(JniError::NullPointer("KeyStore.aliases"))?;
+
+		let mut res = Vec::new();
+		let iterated = (|| {
+			while env.call_boolean_method(aliases, enum_ids.has_more_elements, &[])? {
+				let alias = env.call_object_method(aliases, enum_ids.next_element, &[])?
+					.ok_or(JniError::NullPointer("Enumeration.nextElement"))?;
+				let cert = env.call_object_method(raw_nonnull(store), ids.get_certificate, &[js::jvalue { l: alias.as_ptr() }]);
+				let alias = env.take_string(Some(alias))?.ok_or(JniError::NullPointer("KeyStore.aliases"))?;
+				if let Some(cert) = cert? {
+					let der = encoded(env, ids.cert_get_encoded, cert);
+					env.delete_local_ref(cert)?;
+					res.push((alias, der?));
+				}
+			}
+			Ok(())
+		})();
+		env.delete_local_ref(aliases)?;
+		iterated.map(|()| res)
+	}
+
+	/// Parses a DER-encoded X.509 certificate and stores it as a trusted entry under `alias`, replacing any existing
+	/// certificate with that alias.
+	pub fn add_certificate<R: AsRawObject<JKeyStore>>(env: JniEnv<'_>, store: &R, alias: &str, der: &[u8]) -> Result<(), JniError> {
+		let ids = JKeyStore::descriptors(env);
+		let cert = JKeyStore::parse_certificate(env, ids, der)?;
+		let alias = env.new_string(alias)?;
+		let res = env.call_void_method(raw_nonnull(store), ids.set_certificate_entry, &[js::jvalue { l: alias.as_ptr() }, js::jvalue { l: cert.as_ptr() }]);
+		env.delete_local_ref(alias)?;
+		env.delete_local_ref(cert)?;
+		res
+	}
+
+	fn parse_certificate(env: JniEnv<'_>, ids: &KeyStoreIDs, der: &[u8]) -> Result<RawJObject, JniError> {
+		let x509 = env.new_string("X.509")?;
+		let factory = env.call_static_object_method(ids.cert_factory_class.as_raw_nonnull(), ids.cert_factory_get_instance, &[js::jvalue { l: x509.as_ptr() }]);
+		env.delete_local_ref(x509)?;
+		let factory = factory?.ok_or(JniError::NullPointer("CertificateFactory.getInstance"))?;
+
+		let bytes = JPrimitiveArray::from_bytes(env, der)?;
+		let stream = env.new_object(ids.byte_stream_class.as_raw_nonnull(), ids.byte_stream_ctor, &[js::jvalue { l: bytes.as_raw_nonnull().as_ptr() }]);
+		drop(bytes);
+		let stream = stream?;
+
+		let cert = env.call_object_method(factory, ids.cert_factory_generate, &[js::jvalue { l: stream.as_ptr() }]);
+		env.delete_local_ref(stream)?;
+		env.delete_local_ref(factory)?;
+		cert?.ok_or(JniError::NullPointer("CertificateFactory.generateCertificate"))
+	}
+}
+
+/// `javax.net.ssl.TrustManagerFactory`
+#[derive(Debug)]
+pub struct JTrustManagerFactory;
+#[derive(Debug)]
+pub struct TrustManagerFactoryIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) get_default_algorithm: MethodId,
+	pub(crate) get_instance: MethodId,
+	pub(crate) init: MethodId,
+	pub(crate) get_trust_managers: MethodId,
+	pub(crate) x509_class: GlobalRef<JClass>,
+	pub(crate) x509_get_accepted_issuers: MethodId,
+	pub(crate) ssl_context_class: GlobalRef<JClass>,
+	pub(crate) ssl_context_get_instance: MethodId,
+	pub(crate) ssl_context_init: MethodId,
+	pub(crate) ssl_context_set_default: MethodId,
+}
+impl RichJavaType for JTrustManagerFactory {
+	type IDs = TrustManagerFactoryIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static TrustManagerFactoryIDs {
+		static IDS: OnceCell<TrustManagerFactoryIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JTrustManagerFactory::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			let x509_class = class_ref(env, "javax/net/ssl/X509TrustManager")?;
+			let ssl_context_class = class_ref(env, "javax/net/ssl/SSLContext")?;
+			let ssl_raw = ssl_context_class.as_raw_nonnull();
+
+			Ok(TrustManagerFactoryIDs {
+				get_default_algorithm: env.get_static_method_id(raw, "getDefaultAlgorithm", "()Ljava/lang/String;")?,
+				get_instance: env.get_static_method_id(raw, "getInstance", "(Ljava/lang/String;)Ljavax/net/ssl/TrustManagerFactory;")?,
+				init: env.get_method_id(raw, "init", "(Ljava/security/KeyStore;)V")?,
+				get_trust_managers: env.get_method_id(raw, "getTrustManagers", "()[Ljavax/net/ssl/TrustManager;")?,
+				x509_get_accepted_issuers: env.get_method_id(x509_class.as_raw_nonnull(), "getAcceptedIssuers", "()[Ljava/security/cert/X509Certificate;")?,
+				ssl_context_get_instance: env.get_static_method_id(ssl_raw, "getInstance", "(Ljava/lang/String;)Ljavax/net/ssl/SSLContext;")?,
+				ssl_context_init: env.get_method_id(ssl_raw, "init", "([Ljavax/net/ssl/KeyManager;[Ljavax/net/ssl/TrustManager;Ljava/security/SecureRandom;)V")?,
+				ssl_context_set_default: env.get_static_method_id(ssl_raw, "setDefault", "(Ljavax/net/ssl/SSLContext;)V")?,
+				x509_class,
+				ssl_context_class,
+				class,
+			})
+		}).expect("unable to resolve javax/net/ssl/TrustManagerFactory descriptors");
+		ids
+	}
+}
+impl JavaClass for JTrustManagerFactory {
+	const INTERNAL_NAME: &'static str = "javax/net/ssl/TrustManagerFactory";
+}
+
+impl JTrustManagerFactory {
+	/// Returns the trust managers for `store`, or for the JVM's default trust store (usually `cacerts`) if null.
+	fn trust_managers(env: JniEnv<'_>, ids: &TrustManagerFactoryIDs, store: js::jobject) -> Result<RawJObject, JniError> {
+		let algorithm = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_default_algorithm, &[])?
+			.ok_or(JniError::NullPointer("TrustManagerFactory.getDefaultAlgorithm"))?;
+		let factory = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_instance, &[js::jvalue { l: algorithm.as_ptr() }]);
+		env.delete_local_ref(algorithm)?;
+		let factory = factory?.ok_or(JniError::NullPointer("TrustManagerFactory.getInstance"))?;
+
+		let managers = env.call_void_method(factory, ids.init, &[js::jvalue { l: store }])
+			.and_then(|()| env.call_object_method(factory, ids.get_trust_managers, &[]));
+		env.delete_local_ref(factory)?;
+		managers?.ok_or(JniError::NullPointer("TrustManagerFactory.getTrustManagers"))
+	}
+
+	fn accepted_issuers(env: JniEnv<'_>, store: js::jobject) -> Result<Vec<Vec<u8>>, JniError> {
+		let ids = JTrustManagerFactory::descriptors(env);
+		let get_encoded = JKeyStore::descriptors(env).cert_get_encoded;
+		let managers = JTrustManagerFactory::trust_managers(env, ids, store)?;
+
+		let mut res = Vec::new();
+		let collected = (|| -> Result<(), JniError> {
+			for i in 0..env.array_length(managers)? {
+				let manager = match env.get_object_array_element(managers, i)? {
+					Some(manager) => manager,
+					None => continue,
+				};
+				let issuers = if env.is_instance_of(manager, ids.x509_class.as_raw_nonnull())? {
+					env.call_object_method(manager, ids.x509_get_accepted_issuers, &[])
+				} else {
+					Ok(None)
+				};
+				env.delete_local_ref(manager)?;
+
+				if let Some(issuers) = issuers? {
+					let encoded_all = (0..env.array_length(issuers)?).try_for_each(|j| -> Result<(), JniError> {
+						if let Some(cert) = env.get_object_array_element(issuers, j)? {
+							let der = encoded(env, get_encoded, cert);
+							env.delete_local_ref(cert)?;
+							res.push(der?);
+						}
+						Ok(())
+					});
+					env.delete_local_ref(issuers)?;
+					encoded_all?;
+				}
+			}
+			Ok(())
+		})();
+		env.delete_local_ref(managers)?;
+		collected.map(|()| res)
+	}
+
+	/// Returns the DER-encoded certificates trusted by the JVM by default, typically from its `cacerts` file.
+	pub fn default_trusted_certificates(env: JniEnv<'_>) -> Result<Vec<Vec<u8>>, JniError> {
+		JTrustManagerFactory::accepted_issuers(env, std::ptr::null_mut())
+	}
+
+	/// Returns the DER-encoded certificates that a trust manager built from `store` would accept as issuers.
+	pub fn trusted_certificates<R: AsRawObject<JKeyStore>>(env: JniEnv<'_>, store: &R) -> Result<Vec<Vec<u8>>, JniError> {
+		JTrustManagerFactory::accepted_issuers(env, raw_nonnull(store).as_ptr())
+	}
+
+	/// Replaces the JVM's default `SSLContext` with one that trusts exactly the certificates in `store`.
+	///
+	/// This affects every connection created afterwards without an explicit context, such as `HttpsURLConnection`.
+	pub fn install_default<R: AsRawObject<JKeyStore>>(env: JniEnv<'_>, store: &R) -> Result<(), JniError> {
+		let ids = JTrustManagerFactory::descriptors(env);
+		let managers = JTrustManagerFactory::trust_managers(env, ids, raw_nonnull(store).as_ptr())?;
+
+		let protocol = env.new_string("TLS")?;
+		let context = env.call_static_object_method(ids.ssl_context_class.as_raw_nonnull(), ids.ssl_context_get_instance, &[js::jvalue { l: protocol.as_ptr() }]);
+		env.delete_local_ref(protocol)?;
+
+		let installed = context.and_then(|context| {
+			let context = context.ok_or(JniError::NullPointer("SSLContext.getInstance"))?;
+			let res = env.call_void_method(context, ids.ssl_context_init, &[
+				js::jvalue { l: std::ptr::null_mut() },
+				js::jvalue { l: managers.as_ptr() },
+				js::jvalue { l: std::ptr::null_mut() },
+			]).and_then(|()| env.call_static_void_method(ids.ssl_context_class.as_raw_nonnull(), ids.ssl_context_set_default, &[js::jvalue { l: context.as_ptr() }]));
+			env.delete_local_ref(context)?;
+			res
+		});
+		env.delete_local_ref(managers)?;
+		installed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::security::{JKeyStore, JTrustManagerFactory};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn certificate_roundtrip() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let store = JKeyStore::new_empty(env, "PKCS12").expect("error creating keystore");
+			assert!(JKeyStore::certificates(env, &store).expect("error listing certificates").is_empty());
+
+			let defaults = JTrustManagerFactory::default_trusted_certificates(env).expect("error reading default trust");
+			let some: Vec<_> = defaults.into_iter().take(3).collect();
+			for (i, der) in some.iter().enumerate() {
+				JKeyStore::add_certificate(env, &store, &format!("cert{}", i), der).expect("error adding certificate");
+			}
+
+			let mut stored: Vec<_> = JKeyStore::certificates(env, &store).expect("error listing certificates")
+				.into_iter().map(|(_, der)| der).collect();
+			let mut trusted = JTrustManagerFactory::trusted_certificates(env, &store).expect("error reading trust");
+			let mut expected = some.clone();
+			stored.sort();
+			trusted.sort();
+			expected.sort();
+			assert_eq!(expected, stored);
+			assert_eq!(expected, trusted);
+
+			JTrustManagerFactory::install_default(env, &store).expect("error installing trust");
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
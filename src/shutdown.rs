@@ -0,0 +1,263 @@
+//! Ordered teardown that runs before a JavaVM is destroyed.
+//!
+//! Subsystems that hold Java state register a teardown callback with a [`Priority`]. [`JavaVM::destroy`] runs every
+//! callback, lowest priority first, on the destroying thread while the VM is still fully usable. This lets Java-facing
+//! callbacks be detached before the Rust state they use is released, and global references be deleted last.
+//!
+//! ```no_run
+//! # use yajnir::{buffer::ScratchPool, shutdown::{self, Priority}};
+//! static POOL: once_cell::sync::Lazy<ScratchPool> = once_cell::sync::Lazy::new(|| ScratchPool::new(8));
+//!
+//! shutdown::register(Priority::POOLS, "scratch pool", |_env| {
+//!     POOL.clear();
+//!     Ok(())
+//! });
+//! ```
+//!
+//! Separately, [`JavaVM::add_shutdown_hook`] registers a closure with `Runtime.addShutdownHook`, so it also runs when
+//! Java code calls `System.exit`.
+//!
+//! [`JavaVM::destroy`]: crate::jvm::JavaVM::destroy
+//! [`JavaVM::add_shutdown_hook`]: crate::jvm::JavaVM::add_shutdown_hook
+
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::classfile::ClassFile;
+use crate::env::{FieldId, JniEnv, JniError, MethodId};
+use crate::jref::{GlobalRef, JavaClass, RawJObject, RichJavaType};
+use crate::lang::{class_ref, JClass, JavaException};
+
+/// When a teardown callback runs relative to the others. Lower priorities run first, and callbacks with equal
+/// priorities run in the order they were registered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(pub i32);
+
+impl Priority {
+	/// Stopping Java code from calling into Rust, such as by unregistering proxies and listeners
+	pub const PROXIES: Priority = Priority(100);
+	/// Releasing Rust state owned by Java objects, such as handle tables and [`Cleaner`](crate::cleaner::Cleaner)s
+	pub const HANDLES: Priority = Priority(200);
+	/// Emptying caches of Java objects, such as a [`ScratchPool`](crate::buffer::ScratchPool)
+	pub const POOLS: Priority = Priority(300);
+	/// Deleting any remaining global references
+	pub const GLOBALS: Priority = Priority(400);
+}
+
+type Teardown = Box<dyn FnOnce(JniEnv<'_>) -> Result<(), JniError> + Send>;
+
+struct Registration {
+	priority: Priority,
+	name: &'static str,
+	teardown: Teardown,
+}
+
+static REGISTRY: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+/// Registers `teardown` to run with the given priority when the VM is destroyed. `name` identifies it in logs.
+///
+/// Each callback runs at most once. A callback that fails, panics, or leaves an exception pending is logged, and the
+/// remaining callbacks still run.
+pub fn register<F>(priority: Priority, name: &'static str, teardown: F)
+	where F: FnOnce(JniEnv<'_>) -> Result<(), JniError> + Send + 'static
+{
+	lock().push(Registration { priority, name, teardown: Box::new(teardown) });
+}
+
+/// Runs and removes every registered callback, in priority order.
+pub(crate) fn run(env: JniEnv<'_>) {
+	let mut registered = std::mem::take(&mut *lock());
+	// stable, so equal priorities keep their registration order
+	registered.sort_by_key(|registration| registration.priority);
+
+	for Registration { priority, name, teardown } in registered {
+		log::debug!("running {} teardown (priority {})", name, priority.0);
+		match std::panic::catch_unwind(AssertUnwindSafe(|| teardown(env))) {
+			Ok(Ok(())) => {},
+			Ok(Err(e)) => log::error!("{} teardown failed: {}", name, e),
+			Err(_) => log::error!("{} teardown panicked", name),
+		}
+		if let Err(e) = JavaException::describe_to_log(env, log::Level::Warn) {
+			log::error!("unable to check for exceptions left by {} teardown: {}", name, e);
+		}
+	}
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Vec<Registration>> {
+	REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+type Hook = Box<dyn FnOnce(JniEnv<'_>) + Send>;
+
+/// `yajnir.ShutdownHook`, a `Thread` subclass defined at runtime whose native `run` calls a boxed Rust closure
+#[derive(Debug)]
+pub struct JShutdownHook;
+#[derive(Debug)]
+pub struct ShutdownHookIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	/// A `Box<Hook>` pointer, or zero once the hook has run
+	pub(crate) handle: FieldId,
+	pub(crate) runtime_class: GlobalRef<JClass>,
+	pub(crate) get_runtime: MethodId,
+	pub(crate) add_shutdown_hook: MethodId,
+}
+impl RichJavaType for JShutdownHook {
+	type IDs = ShutdownHookIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ShutdownHookIDs {
+		static IDS: OnceCell<ShutdownHookIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			// SAFETY: run_hook matches the native run()V
+			let class = unsafe {
+				hook_class_file().define(env, &[("run", "()V", run_hook as extern "system" fn(*mut js::JNIEnv, js::jobject) as *mut std::ffi::c_void)])?
+			};
+			let raw = class.as_raw_nonnull();
+
+			let runtime_class = class_ref(env, "java/lang/Runtime")?;
+			Ok(ShutdownHookIDs {
+				ctor: env.get_method_id(raw, "<init>", "()V")?,
+				handle: env.get_field_id(raw, "handle", "J")?,
+				get_runtime: env.get_static_method_id(runtime_class.as_raw_nonnull(), "getRuntime", "()Ljava/lang/Runtime;")?,
+				add_shutdown_hook: env.get_method_id(runtime_class.as_raw_nonnull(), "addShutdownHook", "(Ljava/lang/Thread;)V")?,
+				class,
+				runtime_class,
+			})
+		}).expect("unable to define yajnir/ShutdownHook");
+		ids
+	}
+}
+impl JavaClass for JShutdownHook {
+	const INTERNAL_NAME: &'static str = "yajnir/ShutdownHook";
+}
+
+/// The class file for:
+///
+/// ```java
+/// final class ShutdownHook extends Thread {
+///     private long handle;
+///     ShutdownHook() { super(); }
+///     public native void run();
+/// }
+/// ```
+fn hook_class_file() -> ClassFile {
+	let mut class = ClassFile::new(ClassFile::ACC_FINAL | ClassFile::ACC_SUPER, JShutdownHook::INTERNAL_NAME, "java/lang/Thread");
+	let [hi, lo] = class.methodref("java/lang/Thread", "<init>", "()V").to_be_bytes();
+	class.field(ClassFile::ACC_PRIVATE, "handle", "J")
+		// aload_0, invokespecial Thread.<init>, return
+		.method(0, "<init>", "()V", 1, 1, &[0x2a, 0xb7, hi, lo, 0xb1])
+		.native_method(ClassFile::ACC_PUBLIC, "run", "()V");
+	class
+}
+
+/// Registers `hook` with `Runtime.addShutdownHook`. See [`JavaVM::add_shutdown_hook`](crate::jvm::JavaVM::add_shutdown_hook).
+pub(crate) fn add_hook(env: JniEnv<'_>, hook: Hook) -> Result<(), JniError> {
+	let ids = JShutdownHook::descriptors(env);
+	let thread = env.new_object(ids.class.as_raw_nonnull(), ids.ctor, &[])?;
+	let handle = Box::into_raw(Box::new(hook));
+	let res = (|| -> Result<(), JniError> {
+		env.set_long_field(thread, ids.handle, handle as js::jlong)?;
+		let runtime = env.call_static_object_method(ids.runtime_class.as_raw_nonnull(), ids.get_runtime, &[])?
+			.ok_or(JniError::NullPointer("Runtime.getRuntime"))?;
+		let res = env.call_void_method(runtime, ids.add_shutdown_hook, &[js::jvalue { l: thread.as_ptr() }]);
+		env.delete_local_ref(runtime)?;
+		res
+	})();
+	env.delete_local_ref(thread)?;
+
+	if res.is_err() {
+		// never registered, such as when the VM is already shutting down, so the closure will never run
+		// SAFETY: handle came from Box::into_raw above, and the Java object holding it is unreachable
+		drop(unsafe { Box::from_raw(handle) });
+	}
+	res
+}
+
+/// The native `ShutdownHook.run`, which takes and calls the hook's closure.
+extern "system" fn run_hook(env: *mut js::JNIEnv, this: js::jobject) {
+	let (env, this) = match (NonNull::new(env), NonNull::new(this)) {
+		(Some(env), Some(this)) => (JniEnv { ptr: env, _phantom: PhantomData }, this),
+		_ => return,
+	};
+	let hook = match take_hook(env, this) {
+		Ok(Some(hook)) => hook,
+		Ok(None) => return,
+		Err(e) => {
+			log::error!("unable to read shutdown hook: {}", e);
+			return;
+		},
+	};
+
+	// unwinding into the JVM is undefined behaviour
+	if std::panic::catch_unwind(AssertUnwindSafe(|| hook(env))).is_err() {
+		log::error!("shutdown hook panicked");
+	}
+	if let Err(e) = JavaException::describe_to_log(env, log::Level::Warn) {
+		log::error!("unable to check for exceptions left by shutdown hook: {}", e);
+	}
+}
+
+fn take_hook(env: JniEnv<'_>, this: RawJObject) -> Result<Option<Hook>, JniError> {
+	let ids = JShutdownHook::descriptors(env);
+	// the JVM starts each hook thread once, so nothing else reads the handle concurrently
+	let handle = env.get_long_field(this, ids.handle)?;
+	env.set_long_field(this, ids.handle, 0)?;
+	if handle == 0 {
+		return Ok(None);
+	}
+	// SAFETY: handle came from Box::into_raw in add_hook, and was cleared above so it is only taken once
+	Ok(Some(*unsafe { Box::from_raw(handle as *mut Hook) }))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JSystem;
+	use crate::shutdown::{self, Priority};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn teardown_runs_in_priority_order() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let order = Arc::new(Mutex::new(Vec::new()));
+			for (priority, name) in [(Priority::GLOBALS, "globals"), (Priority::PROXIES, "proxies"), (Priority::POOLS, "pools"), (Priority::PROXIES, "listeners")] {
+				let order = Arc::clone(&order);
+				shutdown::register(priority, name, move |env| {
+					// the VM is still usable during teardown
+					JSystem::property(env, "java.version")?;
+					order.lock().unwrap().push(name);
+					Ok(())
+				});
+			}
+			shutdown::register(Priority::HANDLES, "failing", |env| env.throw_runtime("teardown failure"));
+			shutdown::register(Priority::HANDLES, "panicking", |_env| panic!("teardown failure"));
+
+			vm.destroy().expect("error destroying vm");
+			assert_eq!(vec!["proxies", "listeners", "pools", "globals"], *order.lock().unwrap());
+		}
+
+		#[test]
+		fn java_shutdown_hook_runs_on_destroy() {
+			let (vm, _env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let (tx, rx) = std::sync::mpsc::channel();
+			vm.add_shutdown_hook(move |env| {
+				let name = crate::lang::JThread::current_name(env).expect("error reading thread name");
+				tx.send(name).expect("error reporting hook");
+			}).expect("error adding shutdown hook");
+			assert!(rx.try_recv().is_err());
+
+			vm.destroy().expect("error destroying vm");
+			let name = rx.try_recv().expect("hook to have run");
+			assert!(name.starts_with("Thread-"), "unexpected hook thread name {}", name);
+		}
+	}
+}
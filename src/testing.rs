@@ -0,0 +1,107 @@
+//! Sharing one JavaVM between tests, instead of forking a process per test.
+//!
+//! Only compiled with the `jvm-test` feature enabled, usually as a dev-dependency. A process can only ever create one
+//! JavaVM, so tests that each create and destroy their own have to run in separate processes. Tests marked with
+//! [`jvm_test`](crate::jvm_test) instead share a VM created by the first of them, and receive a `JniEnv` for their
+//! thread:
+//!
+//! ```no_run
+//! use yajnir::env::JniEnv;
+//!
+//! #[yajnir::jvm_test]
+//! fn property_is_set(env: JniEnv<'_>) {
+//!     let version = yajnir::lang::JSystem::property(env, "java.version").unwrap();
+//!     assert!(version.is_some());
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! The VM is created with the options in the `YAJNIR_TEST_OPTIONS` environment variable (split as described for
+//! [`VmOptions::merge_env_var`]), or reused if the process already has a [global](crate::jvm::global) one. It is never
+//! destroyed, so tests must not destroy it either, and shouldn't leave exceptions pending or change global state that
+//! other tests rely on.
+//!
+//! Tests of native methods can build a fixture class with [`ClassFile`], define it with [`define_class`], and call into
+//! it with [`call_static_int`].
+
+use std::ffi::c_void;
+
+use once_cell::sync::OnceCell;
+
+pub use crate::classfile::ClassFile;
+use crate::env::{JniEnv, JniError};
+use crate::jref::{raw_nonnull, AsRawObject, GlobalRef};
+use crate::jvm::{JavaVM, JniVersion, VmOptions};
+use crate::lang::JClass;
+
+/// The environment variable holding the shared VM's options
+pub const OPTIONS_VAR: &str = "YAJNIR_TEST_OPTIONS";
+
+/// Returns the JavaVM shared between tests, creating it on first use.
+///
+/// # Panics
+/// If the VM can't be created, failing the test that first needed it.
+pub fn shared_vm() -> JavaVM {
+	static VM: OnceCell<JavaVM> = OnceCell::new();
+	*VM.get_or_init(|| {
+		if let Some(vm) = crate::jvm::global() {
+			return vm;
+		}
+		let mut opts = VmOptions::new(JniVersion::V1_8);
+		opts.merge_env_var(OPTIONS_VAR).expect("invalid options for the shared test JavaVM");
+
+		// created on its own thread, so the VM's main thread isn't a test thread that exits while attached
+		std::thread::Builder::new()
+			.name("yajnir-test-vm".to_owned())
+			.spawn(move || {
+				let vm = crate::jvm::init_global(opts).expect("error creating the shared test JavaVM");
+				vm.detach_raw().expect("error detaching the shared test JavaVM's creating thread");
+				vm
+			})
+			.expect("error spawning a thread to create the shared test JavaVM")
+			.join()
+			.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+	})
+}
+
+/// Attaches the current test thread to the [`shared_vm`] until it exits. Called by [`jvm_test`](crate::jvm_test).
+#[doc(hidden)]
+pub fn attach_test_thread() -> JniEnv<'static> {
+	shared_vm().attach_permanently().expect("error attaching the test thread to the shared JavaVM")
+}
+
+/// Defines `class` with the bootstrap class loader, and registers its native methods as `(name, descriptor, function)`.
+///
+/// # Safety
+/// Each function pointer must be an `extern "system" fn` whose parameters and return type match its descriptor,
+/// taking the `JNIEnv` and then the class (for static methods) or instance first.
+pub unsafe fn define_class(env: JniEnv<'_>, class: &ClassFile, natives: &[(&str, &str, *mut c_void)]) -> Result<GlobalRef<JClass>, JniError> {
+	class.define(env, natives)
+}
+
+/// Calls the static method `name` with the descriptor `(I)I`. An exception it throws is left pending, and returned as
+/// [`JniError::JavaException`].
+pub fn call_static_int<R: AsRawObject<JClass>>(env: JniEnv<'_>, class: &R, name: &str, arg: i32) -> Result<i32, JniError> {
+	let class = raw_nonnull(class);
+	let method = env.get_static_method_id(class, name, "(I)I")?;
+	env.call_static_method(class, method, &[jni_sys::jvalue { i: arg }])
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::env::JniEnv;
+	use crate::lang::JSystem;
+
+	#[crate::jvm_test]
+	fn shares_vm(env: JniEnv<'_>) {
+		assert!(JSystem::property(env, "java.version").expect("error reading property").is_some());
+		assert_eq!(Some(super::shared_vm().ptr), crate::jvm::global().map(|vm| vm.ptr));
+	}
+
+	#[crate::jvm_test]
+	fn shares_vm_again(env: JniEnv<'_>) -> Result<(), crate::env::JniError> {
+		env.exception_check()?;
+		assert_eq!(1, crate::jvm::JavaVM::created_jvms().expect("error listing vms").len());
+		Ok(())
+	}
+}
@@ -0,0 +1,760 @@
+//! Descriptors and wrappers for `java.util` types.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, Implements, JavaClass, RawJObject, RichJavaType};
+use crate::lang::{class_ref, JClass, JClassLoader, JObject};
+
+/// `java.util.Iterator`
+#[derive(Debug)]
+pub struct JIterator;
+#[derive(Debug)]
+pub struct IteratorIDs {
+	pub(crate) has_next: MethodId,
+	pub(crate) next: MethodId,
+}
+impl RichJavaType for JIterator {
+	type IDs = IteratorIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static IteratorIDs {
+		static IDS: OnceCell<IteratorIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JIterator::INTERNAL_NAME)?;
+			let ids = IteratorIDs {
+				has_next: env.get_method_id(class, "hasNext", "()Z")?,
+				next: env.get_method_id(class, "next", "()Ljava/lang/Object;")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve java/util/Iterator descriptors");
+		ids
+	}
+}
+impl JavaClass for JIterator {
+	const INTERNAL_NAME: &'static str = "java/util/Iterator";
+}
+
+impl JIterator {
+	/// Adapts a `java.util.Iterator` into a Rust iterator over its elements.
+	pub fn iter<'a, T: JavaClass>(env: JniEnv<'a>, iterator: AutoObj<'a, JIterator>) -> JavaIter<'a, T> {
+		let ids = JIterator::descriptors(env);
+		JavaIter {
+			env,
+			source: iterator.upcast(),
+			has_next: ids.has_next,
+			next: ids.next,
+			next_name: "Iterator.next",
+			class: None,
+			done: false,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+/// `java.util.Enumeration`
+#[derive(Debug)]
+pub struct JEnumeration;
+#[derive(Debug)]
+pub struct EnumerationIDs {
+	pub(crate) has_more_elements: MethodId,
+	pub(crate) next_element: MethodId,
+}
+impl RichJavaType for JEnumeration {
+	type IDs = EnumerationIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static EnumerationIDs {
+		static IDS: OnceCell<EnumerationIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JEnumeration::INTERNAL_NAME)?;
+			let ids = EnumerationIDs {
+				has_more_elements: env.get_method_id(class, "hasMoreElements", "()Z")?,
+				next_element: env.get_method_id(class, "nextElement", "()Ljava/lang/Object;")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve java/util/Enumeration descriptors");
+		ids
+	}
+}
+impl JavaClass for JEnumeration {
+	const INTERNAL_NAME: &'static str = "java/util/Enumeration";
+}
+
+impl JEnumeration {
+	/// Adapts a legacy `java.util.Enumeration`, such as from `NetworkInterface.getNetworkInterfaces`, into a Rust
+	/// iterator over its elements.
+	pub fn iter<'a, T: JavaClass>(env: JniEnv<'a>, enumeration: AutoObj<'a, JEnumeration>) -> JavaIter<'a, T> {
+		let ids = JEnumeration::descriptors(env);
+		JavaIter {
+			env,
+			source: enumeration.upcast(),
+			has_next: ids.has_more_elements,
+			next: ids.next_element,
+			next_name: "Enumeration.nextElement",
+			class: None,
+			done: false,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+/// `java.util.Spliterator`
+#[derive(Debug)]
+pub struct JSpliterator;
+#[derive(Debug)]
+pub struct SpliteratorIDs {
+	pub(crate) spliterators_class: GlobalRef<JClass>,
+	pub(crate) spliterators_iterator: MethodId,
+}
+impl RichJavaType for JSpliterator {
+	type IDs = SpliteratorIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static SpliteratorIDs {
+		static IDS: OnceCell<SpliteratorIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let spliterators_class = class_ref(env, "java/util/Spliterators")?;
+			Ok(SpliteratorIDs {
+				spliterators_iterator: env.get_static_method_id(spliterators_class.as_raw_nonnull(), "iterator", "(Ljava/util/Spliterator;)Ljava/util/Iterator;")?,
+				spliterators_class,
+			})
+		}).expect("unable to resolve java/util/Spliterator descriptors");
+		ids
+	}
+}
+impl JavaClass for JSpliterator {
+	const INTERNAL_NAME: &'static str = "java/util/Spliterator";
+}
+
+impl JSpliterator {
+	/// Adapts a `java.util.Spliterator` into a Rust iterator over its remaining elements.
+	///
+	/// Advancing a spliterator directly requires a Java `Consumer`, so this goes through `Spliterators.iterator`.
+	pub fn iter<'a, T: JavaClass, R: AsRawObject<JSpliterator>>(env: JniEnv<'a>, spliterator: &R) -> Result<JavaIter<'a, T>, JniError> {
+		let ids = JSpliterator::descriptors(env);
+		let iterator = env.call_static_object_method(ids.spliterators_class.as_raw_nonnull(), ids.spliterators_iterator, &[js::jvalue { l: spliterator.as_raw() }])?
+			.ok_or(JniError::NullPointer("Spliterators.iterator"))?;
+		Ok(JIterator::iter(env, AutoObj::from_raw(env, iterator)))
+	}
+}
+
+/// A Rust iterator over the elements of a Java `Iterator`, `Enumeration`, or `Spliterator`, each as a new local
+/// reference of type `T`.
+///
+/// Each element is checked to be an instance of `T`. Exceptions thrown while advancing, null elements, and elements of
+/// another class are returned as error items, after which the iterator is fused. Exceptions are left pending.
+#[derive(Debug)]
+pub struct JavaIter<'a, T: JavaClass> {
+	env: JniEnv<'a>,
+	source: AutoObj<'a, JObject>,
+	has_next: MethodId,
+	next: MethodId,
+	next_name: &'static str,
+	/// `T`'s class, resolved on the first element
+	class: Option<AutoObj<'a, JClass>>,
+	done: bool,
+	_phantom: PhantomData<T>,
+}
+
+impl<'a, T: JavaClass> JavaIter<'a, T> {
+	fn advance(&mut self) -> Result<Option<AutoObj<'a, T>>, JniError> {
+		let source = self.source.as_raw_nonnull();
+		if !self.env.call_boolean_method(source, self.has_next, &[])? {
+			return Ok(None);
+		}
+
+		let obj = self.env.call_object_method(source, self.next, &[])?
+			.ok_or(JniError::NullPointer(self.next_name))?;
+		let obj: AutoObj<'a, JObject> = AutoObj::from_raw(self.env, obj);
+
+		let class = match &self.class {
+			Some(class) => class.as_raw_nonnull(),
+			None => {
+				let class = AutoObj::from_raw(self.env, self.env.find_class(T::INTERNAL_NAME)?);
+				self.class.get_or_insert(class).as_raw_nonnull()
+			},
+		};
+		if !self.env.is_instance_of(obj.as_raw_nonnull(), class)? {
+			return Err(JniError::NotInstance(T::INTERNAL_NAME));
+		}
+		Ok(Some(AutoObj::from_raw(self.env, obj.into_raw())))
+	}
+}
+
+impl<'a, T: JavaClass + 'a> Iterator for JavaIter<'a, T> {
+	type Item = Result<AutoObj<'a, T>, JniError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+		let item = self.advance().transpose();
+		self.done = !matches!(item, Some(Ok(_)));
+		item
+	}
+}
+
+/// `java.util.ServiceLoader`
+#[derive(Debug)]
+pub struct JServiceLoader;
+#[derive(Debug)]
+pub struct ServiceLoaderIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) load: MethodId,
+	pub(crate) load_with_loader: MethodId,
+	pub(crate) iterator: MethodId,
+}
+impl RichJavaType for JServiceLoader {
+	type IDs = ServiceLoaderIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ServiceLoaderIDs {
+		static IDS: OnceCell<ServiceLoaderIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JServiceLoader::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(ServiceLoaderIDs {
+				load: env.get_static_method_id(raw, "load", "(Ljava/lang/Class;)Ljava/util/ServiceLoader;")?,
+				load_with_loader: env.get_static_method_id(raw, "load", "(Ljava/lang/Class;Ljava/lang/ClassLoader;)Ljava/util/ServiceLoader;")?,
+				iterator: env.get_method_id(raw, "iterator", "()Ljava/util/Iterator;")?,
+				class,
+			})
+		}).expect("unable to resolve java/util/ServiceLoader descriptors");
+		ids
+	}
+}
+impl JavaClass for JServiceLoader {
+	const INTERNAL_NAME: &'static str = "java/util/ServiceLoader";
+}
+
+impl JServiceLoader {
+	/// Discovers the providers of service `T`, using the current thread's context class loader.
+	///
+	/// Providers are instantiated lazily as the returned iterator advances.
+	///
+	/// Note that on Java 9+ `ServiceLoader.load` checks that its caller declares a `uses` for the service. When called
+	/// from a native thread with no Java frames (such as directly after creating or attaching to the VM) there is no
+	/// caller, and a `ServiceConfigurationError` is thrown. Calls made from within native methods use the class
+	/// declaring the native method as the caller.
+	pub fn load<'a, T: JavaClass>(env: JniEnv<'a>) -> Result<ServiceProviders<'a, T>, JniError> {
+		JServiceLoader::load_raw(env, None)
+	}
+
+	/// Discovers the providers of service `T`, using the provided class loader.
+	pub fn load_with<'a, T: JavaClass, R: AsRawObject<JClassLoader>>(env: JniEnv<'a>, loader: &R) -> Result<ServiceProviders<'a, T>, JniError> {
+		JServiceLoader::load_raw(env, Some(loader.as_raw()))
+	}
+
+	fn load_raw<'a, T: JavaClass>(env: JniEnv<'a>, loader: Option<js::jobject>) -> Result<ServiceProviders<'a, T>, JniError> {
+		let ids = JServiceLoader::descriptors(env);
+		let service = env.find_class(T::INTERNAL_NAME)?;
+
+		let loaded = match loader {
+			None => env.call_static_object_method(ids.class.as_raw_nonnull(), ids.load, &[js::jvalue { l: service.as_ptr() }]),
+			Some(loader) => env.call_static_object_method(ids.class.as_raw_nonnull(), ids.load_with_loader, &[js::jvalue { l: service.as_ptr() }, js::jvalue { l: loader }]),
+		};
+		env.delete_local_ref(service)?;
+		let loaded = loaded?.ok_or(JniError::NullPointer("ServiceLoader.load"))?;
+
+		let iter = env.call_object_method(loaded, ids.iterator, &[]);
+		env.delete_local_ref(loaded)?;
+		let iter = iter?.ok_or(JniError::NullPointer("ServiceLoader.iterator"))?;
+
+		Ok(ServiceProviders {
+			iter: JIterator::iter(env, AutoObj::from_raw(env, iter)),
+		})
+	}
+}
+
+/// An iterator over the providers of a service `T`, as returned from [`JServiceLoader::load`]
+///
+/// Errors instantiating a provider (a `ServiceConfigurationError`) are returned as items, with the exception left pending.
+#[derive(Debug)]
+pub struct ServiceProviders<'a, T: JavaClass> {
+	iter: JavaIter<'a, T>,
+}
+
+impl<'a, T: JavaClass + 'a> Iterator for ServiceProviders<'a, T> {
+	type Item = Result<AutoObj<'a, T>, JniError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.iter.next()
+	}
+}
+
+/// `java.util.Collection`
+#[derive(Debug)]
+pub struct JCollection;
+#[derive(Debug)]
+pub struct CollectionIDs {
+	pub(crate) iterator: MethodId,
+}
+impl RichJavaType for JCollection {
+	type IDs = CollectionIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static CollectionIDs {
+		static IDS: OnceCell<CollectionIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JCollection::INTERNAL_NAME)?;
+			let ids = CollectionIDs {
+				iterator: env.get_method_id(class, "iterator", "()Ljava/util/Iterator;")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve java/util/Collection descriptors");
+		ids
+	}
+}
+impl JavaClass for JCollection {
+	const INTERNAL_NAME: &'static str = "java/util/Collection";
+}
+
+/// `java.util.Map`
+#[derive(Debug)]
+pub struct JMap;
+#[derive(Debug)]
+pub struct MapIDs {
+	pub(crate) entry_set: MethodId,
+	#[cfg_attr(not(feature = "nio"), allow(dead_code))]
+	pub(crate) key_set: MethodId,
+	pub(crate) entry_get_key: MethodId,
+	pub(crate) entry_get_value: MethodId,
+}
+impl RichJavaType for JMap {
+	type IDs = MapIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static MapIDs {
+		static IDS: OnceCell<MapIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JMap::INTERNAL_NAME)?;
+			let entry_set = env.get_method_id(class, "entrySet", "()Ljava/util/Set;")?;
+			let key_set = env.get_method_id(class, "keySet", "()Ljava/util/Set;")?;
+			env.delete_local_ref(class)?;
+
+			let entry = env.find_class("java/util/Map$Entry")?;
+			let ids = MapIDs {
+				entry_set,
+				key_set,
+				entry_get_key: env.get_method_id(entry, "getKey", "()Ljava/lang/Object;")?,
+				entry_get_value: env.get_method_id(entry, "getValue", "()Ljava/lang/Object;")?,
+			};
+			env.delete_local_ref(entry)?;
+			Ok(ids)
+		}).expect("unable to resolve java/util/Map descriptors");
+		ids
+	}
+}
+impl JavaClass for JMap {
+	const INTERNAL_NAME: &'static str = "java/util/Map";
+}
+
+/// `java.util.Properties`
+#[derive(Debug)]
+pub struct JProperties;
+#[derive(Debug)]
+pub struct PropertiesIDs {
+	pub(crate) string_property_names: MethodId,
+	pub(crate) get_property: MethodId,
+}
+impl RichJavaType for JProperties {
+	type IDs = PropertiesIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static PropertiesIDs {
+		static IDS: OnceCell<PropertiesIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = env.find_class(JProperties::INTERNAL_NAME)?;
+			let ids = PropertiesIDs {
+				string_property_names: env.get_method_id(class, "stringPropertyNames", "()Ljava/util/Set;")?,
+				get_property: env.get_method_id(class, "getProperty", "(Ljava/lang/String;)Ljava/lang/String;")?,
+			};
+			env.delete_local_ref(class)?;
+			Ok(ids)
+		}).expect("unable to resolve java/util/Properties descriptors");
+		ids
+	}
+}
+impl JavaClass for JProperties {
+	const INTERNAL_NAME: &'static str = "java/util/Properties";
+}
+// SAFETY: Properties extends Hashtable<Object, Object>, which implements Map
+unsafe impl Implements<JMap> for JProperties {}
+
+/// Calls `func` for each element of a `java.util.Collection`, deleting each element's local reference afterwards.
+pub(crate) fn for_each_element<F>(env: JniEnv<'_>, collection: RawJObject, mut func: F) -> Result<(), JniError>
+	where F: FnMut(Option<RawJObject>) -> Result<(), JniError>
+{
+	let iter_ids = JIterator::descriptors(env);
+	let iter = env.call_object_method(collection, JCollection::descriptors(env).iterator, &[])?
+		.ok_or(JniError::NullPointer("Collection.iterator"))?;
+
+	let res = (|| {
+		while env.call_boolean_method(iter, iter_ids.has_next, &[])? {
+			let elem = env.call_object_method(iter, iter_ids.next, &[])?;
+			let res = func(elem);
+			if let Some(elem) = elem {
+				env.delete_local_ref(elem)?;
+			}
+			res?;
+		}
+		Ok(())
+	})();
+	env.delete_local_ref(iter)?;
+	res
+}
+
+/// Copies a `java.util.Map<String, String>` into a Rust HashMap. Null keys are skipped, and null values are returned as empty strings.
+pub(crate) fn string_map(env: JniEnv<'_>, map: RawJObject) -> Result<HashMap<String, String>, JniError> {
+	let ids = JMap::descriptors(env);
+	let entries = env.call_object_method(map, ids.entry_set, &[])?
+		.ok_or(JniError::NullPointer("Map.entrySet"))?;
+
+	let mut res = HashMap::new();
+	let iterated = for_each_element(env, entries, |entry| {
+		let entry = entry.ok_or(JniError::NullPointer("Map.entrySet element"))?;
+		let key = env.take_string(env.call_object_method(entry, ids.entry_get_key, &[])?)?;
+		let value = env.take_string(env.call_object_method(entry, ids.entry_get_value, &[])?)?;
+		if let Some(key) = key {
+			res.insert(key, value.unwrap_or_default());
+		}
+		Ok(())
+	});
+	env.delete_local_ref(entries)?;
+	iterated.map(|()| res)
+}
+
+/// Collects the keys of a `Map<String, ?>`, in the map's iteration order.
+#[cfg(feature = "nio")]
+pub(crate) fn string_keys(env: JniEnv<'_>, map: RawJObject) -> Result<Vec<String>, JniError> {
+	let keys = env.call_object_method(map, JMap::descriptors(env).key_set, &[])?
+		.ok_or(JniError::NullPointer("Map.keySet"))?;
+
+	let mut res = Vec::new();
+	let iterated = for_each_element(env, keys, |key| {
+		if let Some(key) = key {
+			res.push(env.get_string(key)?);
+		}
+		Ok(())
+	});
+	env.delete_local_ref(keys)?;
+	iterated.map(|()| res)
+}
+
+/// Copies the String entries of a `java.util.Properties`, including its defaults, into a Rust HashMap.
+pub(crate) fn string_properties(env: JniEnv<'_>, props: RawJObject) -> Result<HashMap<String, String>, JniError> {
+	let ids = JProperties::descriptors(env);
+	let names = env.call_object_method(props, ids.string_property_names, &[])?
+		.ok_or(JniError::NullPointer("Properties.stringPropertyNames"))?;
+
+	let mut res = HashMap::new();
+	let iterated = for_each_element(env, names, |name| {
+		let name = name.ok_or(JniError::NullPointer("Properties.stringPropertyNames element"))?;
+		let value = env.take_string(env.call_object_method(props, ids.get_property, &[js::jvalue { l: name.as_ptr() }])?)?;
+		if let Some(value) = value {
+			res.insert(env.get_string(name)?, value);
+		}
+		Ok(())
+	});
+	env.delete_local_ref(names)?;
+	iterated.map(|()| res)
+}
+
+/// `java.util.Locale`
+#[derive(Debug)]
+pub struct JLocale;
+#[derive(Debug)]
+pub struct LocaleIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) get_default: MethodId,
+	pub(crate) to_language_tag: MethodId,
+}
+impl RichJavaType for JLocale {
+	type IDs = LocaleIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static LocaleIDs {
+		static IDS: OnceCell<LocaleIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JLocale::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(LocaleIDs {
+				get_default: env.get_static_method_id(raw, "getDefault", "()Ljava/util/Locale;")?,
+				to_language_tag: env.get_method_id(raw, "toLanguageTag", "()Ljava/lang/String;")?,
+				class,
+			})
+		}).expect("unable to resolve java/util/Locale descriptors");
+		ids
+	}
+}
+impl JavaClass for JLocale {
+	const INTERNAL_NAME: &'static str = "java/util/Locale";
+}
+
+impl JLocale {
+	/// Returns the JVM's default locale as an IETF BCP 47 language tag, such as `en-US`.
+	pub fn default_tag(env: JniEnv<'_>) -> Result<String, JniError> {
+		let ids = JLocale::descriptors(env);
+		let locale = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_default, &[])?
+			.ok_or(JniError::NullPointer("Locale.getDefault"))?;
+		let tag = env.call_object_method(locale, ids.to_language_tag, &[]);
+		env.delete_local_ref(locale)?;
+		env.take_string(tag?)?.ok_or(JniError::NullPointer("Locale.toLanguageTag"))
+	}
+}
+
+/// `java.util.TimeZone`
+#[derive(Debug)]
+pub struct JTimeZone;
+#[derive(Debug)]
+pub struct TimeZoneIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) get_default: MethodId,
+	pub(crate) get_id: MethodId,
+}
+impl RichJavaType for JTimeZone {
+	type IDs = TimeZoneIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static TimeZoneIDs {
+		static IDS: OnceCell<TimeZoneIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JTimeZone::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(TimeZoneIDs {
+				get_default: env.get_static_method_id(raw, "getDefault", "()Ljava/util/TimeZone;")?,
+				get_id: env.get_method_id(raw, "getID", "()Ljava/lang/String;")?,
+				class,
+			})
+		}).expect("unable to resolve java/util/TimeZone descriptors");
+		ids
+	}
+}
+impl JavaClass for JTimeZone {
+	const INTERNAL_NAME: &'static str = "java/util/TimeZone";
+}
+
+impl JTimeZone {
+	/// Returns the ID of the JVM's default time zone, such as `UTC` or `America/New_York`.
+	pub fn default_id(env: JniEnv<'_>) -> Result<String, JniError> {
+		let ids = JTimeZone::descriptors(env);
+		let zone = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.get_default, &[])?
+			.ok_or(JniError::NullPointer("TimeZone.getDefault"))?;
+		let id = env.call_object_method(zone, ids.get_id, &[]);
+		env.delete_local_ref(zone)?;
+		env.take_string(id?)?.ok_or(JniError::NullPointer("TimeZone.getID"))
+	}
+}
+
+/// `java.util.prefs.Preferences`
+#[derive(Debug)]
+pub struct JPreferences;
+#[derive(Debug)]
+pub struct PreferencesIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) user_root: MethodId,
+	pub(crate) system_root: MethodId,
+	pub(crate) node: MethodId,
+	pub(crate) get: MethodId,
+	pub(crate) put: MethodId,
+	pub(crate) remove: MethodId,
+	pub(crate) keys: MethodId,
+	pub(crate) flush: MethodId,
+	pub(crate) sync: MethodId,
+	pub(crate) remove_node: MethodId,
+}
+impl RichJavaType for JPreferences {
+	type IDs = PreferencesIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static PreferencesIDs {
+		static IDS: OnceCell<PreferencesIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JPreferences::INTERNAL_NAME)?;
+			let raw = class.as_raw_nonnull();
+			Ok(PreferencesIDs {
+				user_root: env.get_static_method_id(raw, "userRoot", "()Ljava/util/prefs/Preferences;")?,
+				system_root: env.get_static_method_id(raw, "systemRoot", "()Ljava/util/prefs/Preferences;")?,
+				node: env.get_method_id(raw, "node", "(Ljava/lang/String;)Ljava/util/prefs/Preferences;")?,
+				get: env.get_method_id(raw, "get", "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;")?,
+				put: env.get_method_id(raw, "put", "(Ljava/lang/String;Ljava/lang/String;)V")?,
+				remove: env.get_method_id(raw, "remove", "(Ljava/lang/String;)V")?,
+				keys: env.get_method_id(raw, "keys", "()[Ljava/lang/String;")?,
+				flush: env.get_method_id(raw, "flush", "()V")?,
+				sync: env.get_method_id(raw, "sync", "()V")?,
+				remove_node: env.get_method_id(raw, "removeNode", "()V")?,
+				class,
+			})
+		}).expect("unable to resolve java/util/prefs/Preferences descriptors");
+		ids
+	}
+}
+impl JavaClass for JPreferences {
+	const INTERNAL_NAME: &'static str = "java/util/prefs/Preferences";
+}
+
+impl JPreferences {
+	/// Returns the root preference node for the calling user.
+	pub fn user_root(env: JniEnv<'_>) -> Result<AutoObj<'_, JPreferences>, JniError> {
+		let ids = JPreferences::descriptors(env);
+		let node = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.user_root, &[])?
+			.ok_or(JniError::NullPointer("Preferences.userRoot"))?;
+		Ok(AutoObj::from_raw(env, node))
+	}
+
+	/// Returns the root preference node for the system.
+	pub fn system_root(env: JniEnv<'_>) -> Result<AutoObj<'_, JPreferences>, JniError> {
+		let ids = JPreferences::descriptors(env);
+		let node = env.call_static_object_method(ids.class.as_raw_nonnull(), ids.system_root, &[])?
+			.ok_or(JniError::NullPointer("Preferences.systemRoot"))?;
+		Ok(AutoObj::from_raw(env, node))
+	}
+
+	/// Returns the named node relative to `prefs`, creating it if it does not exist. Absolute paths start with a `/`.
+	pub fn node<'a, R: AsRawObject<JPreferences>>(env: JniEnv<'a>, prefs: &R, path: &str) -> Result<AutoObj<'a, JPreferences>, JniError> {
+		let ids = JPreferences::descriptors(env);
+		let path = env.new_string(path)?;
+		let node = env.call_object_method(raw_nonnull(prefs), ids.node, &[js::jvalue { l: path.as_ptr() }]);
+		env.delete_local_ref(path)?;
+		let node = node?.ok_or(JniError::NullPointer("Preferences.node"))?;
+		Ok(AutoObj::from_raw(env, node))
+	}
+
+	/// Returns the value associated with `key`, if any.
+	pub fn get<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R, key: &str) -> Result<Option<String>, JniError> {
+		let ids = JPreferences::descriptors(env);
+		let key = env.new_string(key)?;
+		let value = env.call_object_method(raw_nonnull(prefs), ids.get, &[js::jvalue { l: key.as_ptr() }, js::jvalue { l: std::ptr::null_mut() }]);
+		env.delete_local_ref(key)?;
+		env.take_string(value?)
+	}
+
+	/// Associates `value` with `key`.
+	pub fn put<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R, key: &str, value: &str) -> Result<(), JniError> {
+		let ids = JPreferences::descriptors(env);
+		let key = env.new_string(key)?;
+		let value = env.new_string(value)?;
+		let res = env.call_void_method(raw_nonnull(prefs), ids.put, &[js::jvalue { l: key.as_ptr() }, js::jvalue { l: value.as_ptr() }]);
+		env.delete_local_ref(key)?;
+		env.delete_local_ref(value)?;
+		res
+	}
+
+	/// Removes any value associated with `key`.
+	pub fn remove<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R, key: &str) -> Result<(), JniError> {
+		let ids = JPreferences::descriptors(env);
+		let key = env.new_string(key)?;
+		let res = env.call_void_method(raw_nonnull(prefs), ids.remove, &[js::jvalue { l: key.as_ptr() }]);
+		env.delete_local_ref(key)?;
+		res
+	}
+
+	/// Returns all keys that have an associated value in this node.
+	pub fn keys<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R) -> Result<Vec<String>, JniError> {
+		let ids = JPreferences::descriptors(env);
+		let keys = env.call_object_method(raw_nonnull(prefs), ids.keys, &[])?
+			.ok_or(JniError::NullPointer("Preferences.keys"))?;
+
+		let res = (0..env.array_length(keys)?)
+			.map(|i| env.get_object_array_element(keys, i).and_then(|key| env.take_string(key)))
+			.filter_map(Result::transpose)
+			.collect();
+		env.delete_local_ref(keys)?;
+		res
+	}
+
+	/// Forces any changes to this node and its descendants to the persistent store.
+	pub fn flush<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R) -> Result<(), JniError> {
+		env.call_void_method(raw_nonnull(prefs), JPreferences::descriptors(env).flush, &[])
+	}
+
+	/// Ensures future reads reflect changes committed to the persistent store, and flushes any local changes.
+	pub fn sync<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R) -> Result<(), JniError> {
+		env.call_void_method(raw_nonnull(prefs), JPreferences::descriptors(env).sync, &[])
+	}
+
+	/// Removes this node and all of its descendants, along with their values. Only [`JPreferences::flush`] may be
+	/// called on the removed node afterwards.
+	pub fn remove_node<R: AsRawObject<JPreferences>>(env: JniEnv<'_>, prefs: &R) -> Result<(), JniError> {
+		env.call_void_method(raw_nonnull(prefs), JPreferences::descriptors(env).remove_node, &[])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use jni_sys as js;
+	use crate::env::JniError;
+	use crate::jref::AutoObj;
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::lang::JObject;
+	use crate::util::{JCollection, JEnumeration, JIterator, JPreferences, JSpliterator};
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn iterator_adapters() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			let collections = env.find_class("java/util/Collections").expect("error finding Collections");
+			let n_copies = env.get_static_method_id(collections, "nCopies", "(ILjava/lang/Object;)Ljava/util/List;").expect("error finding nCopies");
+			let enumeration = env.get_static_method_id(collections, "enumeration", "(Ljava/util/Collection;)Ljava/util/Enumeration;").expect("error finding enumeration");
+			let collection = env.find_class("java/util/Collection").expect("error finding Collection");
+			let iterator = env.get_method_id(collection, "iterator", "()Ljava/util/Iterator;").expect("error finding iterator");
+			let spliterator = env.get_method_id(collection, "spliterator", "()Ljava/util/Spliterator;").expect("error finding spliterator");
+
+			let elem = env.new_string("x").expect("error creating string");
+			let list = env.call_static_object_method(collections, n_copies, &[js::jvalue { i: 3 }, js::jvalue { l: elem.as_ptr() }])
+				.expect("error creating list").expect("list to be non-null");
+			let to_strings = |obj: Result<AutoObj<JObject>, _>| {
+				JObject::to_string(env, &obj.expect("error iterating")).expect("error converting element")
+			};
+
+			let iter = env.call_object_method(list, iterator, &[]).expect("error getting iterator").expect("iterator to be non-null");
+			let items: Vec<String> = JIterator::iter(env, AutoObj::from_raw(env, iter)).map(to_strings).collect();
+			assert_eq!(vec!["x"; 3], items);
+
+			let elems = env.call_static_object_method(collections, enumeration, &[js::jvalue { l: list.as_ptr() }])
+				.expect("error getting enumeration").expect("enumeration to be non-null");
+			assert_eq!(vec!["x"; 3], JEnumeration::iter(env, AutoObj::from_raw(env, elems)).map(to_strings).collect::<Vec<_>>());
+
+			let split = env.call_object_method(list, spliterator, &[]).expect("error getting spliterator").expect("spliterator to be non-null");
+			let split: AutoObj<JSpliterator> = AutoObj::from_raw(env, split);
+			assert_eq!(vec!["x"; 3], JSpliterator::iter(env, &split).expect("error adapting spliterator").map(to_strings).collect::<Vec<_>>());
+
+			let iter = env.call_object_method(list, iterator, &[]).expect("error getting iterator").expect("iterator to be non-null");
+			let mut mistyped = JIterator::iter::<JCollection>(env, AutoObj::from_raw(env, iter));
+			assert!(matches!(mistyped.next(), Some(Err(JniError::NotInstance("java/util/Collection")))));
+			assert!(mistyped.next().is_none());
+
+			vm.destroy().expect("error destroying vm");
+		}
+
+		#[test]
+		fn preferences_node() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			// each forked test runs in its own process, so the pid keeps the node unique
+			let root = JPreferences::user_root(env).expect("error getting preferences");
+			let node = JPreferences::node(env, &root, &format!("yajnir-test-{}", std::process::id())).expect("error creating node");
+
+			assert_eq!(None, JPreferences::get(env, &node, "greeting").expect("error reading missing key"));
+			JPreferences::put(env, &node, "greeting", "hello").expect("error writing key");
+			JPreferences::put(env, &node, "farewell", "goodbye").expect("error writing key");
+			assert_eq!(Some("hello".to_owned()), JPreferences::get(env, &node, "greeting").expect("error reading key"));
+
+			let mut keys = JPreferences::keys(env, &node).expect("error listing keys");
+			keys.sort();
+			assert_eq!(keys, ["farewell", "greeting"]);
+
+			JPreferences::remove(env, &node, "farewell").expect("error removing key");
+			assert_eq!(None, JPreferences::get(env, &node, "farewell").expect("error reading removed key"));
+			JPreferences::flush(env, &node).expect("error flushing node");
+			JPreferences::sync(env, &node).expect("error syncing node");
+			assert_eq!(JPreferences::keys(env, &node).expect("error listing keys"), ["greeting"]);
+
+			JPreferences::remove_node(env, &node).expect("error removing node");
+			JPreferences::flush(env, &root).expect("error flushing removal");
+
+			drop(node);
+			drop(root);
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
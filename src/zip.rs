@@ -0,0 +1,279 @@
+//! Wrappers for reading archives through `java.util.zip`, such as the jars on the JVM's classpath.
+
+use std::convert::TryFrom;
+
+use jni_sys as js;
+use once_cell::sync::OnceCell;
+
+use crate::array::{as_jbytes_mut, JPrimitiveArray};
+use crate::env::{JniEnv, JniError, MethodId};
+use crate::jref::{raw_nonnull, AsRawObject, AutoObj, GlobalRef, JavaClass, RawJObject, RichJavaType};
+use crate::lang::{class_ref, JClass};
+use crate::util::JEnumeration;
+use crate::{from_jsize, to_jsize};
+
+/// Metadata about a single entry within a [`JZipFile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntryInfo {
+	pub name: String,
+	/// The uncompressed size, if known
+	pub size: Option<u64>,
+	pub is_directory: bool,
+}
+
+/// `java.util.zip.ZipFile`
+#[derive(Debug)]
+pub struct JZipFile;
+#[derive(Debug)]
+pub struct ZipFileIDs {
+	pub(crate) class: GlobalRef<JClass>,
+	pub(crate) jar_class: GlobalRef<JClass>,
+	pub(crate) ctor: MethodId,
+	pub(crate) jar_ctor: MethodId,
+	pub(crate) entries: MethodId,
+	pub(crate) get_entry: MethodId,
+	pub(crate) get_input_stream: MethodId,
+	pub(crate) close: MethodId,
+	pub(crate) entry_get_name: MethodId,
+	pub(crate) entry_get_size: MethodId,
+	pub(crate) entry_is_directory: MethodId,
+	pub(crate) stream_read: MethodId,
+	pub(crate) stream_close: MethodId,
+}
+impl RichJavaType for JZipFile {
+	type IDs = ZipFileIDs;
+
+	fn descriptors<'thread>(env: JniEnv<'thread>) -> &'static ZipFileIDs {
+		static IDS: OnceCell<ZipFileIDs> = OnceCell::new();
+		let ids = IDS.get_or_try_init(|| -> Result<_, JniError> {
+			let class = class_ref(env, JZipFile::INTERNAL_NAME)?;
+			let jar_class = class_ref(env, "java/util/jar/JarFile")?;
+			let raw = class.as_raw_nonnull();
+
+			let entry = env.find_class("java/util/zip/ZipEntry")?;
+			let entry_get_name = env.get_method_id(entry, "getName", "()Ljava/lang/String;")?;
+			let entry_get_size = env.get_method_id(entry, "getSize", "()J")?;
+			let entry_is_directory = env.get_method_id(entry, "isDirectory", "()Z")?;
+			env.delete_local_ref(entry)?;
+
+			let stream = env.find_class("java/io/InputStream")?;
+			// readAllBytes was only added in Java 9
+			let stream_read = env.get_method_id(stream, "read", "([BII)I")?;
+			let stream_close = env.get_method_id(stream, "close", "()V")?;
+			env.delete_local_ref(stream)?;
+
+			Ok(ZipFileIDs {
+				ctor: env.get_method_id(raw, "<init>", "(Ljava/lang/String;)V")?,
+				jar_ctor: env.get_method_id(jar_class.as_raw_nonnull(), "<init>", "(Ljava/lang/String;)V")?,
+				entries: env.get_method_id(raw, "entries", "()Ljava/util/Enumeration;")?,
+				get_entry: env.get_method_id(raw, "getEntry", "(Ljava/lang/String;)Ljava/util/zip/ZipEntry;")?,
+				get_input_stream: env.get_method_id(raw, "getInputStream", "(Ljava/util/zip/ZipEntry;)Ljava/io/InputStream;")?,
+				close: env.get_method_id(raw, "close", "()V")?,
+				entry_get_name,
+				entry_get_size,
+				entry_is_directory,
+				stream_read,
+				stream_close,
+				class,
+				jar_class,
+			})
+		}).expect("unable to resolve java/util/zip/ZipFile descriptors");
+		ids
+	}
+}
+impl JavaClass for JZipFile {
+	const INTERNAL_NAME: &'static str = "java/util/zip/ZipFile";
+}
+
+impl JZipFile {
+	fn open_class<'a>(env: JniEnv<'a>, class: RawJObject, ctor: MethodId, path: &str) -> Result<AutoObj<'a, JZipFile>, JniError> {
+		let path = env.new_string(path)?;
+		let zip = env.new_object(class, ctor, &[js::jvalue { l: path.as_ptr() }]);
+		env.delete_local_ref(path)?;
+		Ok(AutoObj::from_raw(env, zip?))
+	}
+
+	/// Opens a zip archive for reading.
+	pub fn open<'a>(env: JniEnv<'a>, path: &str) -> Result<AutoObj<'a, JZipFile>, JniError> {
+		let ids = JZipFile::descriptors(env);
+		JZipFile::open_class(env, ids.class.as_raw_nonnull(), ids.ctor, path)
+	}
+
+	/// Opens a jar as a `java.util.jar.JarFile`, which verifies signed entries as they are read.
+	pub fn open_jar<'a>(env: JniEnv<'a>, path: &str) -> Result<AutoObj<'a, JZipFile>, JniError> {
+		let ids = JZipFile::descriptors(env);
+		JZipFile::open_class(env, ids.jar_class.as_raw_nonnull(), ids.jar_ctor, path)
+	}
+
+	/// Lists every entry in the archive, in the order they appear in its central directory.
+	pub fn entries<R: AsRawObject<JZipFile>>(env: JniEnv<'_>, zip: &R) -> Result<Vec<ZipEntryInfo>, JniError> {
+		let ids = JZipFile::descriptors(env);
+		let enum_ids = JEnumeration::descriptors(env);
+		let entries = env.call_object_method(raw_nonnull(zip), ids.entries, &[])?
+			.ok_or(JniError::NullPointer("ZipFile.entries"))?;
+
+		let mut res = Vec::new();
+		let iterated = (|| {
+			while env.call_boolean_method(entries, enum_ids.has_more_elements, &[])? {
+				let entry = env.call_object_method(entries, enum_ids.next_element, &[])?
+					.ok_or(JniError::NullPointer("Enumeration.nextElement"))?;
+				let info = JZipFile::entry_info(env, ids, entry);
+				env.delete_local_ref(entry)?;
+				res.push(info?);
+			}
+			Ok(())
+		})();
+		env.delete_local_ref(entries)?;
+		iterated.map(|()| res)
+	}
+
+	fn entry_info(env: JniEnv<'_>, ids: &ZipFileIDs, entry: RawJObject) -> Result<ZipEntryInfo, JniError> {
+		let name = env.call_object_method(entry, ids.entry_get_name, &[])?;
+		let size = env.call_long_method(entry, ids.entry_get_size, &[])?;
+		Ok(ZipEntryInfo {
+			name: env.take_string(name)?.ok_or(JniError::NullPointer("ZipEntry.getName"))?,
+			size: u64::try_from(size).ok(),
+			is_directory: env.call_boolean_method(entry, ids.entry_is_directory, &[])?,
+		})
+	}
+
+	/// Reads the full, uncompressed contents of the named entry, or `None` if the archive has no such entry.
+	pub fn read<R: AsRawObject<JZipFile>>(env: JniEnv<'_>, zip: &R, name: &str) -> Result<Option<Vec<u8>>, JniError> {
+		let ids = JZipFile::descriptors(env);
+		let name = env.new_string(name)?;
+		let entry = env.call_object_method(raw_nonnull(zip), ids.get_entry, &[js::jvalue { l: name.as_ptr() }]);
+		env.delete_local_ref(name)?;
+		let entry = match entry? {
+			Some(entry) => entry,
+			None => return Ok(None),
+		};
+
+		let stream = env.call_object_method(raw_nonnull(zip), ids.get_input_stream, &[js::jvalue { l: entry.as_ptr() }]);
+		env.delete_local_ref(entry)?;
+		let stream = stream?.ok_or(JniError::NullPointer("ZipFile.getInputStream"))?;
+
+		let bytes = JZipFile::read_stream(env, ids, stream);
+		let closed = env.call_void_method(stream, ids.stream_close, &[]);
+		env.delete_local_ref(stream)?;
+		let bytes = bytes?;
+		closed?;
+		Ok(Some(bytes))
+	}
+
+	/// Reads `stream` to its end through a reusable `byte[]`.
+	fn read_stream(env: JniEnv<'_>, ids: &ZipFileIDs, stream: RawJObject) -> Result<Vec<u8>, JniError> {
+		const CHUNK: usize = 8192;
+		let chunk: AutoObj<JPrimitiveArray<js::jbyte>> = JPrimitiveArray::new(env, CHUNK)?;
+		let mut bytes = Vec::new();
+		loop {
+			let n = env.call_int_method(stream, ids.stream_read, &[
+				js::jvalue { l: chunk.as_raw_nonnull().as_ptr() },
+				js::jvalue { i: 0 },
+				js::jvalue { i: to_jsize(CHUNK)? },
+			])?;
+			// -1 at the end of the stream
+			if n < 0 {
+				return Ok(bytes);
+			}
+			let start = bytes.len();
+			bytes.resize(start + from_jsize(n)?, 0);
+			JPrimitiveArray::read_region(env, &chunk, 0, as_jbytes_mut(&mut bytes[start..]))?;
+		}
+	}
+
+	/// Closes the archive. Any further use of it from Java will fail.
+	pub fn close<R: AsRawObject<JZipFile>>(env: JniEnv<'_>, zip: &R) -> Result<(), JniError> {
+		env.call_void_method(raw_nonnull(zip), JZipFile::descriptors(env).close, &[])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use crate::jvm::{JavaVM, JniVersion, VmOptions};
+	use crate::zip::JZipFile;
+
+	fn crc32(data: &[u8]) -> u32 {
+		let mut crc = !0u32;
+		for &byte in data {
+			crc ^= u32::from(byte);
+			for _ in 0..8 {
+				crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+			}
+		}
+		!crc
+	}
+
+	/// Writes a zip holding each `(name, contents)` entry uncompressed.
+	fn write_stored_zip(path: &std::path::Path, entries: &[(&str, &[u8])]) {
+		let mut out = Vec::new();
+		let mut central = Vec::new();
+		for (name, data) in entries {
+			let offset = out.len() as u32;
+			let fields = |header: &mut Vec<u8>| {
+				header.extend_from_slice(&20u16.to_le_bytes()); // version needed
+				header.extend_from_slice(&[0; 4]); // flags, method (stored)
+				header.extend_from_slice(&[0, 0, 0x21, 0]); // mod time, date (1980-01-01)
+				header.extend_from_slice(&crc32(data).to_le_bytes());
+				header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+				header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+				header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+				header.extend_from_slice(&0u16.to_le_bytes()); // extra length
+			};
+
+			out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+			fields(&mut out);
+			out.extend_from_slice(name.as_bytes());
+			out.extend_from_slice(data);
+
+			central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+			central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+			fields(&mut central);
+			central.extend_from_slice(&[0; 10]); // comment length, disk, internal and external attributes
+			central.extend_from_slice(&offset.to_le_bytes());
+			central.extend_from_slice(name.as_bytes());
+		}
+
+		let central_offset = out.len() as u32;
+		out.extend_from_slice(&central);
+		out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+		out.extend_from_slice(&[0; 4]); // disk numbers
+		out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+		out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+		out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+		out.extend_from_slice(&central_offset.to_le_bytes());
+		out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+		std::fs::File::create(path).and_then(|mut f| f.write_all(&out)).expect("error writing zip");
+	}
+
+	rusty_fork::rusty_fork_test! {
+		#[test]
+		fn read_written_jar() {
+			let (vm, env) = JavaVM::create(VmOptions::new(JniVersion::V10)).expect("error creating vm");
+
+			// larger than one read chunk, to cover the read loop
+			let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+			let path = std::env::temp_dir().join(format!("yajnir-zip-test-{}.jar", std::process::id()));
+			write_stored_zip(&path, &[
+				("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\n\r\n"),
+				("data.bin", &data),
+			]);
+			let jar = JZipFile::open_jar(env, path.to_str().expect("temp path to be unicode")).expect("error opening jar");
+
+			let entries = JZipFile::entries(env, &jar).expect("error listing entries");
+			assert!(entries.iter().any(|e| e.name == "META-INF/MANIFEST.MF" && !e.is_directory));
+			assert!(entries.iter().any(|e| e.name == "data.bin" && e.size == Some(data.len() as u64)));
+
+			let manifest = JZipFile::read(env, &jar, "META-INF/MANIFEST.MF").expect("error reading manifest").expect("manifest exists");
+			assert!(manifest.starts_with(b"Manifest-Version"));
+			assert_eq!(Some(data), JZipFile::read(env, &jar, "data.bin").expect("error reading data"));
+			assert_eq!(None, JZipFile::read(env, &jar, "does/not/exist").expect("error reading missing entry"));
+
+			JZipFile::close(env, &jar).expect("error closing jar");
+			std::fs::remove_file(&path).expect("error removing zip");
+			vm.destroy().expect("error destroying vm");
+		}
+	}
+}
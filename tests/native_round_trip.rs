@@ -0,0 +1,100 @@
+//! Calls from Rust into Java and back through a native method, nested several frames deep.
+
+extern crate jvm_link;
+
+use yajnir::env::{JniEnv, JniError};
+use yajnir::jref::AsRawObject;
+use yajnir::lang::{JClass, JObject, JThreadGroup};
+use yajnir::testing::{self, ClassFile};
+
+#[yajnir::jvm_test]
+fn native_round_trip(env: JniEnv<'_>) {
+	// SAFETY: from_java matches the native fromJava(I)I
+	let class = unsafe {
+		testing::define_class(env, &round_trip_class(), &[
+			("fromJava", "(I)I", from_java as extern "system" fn(*mut jni_sys::JNIEnv, jni_sys::jclass, jni_sys::jint) -> jni_sys::jint as *mut std::ffi::c_void),
+		])
+	}.expect("error defining fixture class");
+
+	// Rust -> enter(2) -> fromJava(2) -> [enter(1) -> fromJava(1) -> [enter(0) -> fromJava(0) -> callback(0)] -> callback(1)] -> callback(2)
+	let res = testing::call_static_int(env, &class, "enter", 2).expect("error calling into the round trip");
+	assert_eq!(3 + 2 + 1, res);
+
+	// thrown by the innermost native call, and propagated back out through each Java and native frame
+	let res = testing::call_static_int(env, &class, "enter", -3);
+	assert!(matches!(res, Err(JniError::JavaException)));
+	let exc = env.catch_exception("java/lang/IllegalStateException").expect("error catching exception")
+		.expect("exception to be an IllegalStateException");
+	assert_eq!("java.lang.IllegalStateException: bottom of the round trip", JObject::to_string(env, &exc).expect("error calling toString"));
+	env.exception_check().expect("exception to be cleared");
+}
+
+/// The class passed to a static native method, borrowed for the length of the call
+struct NativeClass(jni_sys::jclass);
+impl AsRawObject<JClass> for NativeClass {
+	fn as_raw(&self) -> jni_sys::jobject {
+		self.0
+	}
+}
+
+/// The native `RoundTrip.fromJava(int)`. Any error is left pending as a Java exception.
+extern "system" fn from_java(_env: *mut jni_sys::JNIEnv, class: jni_sys::jclass, n: jni_sys::jint) -> jni_sys::jint {
+	let env = JniEnv::current().expect("native method to be called on an attached thread");
+	match round_trip(env, &NativeClass(class), n) {
+		Ok(sum) => sum,
+		Err(JniError::JavaException) => 0,
+		Err(e) => {
+			// nothing is pending yet, so report the Rust error to Java
+			let _ = env.throw_runtime(&e.to_string());
+			0
+		},
+	}
+}
+
+/// Returns the sum of `callback(i)` for `i` from `n` down to zero, re-entering Java and Rust once per level. A negative
+/// `n` instead recurses until -1, where it throws.
+fn round_trip(env: JniEnv<'_>, class: &NativeClass, n: jni_sys::jint) -> Result<jni_sys::jint, JniError> {
+	if n == -1 {
+		env.throw_illegal_state("bottom of the round trip")?;
+		return Ok(0);
+	}
+	if n < -1 {
+		return testing::call_static_int(env, class, "enter", n + 1);
+	}
+
+	// a local reference created in this native frame must outlive the nested frames
+	let label = JThreadGroup::new(env, &format!("frame {}", n))?;
+	let nested = if n > 0 {
+		testing::call_static_int(env, class, "enter", n - 1)?
+	} else {
+		0
+	};
+	if JThreadGroup::name(env, &label)? != format!("frame {}", n) {
+		env.throw_illegal_state("local reference changed by a nested native frame")?;
+		return Ok(0);
+	}
+
+	let own = testing::call_static_int(env, class, "callback", n)?;
+	Ok(own + nested)
+}
+
+/// `yajnir/test/RoundTrip`, equivalent to:
+///
+/// ```java
+/// public class RoundTrip {
+///     public static native int fromJava(int n);
+///     public static int enter(int n) { return fromJava(n); }
+///     public static int callback(int n) { return n + 1; }
+/// }
+/// ```
+fn round_trip_class() -> ClassFile {
+	let static_public = ClassFile::ACC_PUBLIC | ClassFile::ACC_STATIC;
+	let mut class = ClassFile::new(ClassFile::ACC_PUBLIC | ClassFile::ACC_SUPER, "yajnir/test/RoundTrip", "java/lang/Object");
+	let [hi, lo] = class.methodref("yajnir/test/RoundTrip", "fromJava", "(I)I").to_be_bytes();
+	class.native_method(static_public, "fromJava", "(I)I")
+		// iload_0, invokestatic fromJava, ireturn
+		.method(static_public, "enter", "(I)I", 1, 1, &[0x1a, 0xb8, hi, lo, 0xac])
+		// iload_0, iconst_1, iadd, ireturn
+		.method(static_public, "callback", "(I)I", 2, 1, &[0x1a, 0x04, 0x60, 0xac]);
+	class
+}
@@ -0,0 +1,47 @@
+//! Procedural macros for `yajnir`. Use them through their re-exports, such as `yajnir::jvm_test`.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, ItemFn};
+
+/// Runs a test function taking a `JniEnv` on the current thread, attached to a JavaVM shared by every such test.
+///
+/// See `yajnir::testing` for how the shared VM is created.
+#[proc_macro_attribute]
+pub fn jvm_test(args: TokenStream, item: TokenStream) -> TokenStream {
+	if !args.is_empty() {
+		let args = proc_macro2::TokenStream::from(args);
+		return Error::new(args.span(), "#[jvm_test] takes no arguments").into_compile_error().into();
+	}
+	let func = parse_macro_input!(item as ItemFn);
+	match expand(func) {
+		Ok(tokens) => tokens.into(),
+		Err(e) => e.into_compile_error().into(),
+	}
+}
+
+fn expand(func: ItemFn) -> Result<proc_macro2::TokenStream, Error> {
+	let ItemFn { attrs, vis, sig, block } = func;
+	if let Some(asyncness) = sig.asyncness {
+		return Err(Error::new(asyncness.span(), "#[jvm_test] functions cannot be async"));
+	}
+	if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+		return Err(Error::new(sig.generics.span(), "#[jvm_test] functions cannot be generic"));
+	}
+	if sig.inputs.len() != 1 {
+		return Err(Error::new(sig.inputs.span(), "#[jvm_test] functions take a single `JniEnv` parameter"));
+	}
+
+	let name = &sig.ident;
+	let inputs = &sig.inputs;
+	let output = &sig.output;
+	Ok(quote! {
+		#[test]
+		#(#attrs)*
+		#vis fn #name() #output {
+			fn #name(#inputs) #output #block
+			#name(::yajnir::testing::attach_test_thread())
+		}
+	})
+}